@@ -11,6 +11,10 @@ pub struct App {
     output_files: Vec<PathBuf>,
     // the output of the decompiled code
     output: Vec<Result<DecompiledCode>>,
+    // the style of the generated code
+    style: CodeStyle,
+    // annotate the statements with their bytecode, None for plain code
+    annotation: Option<Annotation>,
 }
 
 impl Default for App {
@@ -27,6 +31,8 @@ impl App {
             resources: HashMap::new(),
             output_files: Vec::new(),
             output: Vec::new(),
+            style: CodeStyle::default(),
+            annotation: None,
         }
     }
 
@@ -78,13 +84,36 @@ impl App {
         self
     }
 
+    /// 指定生成代码的风格
+    pub fn with_style(&mut self, style: CodeStyle) -> &mut Self {
+        self.style = style;
+        self
+    }
+
+    /// 在每条语句前面用注释标出它来自的字节码
+    pub fn with_annotation(&mut self, annotation: Annotation) -> &mut Self {
+        self.annotation = Some(annotation);
+        self
+    }
+
+    fn decompile(&self, code_object_map: &CodeObjectMap) -> Result<DecompiledCode> {
+        match self.annotation {
+            Some(annotation) => code_object_map.decompile_with(&AnnotatedGenerator::new(
+                code_object_map,
+                self.style.clone(),
+                annotation,
+            )),
+            None => code_object_map.decompile_with(&PythonGenerator::new(self.style.clone())),
+        }
+    }
+
     pub fn run(&mut self) -> &mut Self {
         for path in self.files.iter() {
             let code_object_map = self
                 .resources
                 .get(path)
                 .unwrap_or_else(|| panic!("[App run] resource {} not found", path.display()));
-            let decompiled_result = code_object_map.decompile();
+            let decompiled_result = self.decompile(code_object_map);
             self.output.push(decompiled_result);
         }
         self
@@ -92,7 +121,7 @@ impl App {
 
     pub fn run_once(&mut self, _stdin: String) -> &mut Self {
         let parsed_map = _stdin.parse_opcode().unwrap();
-        let decompiled_result = parsed_map.decompile();
+        let decompiled_result = self.decompile(&parsed_map);
         self.files.push(PathBuf::from("[Temp file]"));
         self.output.push(decompiled_result);
         self
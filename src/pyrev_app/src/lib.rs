@@ -83,6 +83,26 @@ pub mod prelude {
                 .required(false)
                 .value_parser(value_parser!(PathBuf)),
             )
+            .arg(
+                arg!(
+                    -l --"line-aligned" "put each statement on its line in the original source"
+                )
+                .action(ArgAction::SetTrue)
+                // the annotations are put on their own lines, which would move the statements
+                .conflicts_with_all(["annotate", "with-instructions"]),
+            )
+            .arg(
+                arg!(
+                    -a --annotate "annotate each statement with its line and bytecode offsets"
+                )
+                .action(ArgAction::SetTrue),
+            )
+            .arg(
+                arg!(
+                    --"with-instructions" "list the instructions of each statement, implies --annotate"
+                )
+                .action(ArgAction::SetTrue),
+            )
             .subcommand(
                 Command::new("test")
                     .about("test by your given python code")
@@ -114,6 +134,17 @@ pub mod prelude {
                 .unwrap_or_default()
                 .cloned()
                 .collect::<Vec<_>>();
+            let style = CodeStyle {
+                line_aligned: args.get_flag("line-aligned"),
+                ..Default::default()
+            };
+            let annotation = if args.get_flag("with-instructions") {
+                Some(Annotation::Instructions)
+            } else if args.get_flag("annotate") {
+                Some(Annotation::Offsets)
+            } else {
+                None
+            };
 
             if ifiles.is_empty() {
                 if atty::is(atty::Stream::Stdin) {
@@ -123,12 +154,23 @@ pub mod prelude {
                     // read from stdin
                     let mut buf = String::new();
                     std::io::stdin().read_to_string(&mut buf)?;
-                    App::new().run_once(buf).with_files(ofiles).output();
+                    let mut app = App::new();
+                    if let Some(annotation) = annotation {
+                        app.with_annotation(annotation);
+                    }
+                    app.with_style(style)
+                        .run_once(buf)
+                        .with_files(ofiles)
+                        .output();
                 }
             } else {
                 //dbg!(&ifiles);
                 //dbg!(&ofiles);
-                App::new()
+                let mut app = App::new();
+                if let Some(annotation) = annotation {
+                    app.with_annotation(annotation);
+                }
+                app.with_style(style)
                     .insert_resources(ifiles)
                     .with_files(ofiles)
                     .run()
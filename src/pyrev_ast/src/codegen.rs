@@ -0,0 +1,1480 @@
+use crate::*;
+
+/// 字符串常量用的引号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoteStyle {
+    /// 保持字节码里 repr 的引号
+    #[default]
+    Preserve,
+    /// 'a', 包含单引号的字符串保持不变
+    Single,
+    /// "a", 包含双引号的字符串保持不变
+    Double,
+}
+
+/// 调用和容器的最后一个元素后面的逗号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingComma {
+    /// 不加 (只有一个元素的元组除外)
+    Never,
+    /// 拆成多行的时候加
+    #[default]
+    Multiline,
+    /// 总是加
+    Always,
+}
+
+/// 生成代码的风格
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeStyle {
+    /// 每一级缩进的空格数
+    pub indent_width: usize,
+    pub quote_style: QuoteStyle,
+    /// 模块顶层的函数和类定义前后的空行数
+    pub blank_lines: usize,
+    /// 函数体和类里面的定义前后的空行数
+    pub nested_blank_lines: usize,
+    /// 写成一行超过这个长度的调用和容器会拆成每个元素一行, None 表示不拆
+    pub max_line_length: Option<usize>,
+    pub trailing_comma: TrailingComma,
+    /// 把每个语句放到它在源代码里的那一行, 这时不再按 `blank_lines` 空行
+    pub line_aligned: bool,
+}
+
+impl Default for CodeStyle {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            quote_style: QuoteStyle::Preserve,
+            blank_lines: 2,
+            nested_blank_lines: 1,
+            max_line_length: None,
+            trailing_comma: TrailingComma::Multiline,
+            line_aligned: false,
+        }
+    }
+}
+
+impl CodeStyle {
+    pub fn indent(&self) -> String {
+        " ".repeat(self.indent_width)
+    }
+}
+
+/// 从 AST 生成代码
+///
+/// 实现 [`CodeGenerator::generate`] 可以改写某些节点的输出, 其它节点交给 [`emit`],
+/// 子节点也会经过 `generate`
+pub trait CodeGenerator {
+    fn style(&self) -> &CodeStyle;
+
+    /// 生成一个表达式的代码
+    fn generate(&self, expr: &ExpressionEnum) -> Result<Vec<String>> {
+        emit(self, expr)
+    }
+
+    /// 生成整个模块的代码, 按行号对齐的时候第 i 行就是源代码的第 i + 1 行
+    fn generate_module(&self, bodys: &[ExpressionEnum]) -> Result<Vec<String>> {
+        emit_module(self, bodys)
+    }
+
+    /// 放在语句前面的注释, 每一项是一行, 不需要缩进
+    fn annotate(&self, _statement: &ExpressionEnum) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// 默认的 Python 代码生成器
+#[derive(Debug, Clone, Default)]
+pub struct PythonGenerator {
+    pub style: CodeStyle,
+}
+
+impl PythonGenerator {
+    pub fn new(style: CodeStyle) -> Self {
+        Self { style }
+    }
+}
+
+impl CodeGenerator for PythonGenerator {
+    fn style(&self) -> &CodeStyle {
+        &self.style
+    }
+}
+
+impl ExpressionEnum {
+    /// 用默认的风格生成代码
+    pub fn build(&self) -> Result<Vec<String>> {
+        self.build_with(&PythonGenerator::default())
+    }
+
+    pub fn build_with<G: CodeGenerator + ?Sized>(&self, generator: &G) -> Result<Vec<String>> {
+        Ok(self
+            .emit_with(generator)?
+            .iter()
+            .map(|code| {
+                code.split('\n')
+                    .flat_map(|line| wrap_line(generator, line))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect())
+    }
+
+    /// 还没有拆长行的代码, 子表达式都用这个, 拆行要等到知道整行多长的时候
+    fn emit_with<G: CodeGenerator + ?Sized>(&self, generator: &G) -> Result<Vec<String>> {
+        generator.generate(self)
+    }
+}
+
+/// 作为值的表达式, 例如: 赋值的值, 调用的参数
+fn build_value<G: CodeGenerator + ?Sized>(generator: &G, expr: &ExpressionEnum) -> Result<String> {
+    let code = expr.emit_with(generator)?.join("");
+    Ok(parenthesize_yield(expr, code))
+}
+
+/// 常量 None, 例如: `return` 和 `yield` 省略的值
+fn is_none(expr: &ExpressionEnum) -> bool {
+    matches!(expr, ExpressionEnum::BaseValue(none) if none.value == "None")
+}
+
+/// yield 作为子表达式的时候要加括号, 例如: print((yield x))
+fn parenthesize_yield(expr: &ExpressionEnum, code: String) -> String {
+    match expr {
+        ExpressionEnum::Yield(_) | ExpressionEnum::YieldFrom(_) => format!("({})", code),
+        _ => code,
+    }
+}
+
+/// 作为操作数的值, 优先级比 `min` 低的时候要加括号, 例如: (a + b) * c
+fn build_operand<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    expr: &ExpressionEnum,
+    min: Precedence,
+) -> Result<String> {
+    let code = build_value(generator, expr)?;
+    match expr {
+        ExpressionEnum::Yield(_) | ExpressionEnum::YieldFrom(_) => Ok(code),
+        _ if expr.precedence() < min => Ok(format!("({})", code)),
+        _ => Ok(code),
+    }
+}
+
+/// 惰性求值的值 (类型别名, 类型参数的上界和默认值) 是一个只有 return 的函数
+fn build_lazy_value<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    expr: &ExpressionEnum,
+) -> Result<String> {
+    match expr {
+        ExpressionEnum::Function(function) => {
+            let value = function
+                .bodys
+                .iter()
+                .find_map(|expr| match expr {
+                    ExpressionEnum::Return(ret) => Some(&ret.value),
+                    _ => None,
+                })
+                .ok_or(format!("No lazy value in {}", function.name))?;
+            build_value(generator, value)
+        }
+        expr => build_value(generator, expr),
+    }
+}
+
+/// 解码 repr 打印的字符串常量, 例如: 'it\'s\n' -> it's + 换行
+fn unescape_string(repr: &str) -> Result<String> {
+    let quote = repr.chars().next().ok_or("[Docstring] Empty string")?;
+    let inner = repr
+        .strip_prefix(quote)
+        .and_then(|x| x.strip_suffix(quote))
+        .ok_or(format!("[Docstring] Invalid string {}", repr))?;
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let escaped = chars
+            .next()
+            .ok_or(format!("[Docstring] Invalid string {}", repr))?;
+        let digits = match escaped {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => 0,
+        };
+        if digits == 0 {
+            text.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                c => c,
+            });
+            continue;
+        }
+        let code = chars.by_ref().take(digits).collect::<String>();
+        let c = u32::from_str_radix(&code, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(format!("[Docstring] Invalid escape in {}", repr))?;
+        text.push(c);
+    }
+    Ok(text)
+}
+
+/// 文档字符串用三引号输出, 后面几行去掉公共的缩进, 由外层重新缩进
+fn build_docstring(repr: &str) -> Result<Vec<String>> {
+    let text = unescape_string(repr)?;
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' | '\t' => escaped.push(c),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    let mut escaped = escaped.replace("\"\"\"", "\\\"\\\"\\\"");
+    if escaped.ends_with('"') {
+        escaped.pop();
+        escaped.push_str("\\\"");
+    }
+    let lines = escaped.split('\n').collect::<Vec<_>>();
+    let indent = lines
+        .iter()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or_default();
+    let mut code = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| match i {
+            0 => line.to_string(),
+            _ if line.trim().is_empty() => String::new(),
+            _ => line[indent..].to_string(),
+        })
+        .collect::<Vec<_>>();
+    code[0] = format!("\"\"\"{}", code[0]);
+    if let Some(last) = code.last_mut() {
+        last.push_str("\"\"\"");
+    }
+    Ok(code)
+}
+
+/// 类型参数列表, 例如: [T: int, *Ts, **P]
+fn build_type_params<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    type_params: &[ExpressionEnum],
+) -> Result<String> {
+    if type_params.is_empty() {
+        return Ok(String::new());
+    }
+    let mut type_params_code = Vec::new();
+    for type_param in type_params.iter() {
+        type_params_code.push(type_param.emit_with(generator)?.join(""));
+    }
+    Ok(format!("[{}]", type_params_code.join(", ")))
+}
+
+/// 函数的参数列表, 例如: a, /, b=1, *args, c, d=2, **kwargs
+fn build_arguments<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    function: &Function,
+) -> Result<String> {
+    let args = function
+        .args_iter()
+        .filter(|(name, _)| name.as_str() != "return")
+        .collect::<Vec<_>>();
+    let variadic_count = function.has_varargs as usize + function.has_varkeywords as usize;
+    let positional_count = args
+        .len()
+        .checked_sub(function.kw_only_count + variadic_count)
+        .ok_or("Invalid argument count")?;
+    let build_arg = |(name, anno): &(&String, &Option<String>)| match anno {
+        Some(anno) => format!("{}: {}", name, anno),
+        None => name.to_string(),
+    };
+    // PEP 8: `b=2`, but `b: int = 2`
+    let build_default = |arg: &(&String, &Option<String>), value: String| match arg.1 {
+        Some(_) => format!("{} = {}", build_arg(arg), value),
+        None => format!("{}={}", build_arg(arg), value),
+    };
+
+    let mut args_code = Vec::new();
+    let mut args_iter = args.iter();
+    let default_offset = positional_count.saturating_sub(function.defaults.len());
+    for i in 0..positional_count {
+        let arg = args_iter.next().ok_or("No argument! Iter error")?;
+        if i >= default_offset {
+            // 有默认值的参数
+            args_code.push(build_default(
+                arg,
+                build_value(
+                    generator,
+                    function
+                        .defaults
+                        .get(i - default_offset)
+                        .ok_or("No default! Iter error")?,
+                )?,
+            ));
+        } else {
+            args_code.push(build_arg(arg));
+        }
+        if i + 1 == function.pos_only_count {
+            args_code.push("/".to_string());
+        }
+    }
+    // *args 在 varnames 中排在仅限关键字参数之后
+    let kw_only_args = args_iter
+        .by_ref()
+        .take(function.kw_only_count)
+        .collect::<Vec<_>>();
+    if function.has_varargs {
+        let arg = args_iter.next().ok_or("No *args! Iter error")?;
+        args_code.push(format!("*{}", build_arg(arg)));
+    } else if !kw_only_args.is_empty() {
+        args_code.push("*".to_string());
+    }
+    for arg in kw_only_args {
+        let default = function
+            .kw_defaults
+            .iter()
+            .find(|keyword| keyword.arg.as_ref() == Some(arg.0));
+        match default {
+            Some(keyword) => {
+                args_code.push(build_default(arg, build_value(generator, &keyword.value)?))
+            }
+            None => args_code.push(build_arg(arg)),
+        }
+    }
+    if function.has_varkeywords {
+        let arg = args_iter.next().ok_or("No **kwargs! Iter error")?;
+        args_code.push(format!("**{}", build_arg(arg)));
+    }
+    Ok(args_code.join(", "))
+}
+
+/// 关键字参数, 例如: a=1, **b
+fn build_keywords<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    keywords: &[Keyword],
+) -> Result<Vec<String>> {
+    keywords
+        .iter()
+        .map(|keyword| {
+            let value_code = build_value(generator, &keyword.value)?;
+            match &keyword.arg {
+                Some(arg) => Ok(format!("{}={}", arg, value_code)),
+                None => Ok(format!("**{}", value_code)),
+            }
+        })
+        .collect()
+}
+
+/// 格式化字符串引号里面的部分, 例如: a={a!r:>{width}}
+fn build_format_string<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    values: &[ExpressionEnum],
+) -> Result<String> {
+    let mut code = String::new();
+    for value in values.iter() {
+        match value {
+            ExpressionEnum::FormatValue(format_value) => {
+                code.push('{');
+                code.push_str(&format_value.value.emit_with(generator)?.join(""));
+                code.push_str(match format_value.conversion {
+                    Conversion::None => "",
+                    Conversion::Str => "!s",
+                    Conversion::Repr => "!r",
+                    Conversion::Ascii => "!a",
+                });
+                if let Some(format_spec) = &format_value.format_spec {
+                    code.push(':');
+                    match format_spec.as_ref() {
+                        ExpressionEnum::Format(format) => {
+                            code.push_str(&build_format_string(generator, &format.format_values)?)
+                        }
+                        spec => code
+                            .push_str(&build_format_string(generator, std::slice::from_ref(spec))?),
+                    }
+                }
+                code.push('}');
+            }
+            _ => {
+                // the literal part is a string constant
+                let literal = value.emit_with(generator)?.join("");
+                let literal = literal
+                    .strip_prefix('\'')
+                    .and_then(|x| x.strip_suffix('\''))
+                    .or_else(|| literal.strip_prefix('"').and_then(|x| x.strip_suffix('"')))
+                    .unwrap_or(&literal);
+                code.push_str(
+                    &literal
+                        .replace('{', "{{")
+                        .replace('}', "}}")
+                        .replace('"', "\\\""),
+                );
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// 函数和类定义前后要空行, lambda 和推导式不算
+fn is_definition(expr: &ExpressionEnum) -> bool {
+    match expr {
+        ExpressionEnum::Class(_) => true,
+        ExpressionEnum::Function(function) => {
+            !function.name.starts_with('<') || function.name.starts_with("<generic parameters of ")
+        }
+        _ => false,
+    }
+}
+
+/// 按行号对齐的时候, 语句的第一行前面先标上行号, 最后在 [`align_lines`] 里去掉
+const LINE_MARK: char = '\u{e000}';
+
+/// 把生成好的语句连起来, 定义前后空 `blank_lines` 行
+fn join_statements<'a, G: CodeGenerator + ?Sized>(
+    generator: &G,
+    statements: impl IntoIterator<Item = (&'a ExpressionEnum, Vec<String>)>,
+    blank_lines: usize,
+) -> Vec<String> {
+    let line_aligned = generator.style().line_aligned;
+    let mut code: Vec<String> = Vec::new();
+    let mut last_is_definition = false;
+    // where the comments right before this statement start, they stay with a definition
+    let mut comments_start = None;
+    for (expr, mut lines) in statements {
+        // e.g. the implicit `return None`
+        if lines.is_empty() {
+            continue;
+        }
+        let is_definition = is_definition(expr);
+        if !line_aligned && !code.is_empty() && (is_definition || last_is_definition) {
+            let at = comments_start.unwrap_or(code.len());
+            // the comments after a definition are already apart from it
+            if at > 0 && !code[at - 1].is_empty() {
+                code.splice(at..at, std::iter::repeat_n(String::new(), blank_lines));
+            }
+        }
+        comments_start = match expr {
+            ExpressionEnum::Comment(_) => comments_start.or(Some(code.len())),
+            _ => None,
+        };
+        // the line of a definition is the line of its first decorator
+        let line = expr.get_start_line();
+        if line_aligned && line > 0 {
+            lines[0] = format!("{}{}{}{}", LINE_MARK, line, LINE_MARK, lines[0]);
+        }
+        code.extend(generator.annotate(expr));
+        code.extend(lines);
+        last_is_definition = is_definition;
+    }
+    code
+}
+
+/// 把标了行号的语句放到那一行, 前面用空行补齐,
+/// 和前一条语句在同一行的简单语句用 `; ` 接在后面,
+/// 其他情况下前面的代码已经占了那一行的话只能往后放, 并注释原来的行号
+fn align_lines(code: &[String]) -> Vec<String> {
+    let mut aligned: Vec<String> = Vec::new();
+    // the source line of the last line, and the comment after it if it was put after that line
+    let mut last: Option<(usize, String)> = None;
+    for line in code.iter().flat_map(|line| line.split('\n')) {
+        // the text before the first mark, then the line numbers and the text after them in turns
+        let mut parts = line.split(LINE_MARK);
+        let mut text = parts.next().unwrap_or_default().to_string();
+        let mut target = None;
+        while let (Some(number), Some(rest)) = (parts.next(), parts.next()) {
+            target = target.or(number.parse::<usize>().ok());
+            text.push_str(rest);
+        }
+        // e.g. `a = 1; b = 2`
+        let joined = match (target, last.as_ref(), aligned.last()) {
+            (Some(target), Some((line, comment)), Some(previous)) if *line == target => previous
+                .strip_suffix(comment.as_str())
+                .filter(|statement| can_join(statement, &text))
+                .map(|statement| format!("{}; {}{}", statement, text.trim_start(), comment)),
+            _ => None,
+        };
+        if let Some(joined) = joined {
+            aligned.pop();
+            aligned.push(joined);
+            continue;
+        }
+        last = None;
+        match target {
+            Some(target) if target > aligned.len() => {
+                aligned.resize(target - 1, String::new());
+                aligned.push(text);
+                last = Some((target, String::new()));
+            }
+            // a comment after the opening quotes would be a part of the docstring
+            Some(target) if !text.trim_start().starts_with("\"\"\"") => {
+                let comment = format!("  # pyrev: line {}", target);
+                aligned.push(format!("{}{}", text, comment));
+                last = Some((target, comment));
+            }
+            _ => aligned.push(text),
+        }
+    }
+    aligned
+}
+
+/// 两行能不能用 `; ` 连成一行: 同一层缩进的简单语句,
+/// 复合语句的头, 装饰器, 注释和文档字符串都不行
+fn can_join(last: &str, text: &str) -> bool {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let is_simple = |line: &str| {
+        let line = line.trim();
+        !line.is_empty()
+            && !line.ends_with(':')
+            && !["#", "@", "\"\"\"", "'''"]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+    };
+    indent(last) == indent(text) && is_simple(last) && is_simple(text)
+}
+
+/// 生成一串语句, 不缩进
+fn build_body<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    body: &[ExpressionEnum],
+    blank_lines: usize,
+) -> Result<Vec<String>> {
+    let statements = body
+        .iter()
+        .map(|expr| match expr.emit_with(generator) {
+            Ok(lines) => (expr, lines),
+            // one statement which can't be generated shouldn't fail the whole file
+            Err(err) => (expr, vec![format!("# pyrev: failed to generate: {}", err)]),
+        })
+        .collect::<Vec<_>>();
+    Ok(join_statements(generator, statements, blank_lines))
+}
+
+/// 缩进一层, 多行的表达式每一行都要缩进, 空行不缩进
+fn indent_lines<G: CodeGenerator + ?Sized>(generator: &G, lines: &[String]) -> Vec<String> {
+    let indent = generator.style().indent();
+    lines
+        .iter()
+        .map(|line| {
+            line.split('\n')
+                .map(|line| {
+                    if line.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{}{}", indent, line)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect()
+}
+
+/// 生成一个缩进的代码块, 空的代码块用 `pass` 占位
+fn build_block<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    body: &[ExpressionEnum],
+) -> Result<Vec<String>> {
+    let mut code = build_body(generator, body, generator.style().nested_blank_lines)?;
+    fill_empty_block(&mut code);
+    Ok(indent_lines(generator, &code))
+}
+
+/// 空的代码块用 `pass` 占位, 只有注释的代码块也算空的
+fn fill_empty_block(code: &mut Vec<String>) {
+    if code
+        .iter()
+        .flat_map(|line| line.split('\n'))
+        // the text after the line numbers
+        .filter_map(|line| line.rsplit(LINE_MARK).next())
+        .all(|line| line.is_empty() || line.starts_with('#'))
+    {
+        code.push("pass".to_string());
+    }
+}
+
+/// 设置了 `max_line_length` 的时候, 括号里的元素先用这几个字符标出来,
+/// 整行生成好之后再在 [`wrap_line`] 里决定拆不拆
+const SEQUENCE_START: char = '\u{e001}';
+const SEQUENCE_ITEM: char = '\u{e002}';
+const SEQUENCE_END: char = '\u{e003}';
+
+/// 括号里用逗号隔开的元素, 例如: 调用的参数, 列表
+///
+/// 写成一行太长的时候每个元素一行, `padding` 是写成一行的时候括号里面的空格
+fn build_sequence<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    brackets: (&str, &str),
+    items: &[String],
+    padding: &str,
+    single_tuple: bool,
+) -> String {
+    let style = generator.style();
+    if items.is_empty() {
+        return format!("{}{}", brackets.0, brackets.1);
+    }
+    let trailing_comma = |multiline: bool| {
+        single_tuple
+            || match style.trailing_comma {
+                TrailingComma::Never => false,
+                TrailingComma::Multiline => multiline,
+                TrailingComma::Always => true,
+            }
+    };
+    if style.max_line_length.is_none() {
+        return format!(
+            "{}{}{}{}{}{}",
+            brackets.0,
+            padding,
+            items.join(", "),
+            if trailing_comma(false) { "," } else { "" },
+            padding,
+            brackets.1
+        );
+    }
+    // the two trailing commas and the padding come first, then the items
+    let flag = |comma: bool| if comma { '1' } else { '0' };
+    format!(
+        "{}{}{}{}{}{}{}{}{}",
+        brackets.0,
+        SEQUENCE_START,
+        flag(trailing_comma(false)),
+        flag(trailing_comma(true)),
+        padding,
+        SEQUENCE_ITEM,
+        items.join(&SEQUENCE_ITEM.to_string()),
+        SEQUENCE_END,
+        brackets.1
+    )
+}
+
+/// 一行代码里的一段, 括号里的元素是 [`build_sequence`] 标出来的
+enum Piece {
+    Text(String),
+    Sequence(Sequence),
+}
+
+struct Sequence {
+    padding: String,
+    trailing_comma: bool,
+    multiline_trailing_comma: bool,
+    items: Vec<Vec<Piece>>,
+}
+
+/// 读到这一层的元素结束为止
+fn parse_pieces(chars: &mut std::iter::Peekable<std::str::Chars>) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == SEQUENCE_ITEM || c == SEQUENCE_END {
+            break;
+        }
+        chars.next();
+        if c != SEQUENCE_START {
+            text.push(c);
+            continue;
+        }
+        pieces.push(Piece::Text(std::mem::take(&mut text)));
+        let trailing_comma = chars.next() == Some('1');
+        let multiline_trailing_comma = chars.next() == Some('1');
+        let padding = chars.by_ref().take_while(|&c| c != SEQUENCE_ITEM).collect();
+        let mut items = Vec::new();
+        loop {
+            items.push(parse_pieces(chars));
+            if chars.next() != Some(SEQUENCE_ITEM) {
+                break;
+            }
+        }
+        pieces.push(Piece::Sequence(Sequence {
+            padding,
+            trailing_comma,
+            multiline_trailing_comma,
+            items,
+        }));
+    }
+    pieces.push(Piece::Text(text));
+    pieces
+}
+
+/// 写成一行
+fn flatten_pieces(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => text.clone(),
+            Piece::Sequence(sequence) => format!(
+                "{}{}{}{}",
+                sequence.padding,
+                sequence
+                    .items
+                    .iter()
+                    .map(|item| flatten_pieces(item))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if sequence.trailing_comma { "," } else { "" },
+                sequence.padding
+            ),
+        })
+        .collect()
+}
+
+/// 一行的长度, 不算 [`LINE_MARK`] 标的行号
+fn line_width(line: &str) -> usize {
+    line.split(LINE_MARK)
+        .step_by(2)
+        .map(|text| text.chars().count())
+        .sum()
+}
+
+/// 整行超过 `max_line_length` 的时候, 把第一个括号里的元素拆成每个元素一行,
+/// 拆出来的元素还太长就接着拆
+fn wrap_line<G: CodeGenerator + ?Sized>(generator: &G, line: &str) -> Vec<String> {
+    let text = line.trim_start_matches(' ');
+    let indent = &line[..line.len() - text.len()];
+    wrap_pieces(
+        generator,
+        indent,
+        &parse_pieces(&mut text.chars().peekable()),
+    )
+}
+
+fn wrap_pieces<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    indent: &str,
+    pieces: &[Piece],
+) -> Vec<String> {
+    let line = format!("{}{}", indent, flatten_pieces(pieces));
+    let sequence = pieces
+        .iter()
+        .position(|piece| matches!(piece, Piece::Sequence(_)));
+    let (Some(max), Some(index)) = (generator.style().max_line_length, sequence) else {
+        return vec![line];
+    };
+    let Piece::Sequence(sequence) = &pieces[index] else {
+        unreachable!()
+    };
+    if line_width(&line) <= max {
+        return vec![line];
+    }
+    let item_indent = format!("{}{}", indent, generator.style().indent());
+    let mut lines = vec![format!("{}{}", indent, flatten_pieces(&pieces[..index]))];
+    for item in sequence.items.iter() {
+        let start = lines.len();
+        lines.extend(wrap_pieces(generator, &item_indent, item));
+        // the item may be split itself, the comma goes after its last line
+        if start < lines.len() {
+            lines.last_mut().unwrap().push(',');
+        }
+    }
+    if !sequence.multiline_trailing_comma {
+        lines.last_mut().map(String::pop);
+    }
+    // e.g. `f(a)(b)`, the brackets after it can be too long too
+    lines.extend(wrap_pieces(generator, indent, &pieces[index + 1..]));
+    lines
+}
+
+/// 把字符串常量换成 `quote` 引号, 里面有 `quote` 的字符串保持不变, 例如: 'a' -> "a"
+fn requote(code: &str, quote: char) -> String {
+    let chars = code.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(code.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c != '\'' && c != '"' {
+            result.push(c);
+            i += 1;
+            continue;
+        }
+        let mut end = i + 1;
+        while end < chars.len() && chars[end] != c {
+            end += if chars[end] == '\\' { 2 } else { 1 };
+        }
+        let end = end.min(chars.len() - 1);
+        let literal = chars[i..=end].iter().collect::<String>();
+        let is_raw = result
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .any(|c| c == 'r' || c == 'R');
+        result.push_str(&requote_literal(&literal, quote, is_raw).unwrap_or(literal));
+        i = end + 1;
+    }
+    result
+}
+
+fn requote_literal(literal: &str, quote: char, is_raw: bool) -> Option<String> {
+    let old = literal.chars().next()?;
+    if old == quote {
+        return Some(literal.to_string());
+    }
+    let inner = literal.strip_prefix(old)?.strip_suffix(old)?;
+    if is_raw && inner.contains('\\') {
+        return None;
+    }
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next()?;
+                if escaped != old {
+                    text.push('\\');
+                }
+                text.push(escaped);
+            }
+            c if c == quote => return None,
+            c => text.push(c),
+        }
+    }
+    Some(format!("{}{}{}", quote, text, quote))
+}
+
+/// 装饰器, 例如: @property
+fn build_decorators<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    decorators: &[ExpressionEnum],
+) -> Result<Vec<String>> {
+    decorators
+        .iter()
+        .map(|decorator| Ok(format!("@{}", decorator.emit_with(generator)?.join(""))))
+        .collect()
+}
+
+/// 生成整个模块的代码, 是 [`CodeGenerator::generate_module`] 的默认实现
+pub fn emit_module<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    bodys: &[ExpressionEnum],
+) -> Result<Vec<String>> {
+    let code = build_body(generator, bodys, generator.style().blank_lines)?
+        .iter()
+        .flat_map(|line| line.split('\n'))
+        .flat_map(|line| wrap_line(generator, line))
+        .collect::<Vec<_>>();
+    if generator.style().line_aligned {
+        return Ok(align_lines(&code));
+    }
+    Ok(code
+        .iter()
+        .flat_map(|line| line.split('\n'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// 递归遍历表达式树, 生成代码
+pub fn emit<G: CodeGenerator + ?Sized>(
+    generator: &G,
+    expr: &ExpressionEnum,
+) -> Result<Vec<String>> {
+    match expr {
+        ExpressionEnum::Class(class) => {
+            let mut code = build_decorators(generator, &class.decorators)?;
+            let mut bases_code = Vec::new();
+            for base in class.bases.iter() {
+                bases_code.push(base.emit_with(generator)?.join(""));
+            }
+            bases_code.extend(build_keywords(generator, &class.keywords)?);
+            let name = format!(
+                "{}{}",
+                class.name,
+                build_type_params(generator, &class.type_params)?
+            );
+            if bases_code.is_empty() {
+                code.push(format!("class {}:", name));
+            } else {
+                code.push(format!("class {}({}):", name, bases_code.join(", ")));
+            }
+
+            let filter_members = [
+                "__module__",
+                "__qualname__",
+                "__firstlineno__",
+                "__classdict__",
+                "__type_params__",
+            ];
+            // expect to skip the __module__ and __qualname__ assignment
+            let skipped = class
+                .members
+                .iter()
+                .take_while(|expr| match expr {
+                    ExpressionEnum::Assign(assign) => matches!(
+                        assign.target.as_ref(),
+                        ExpressionEnum::BaseValue(name)
+                            if filter_members.contains(&name.value.as_str())
+                    ),
+                    _ => false,
+                })
+                .count();
+            // it may be an empty class, or only the implicit `return None` is left
+            code.extend(build_block(generator, &class.members[skipped..])?);
+            Ok(code)
+        }
+        ExpressionEnum::Function(function) => {
+            let mut code = Vec::new();
+            let args_code = build_arguments(generator, function)?;
+            let ret_code = function
+                .args
+                .iter()
+                .find(|arg| arg.name == "return")
+                .map(|arg| format!(" -> {}", arg.annotation.as_deref().unwrap_or("None")))
+                .unwrap_or_default();
+            match function.name.as_str() {
+                // the body of a lambda or a comprehension is a part of an expression,
+                // the stub of the code object which failed to decompile can't be put there
+                "<lambda>" | "<listcomp>"
+                    if matches!(function.bodys.first(), Some(ExpressionEnum::Comment(_))) =>
+                {
+                    return Err(format!("{} failed to decompile", function.name).into());
+                }
+                "<lambda>" => {
+                    let lambda_args = args_code;
+                    let lambda_body = match function.bodys.first().ok_or("No lambda body")? {
+                        ExpressionEnum::Return(ret) => &ret.value,
+                        body => body,
+                    };
+                    let lambda_body = build_value(generator, lambda_body)?;
+                    #[cfg(debug_assertions)]
+                    {
+                        // dbg!(&lambda_body);
+                    }
+                    code.push(format!("lambda {}: {}", lambda_args, lambda_body));
+                }
+                // 3.12 的泛型定义被包在这个函数里, 合并之后 bodys 就是那个定义
+                name if name.starts_with("<generic parameters of ") => {
+                    for expr in function.bodys.iter() {
+                        code.extend(expr.emit_with(generator)?);
+                    }
+                }
+                "<listcomp>" => {
+                    let [element, iter, ..] = function.bodys.as_slice() else {
+                        return Err("No listcomp body".into());
+                    };
+                    code.push(format!(
+                        "[{} for {} in {}]",
+                        element.emit_with(generator)?.join(""),
+                        args_code,
+                        iter.emit_with(generator)?.join(""),
+                    ));
+                }
+                _ => {
+                    #[cfg(debug_assertions)]
+                    {
+                        //dbg!(&args_code);
+                    }
+                    let name = format!(
+                        "{}{}",
+                        function.name,
+                        build_type_params(generator, &function.type_params)?
+                    );
+                    let first_line = if function.is_async {
+                        format!("async def {}({}){}:", name, args_code, ret_code)
+                    } else {
+                        format!("def {}({}){}:", name, args_code, ret_code)
+                    };
+                    code.extend(build_decorators(generator, &function.decorators)?);
+                    code.push(first_line);
+                    code.extend(build_block(generator, &function.bodys)?);
+                }
+            }
+            Ok(code)
+        }
+        ExpressionEnum::FastVariable(fast_var) => {
+            if fast_var.name == "0" {
+                Ok(vec![])
+            } else {
+                Ok(vec![fast_var.name.clone()])
+            }
+        }
+        ExpressionEnum::Return(r) => {
+            if is_none(&r.value) {
+                Ok(vec!["return".to_string()])
+            } else {
+                Ok(vec![format!(
+                    "return {}",
+                    build_value(generator, &r.value)?
+                )])
+            }
+        }
+        ExpressionEnum::Yield(y) => {
+            if is_none(&y.value) {
+                Ok(vec!["yield".to_string()])
+            } else {
+                Ok(vec![format!(
+                    "yield {}",
+                    y.value.emit_with(generator)?.join("")
+                )])
+            }
+        }
+        ExpressionEnum::YieldFrom(y) => Ok(vec![format!(
+            "yield from {}",
+            build_value(generator, &y.value)?
+        )]),
+        ExpressionEnum::Assign(a) => {
+            let mut code = Vec::new();
+            let mut target_code = a
+                .chain
+                .iter()
+                .map(|target| Ok(target.emit_with(generator)?.join("")))
+                .collect::<Result<Vec<_>>>()?;
+            target_code.push(a.target.emit_with(generator)?.join(""));
+            let value_code = a.values.emit_with(generator)?;
+            code.push(format!(
+                "{} {} {}",
+                target_code.join(" = "),
+                a.operator,
+                value_code.join("")
+            ));
+            Ok(code)
+        }
+        ExpressionEnum::Docstring(docstring) => build_docstring(&docstring.value),
+        ExpressionEnum::Comment(comment) => Ok(comment
+            .lines
+            .iter()
+            .map(|line| format!("# {}", line).trim_end().to_string())
+            .collect()),
+        ExpressionEnum::NamedExpr(named) => Ok(vec![format!(
+            "({} := {})",
+            named.target.emit_with(generator)?.join(""),
+            build_value(generator, &named.value)?
+        )]),
+        ExpressionEnum::AnnAssign(a) => {
+            let mut code = format!(
+                "{}: {}",
+                a.target.emit_with(generator)?.join(""),
+                a.annotation.emit_with(generator)?.join("")
+            );
+            if let Some(value) = &a.value {
+                code.push_str(&format!(" = {}", build_value(generator, value)?));
+            }
+            Ok(vec![code])
+        }
+        ExpressionEnum::TypeAlias(alias) => Ok(vec![format!(
+            "type {}{} = {}",
+            alias.name,
+            build_type_params(generator, &alias.type_params)?,
+            build_lazy_value(generator, &alias.value)?
+        )]),
+        ExpressionEnum::TypeParam(param) => {
+            let mut code = match param.kind {
+                TypeParamKind::TypeVar => param.name.clone(),
+                TypeParamKind::TypeVarTuple => format!("*{}", param.name),
+                TypeParamKind::ParamSpec => format!("**{}", param.name),
+            };
+            if let Some(bound) = &param.bound {
+                code.push_str(&format!(": {}", build_lazy_value(generator, bound)?));
+            }
+            if let Some(default_value) = &param.default_value {
+                code.push_str(&format!(
+                    " = {}",
+                    build_lazy_value(generator, default_value)?
+                ));
+            }
+            Ok(vec![code])
+        }
+        ExpressionEnum::Global(global) => Ok(vec![format!("global {}", global.names.join(", "))]),
+        ExpressionEnum::Nonlocal(nonlocal) => {
+            Ok(vec![format!("nonlocal {}", nonlocal.names.join(", "))])
+        }
+        ExpressionEnum::Delete(delete) => {
+            let targets_code = delete
+                .targets
+                .iter()
+                .map(|target| Ok(target.emit_with(generator)?.join("")))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(vec![format!("del {}", targets_code.join(", "))])
+        }
+        ExpressionEnum::Alias(alias) => {
+            let target_code = alias.target.emit_with(generator)?.join("");
+            let alias_code = alias.alias.emit_with(generator)?.join("");
+            Ok(vec![format!("{} as {}", target_code, alias_code)])
+        }
+        ExpressionEnum::Try(try_expr) => {
+            let mut code = vec!["try:".to_string()];
+            code.extend(build_block(generator, &try_expr.body)?);
+            for expr in try_expr.except.iter() {
+                code.extend(expr.emit_with(generator)?);
+            }
+            if !try_expr.or_else.is_empty() {
+                code.push("else:".to_string());
+                code.extend(build_block(generator, &try_expr.or_else)?);
+            }
+            if let Some(finally) = try_expr.finally.as_ref() {
+                code.extend(finally.emit_with(generator)?);
+            }
+            Ok(code)
+        }
+        ExpressionEnum::Except(except) => {
+            let exception_code = except.exception.emit_with(generator)?.join("");
+            let keyword = if except.is_star { "except*" } else { "except" };
+            let mut code = Vec::new();
+            if exception_code.is_empty() {
+                code.push(format!("{}:", keyword));
+            } else {
+                code.push(format!("{} {}:", keyword, exception_code));
+            }
+            code.extend(build_block(generator, &except.body)?);
+            Ok(code)
+        }
+        ExpressionEnum::Finally(finally) => {
+            let mut code = vec!["finally:".to_string()];
+            code.extend(build_block(generator, &finally.body)?);
+            Ok(code)
+        }
+        ExpressionEnum::Assert(assert) => {
+            let test_code = assert.test.emit_with(generator)?.join("");
+            match &assert.msg {
+                Some(msg) => {
+                    let msg_code = msg.emit_with(generator)?.join("");
+                    Ok(vec![format!("assert {}, {}", test_code, msg_code)])
+                }
+                None => Ok(vec![format!("assert {}", test_code)]),
+            }
+        }
+        ExpressionEnum::Raise(raise) => match (&raise.exception, &raise.cause) {
+            (Some(exception), Some(cause)) => Ok(vec![format!(
+                "raise {} from {}",
+                exception.emit_with(generator)?.join(""),
+                build_value(generator, cause)?
+            )]),
+            (Some(exception), None) => Ok(vec![format!(
+                "raise {}",
+                exception.emit_with(generator)?.join("")
+            )]),
+            (None, _) => Ok(vec!["raise".to_string()]),
+        },
+        ExpressionEnum::Await(await_expr) => {
+            let awaitable_code =
+                build_operand(generator, &await_expr.awaitable_expr, Precedence::Primary)?;
+            Ok(vec![format!("await {}", awaitable_code)])
+        }
+        ExpressionEnum::BaseValue(base_value) => match generator.style().quote_style {
+            QuoteStyle::Preserve => Ok(vec![base_value.value.clone()]),
+            QuoteStyle::Single => Ok(vec![requote(&base_value.value, '\'')]),
+            QuoteStyle::Double => Ok(vec![requote(&base_value.value, '"')]),
+        },
+        ExpressionEnum::Call(call) => {
+            let func_code = build_operand(generator, &call.func, Precedence::Primary)?;
+            let mut args_code = Vec::new();
+            for arg in call.args.iter() {
+                let arg_code = arg.emit_with(generator)?;
+                args_code.push(parenthesize_yield(arg, arg_code.join("")));
+            }
+            args_code.extend(build_keywords(generator, &call.keywords)?);
+            while args_code.last().is_some_and(String::is_empty) {
+                args_code.pop();
+            }
+            Ok(vec![format!(
+                "{}{}",
+                func_code,
+                build_sequence(generator, ("(", ")"), &args_code, "", false)
+            )])
+        }
+        ExpressionEnum::Starred(starred) => {
+            let value_code = build_operand(generator, &starred.value, Precedence::BitOr)?;
+            if starred.double_star {
+                Ok(vec![format!("**{}", value_code)])
+            } else {
+                Ok(vec![format!("*{}", value_code)])
+            }
+        }
+        ExpressionEnum::FormatValue(_) => Ok(vec![format!(
+            "f\"{}\"",
+            build_format_string(generator, std::slice::from_ref(expr))?
+        )]),
+        ExpressionEnum::Format(format) => Ok(vec![format!(
+            "f\"{}\"",
+            build_format_string(generator, &format.format_values)?
+        )]),
+        ExpressionEnum::BinaryOperation(binary_operation) => {
+            let precedence = Precedence::of_operator(&binary_operation.operator);
+            let (left, right) = match precedence {
+                // in-place operators, e.g. a += b
+                Precedence::Lowest => (Precedence::Lowest, Precedence::Lowest),
+                // comparisons chain rather than associate, e.g. (a < b) < c
+                Precedence::Compare => (Precedence::BitOr, Precedence::BitOr),
+                // right-associative and binds less tightly than a unary operator on its right,
+                // e.g. (-a) ** -b
+                Precedence::Power => (Precedence::Await, Precedence::Unary),
+                // left-associative, e.g. a - (b - c)
+                precedence => (precedence, precedence.next()),
+            };
+            Ok(vec![format!(
+                "{} {} {}",
+                build_operand(generator, &binary_operation.left, left)?,
+                binary_operation.operator,
+                build_operand(generator, &binary_operation.right, right)?
+            )])
+        }
+        ExpressionEnum::BoolOp(bool_op) => {
+            // `or` has lower priority than `and`, e.g. (a or b) and c
+            let precedence = Precedence::of_operator(&bool_op.operator);
+            let values_code = bool_op
+                .values
+                .iter()
+                .map(|value| build_operand(generator, value, precedence))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(vec![values_code.join(&format!(" {} ", bool_op.operator))])
+        }
+        ExpressionEnum::Compare(compare) => {
+            let mut code = build_operand(generator, &compare.left, Precedence::BitOr)?;
+            for (operator, comparator) in compare.operators.iter().zip(compare.comparators.iter()) {
+                code.push_str(&format!(
+                    " {} {}",
+                    operator,
+                    build_operand(generator, comparator, Precedence::BitOr)?
+                ));
+            }
+            Ok(vec![code])
+        }
+        ExpressionEnum::IfExp(if_exp) => Ok(vec![format!(
+            "{} if {} else {}",
+            build_operand(generator, &if_exp.body, Precedence::Or)?,
+            build_operand(generator, &if_exp.test, Precedence::Or)?,
+            build_operand(generator, &if_exp.or_else, Precedence::IfExp)?
+        )]),
+        ExpressionEnum::UnaryOperation(unary_operation) => {
+            let (operator, precedence) = match unary_operation.unary_type {
+                UnaryType::Negative => ("-", Precedence::Unary),
+                UnaryType::Invert => ("~", Precedence::Unary),
+                UnaryType::Not => ("not ", Precedence::Not),
+                UnaryType::Positive => unreachable!(),
+            };
+            Ok(vec![format!(
+                "{}{}",
+                operator,
+                build_operand(generator, &unary_operation.target, precedence)?
+            )])
+        }
+        ExpressionEnum::Import(import) => match &import.names {
+            Some(names) => {
+                let names_code = names
+                    .iter()
+                    .map(|name| Ok(name.emit_with(generator)?.join("")))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(vec![format!(
+                    "from {}{} import {}",
+                    ".".repeat(import.level),
+                    import.module,
+                    names_code.join(", ")
+                )])
+            }
+            None => match &import.alias {
+                Some(alias) => Ok(vec![format!("import {} as {}", import.module, alias)]),
+                None => Ok(vec![format!("import {}", import.module)]),
+            },
+        },
+        ExpressionEnum::Container(container) => {
+            let mut code = Vec::new();
+            let mut values_code = Vec::new();
+            for value in container.values.iter() {
+                let mut value_code = value.emit_with(generator)?;
+                values_code.append(&mut value_code);
+            }
+            match container.container_type {
+                ContainerType::List => {
+                    code.push(build_sequence(
+                        generator,
+                        ("[", "]"),
+                        &values_code,
+                        "",
+                        false,
+                    ));
+                }
+                ContainerType::Tuple => {
+                    let single_tuple = values_code.len() == 1;
+                    code.push(build_sequence(
+                        generator,
+                        ("(", ")"),
+                        &values_code,
+                        "",
+                        single_tuple,
+                    ));
+                }
+                ContainerType::Set => {
+                    code.push(build_sequence(
+                        generator,
+                        ("{", "}"),
+                        &values_code,
+                        " ",
+                        false,
+                    ));
+                }
+                ContainerType::Dict => {
+                    let mut dict_code = Vec::new();
+                    let mut i = 0;
+                    while i < values_code.len() {
+                        // `**a` takes up only one place
+                        if matches!(
+                            container.values.get(i),
+                            Some(ExpressionEnum::Starred(Starred {
+                                double_star: true,
+                                ..
+                            }))
+                        ) {
+                            dict_code.push(values_code[i].clone());
+                            i += 1;
+                        } else {
+                            dict_code.push(format!(
+                                "{}: {}",
+                                values_code[i],
+                                values_code.get(i + 1).ok_or("[Dict] Missing value")?
+                            ));
+                            i += 2;
+                        }
+                    }
+                    code.push(build_sequence(
+                        generator,
+                        ("{", "}"),
+                        &dict_code,
+                        " ",
+                        false,
+                    ));
+                }
+            }
+            Ok(code)
+        }
+        ExpressionEnum::Subscr(subscr) => {
+            let index_code = match subscr.index.as_ref() {
+                // a[1, :] rather than a[(1, :)]
+                ExpressionEnum::Container(Container {
+                    values,
+                    container_type: ContainerType::Tuple,
+                    ..
+                }) if !values.is_empty() => {
+                    let values_code = values
+                        .iter()
+                        .map(|value| Ok(value.emit_with(generator)?.join("")))
+                        .collect::<Result<Vec<_>>>()?;
+                    if values_code.len() == 1 {
+                        format!("{},", values_code[0])
+                    } else {
+                        values_code.join(", ")
+                    }
+                }
+                index => index.emit_with(generator)?.join(""),
+            };
+            let target_code = build_operand(generator, &subscr.target, Precedence::Primary)?;
+            Ok(vec![format!("{}[{}]", target_code, index_code)])
+        }
+        ExpressionEnum::Slice(slice) => {
+            // the omitted bounds are None, e.g. a[::2] is a[None:None:2]
+            let mut slice_code = slice
+                .slice
+                .iter()
+                .map(|s| {
+                    let code = s.emit_with(generator)?.join("");
+                    Ok(if code == "None" { String::new() } else { code })
+                })
+                .collect::<Result<Vec<String>>>()?;
+            if slice_code.len() == 3 && slice_code[2].is_empty() {
+                slice_code.pop();
+            }
+            Ok(vec![slice_code.join(":")])
+        }
+        ExpressionEnum::Attribute(attribute) => {
+            let parent_code = build_operand(generator, &attribute.parent, Precedence::Primary)?;
+            // `1.real` is read as the float `1.`, e.g. (1).real
+            let parent_code = match attribute.parent.as_ref() {
+                ExpressionEnum::BaseValue(_) if parent_code.parse::<i128>().is_ok() => {
+                    format!("({})", parent_code)
+                }
+                _ => parent_code,
+            };
+            Ok(vec![format!(
+                "{}.{}",
+                parent_code,
+                attribute.attr.emit_with(generator)?.join("")
+            )])
+        }
+        ExpressionEnum::With(with) => {
+            let items_code = with
+                .items
+                .iter()
+                .map(|item| {
+                    let context_code = build_value(generator, &item.context_expr)?;
+                    match &item.target {
+                        Some(target) => Ok(format!(
+                            "{} as {}",
+                            context_code,
+                            target.emit_with(generator)?.join("")
+                        )),
+                        None => Ok(context_code),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join(", ");
+            let mut code = Vec::new();
+            let first_line = if with.is_async {
+                format!("async with {}:", items_code)
+            } else {
+                format!("with {}:", items_code)
+            };
+            code.push(first_line);
+            code.extend(build_block(generator, &with.body)?);
+            Ok(code)
+        }
+        ExpressionEnum::If(if_else) => {
+            let mut code = Vec::new();
+            if let Some(test) = if_else.test.as_ref() {
+                let test_code = test.emit_with(generator)?.join("");
+                code.push(format!("if {}:", test_code));
+            } else {
+                code.push("else:".to_string());
+            }
+
+            let statements = if_else
+                .body
+                .iter()
+                .map(|expr| match expr {
+                    ExpressionEnum::Jump(jump) if jump.is_backward => {
+                        Ok((expr, vec!["continue".to_string()]))
+                    }
+                    ExpressionEnum::Jump(_) => Ok((expr, vec![])),
+                    expr => Ok((expr, expr.emit_with(generator)?)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let mut body_code =
+                join_statements(generator, statements, generator.style().nested_blank_lines);
+            // e.g. the body was only the implicit `return None`
+            fill_empty_block(&mut body_code);
+            code.extend(indent_lines(generator, &body_code));
+
+            if let Some(or_else) = if_else.or_else.as_ref() {
+                let or_else_code = or_else.emit_with(generator)?;
+                // dbg!(&or_else_code);
+                if or_else_code[0].starts_with("if ") {
+                    // elif
+                    code.push(format!("el{}", or_else_code[0]));
+                    code.extend(or_else_code.into_iter().skip(1));
+                } else {
+                    // starts with "else:"
+                    code.extend(or_else_code);
+                }
+            }
+
+            Ok(code)
+        }
+        ExpressionEnum::For(for_expr) => {
+            let iter_code = for_expr.iterator.emit_with(generator)?.join("");
+            let item_code = for_expr.items.emit_with(generator)?.join("");
+            let mut code = Vec::new();
+            let first_line = if for_expr.is_async {
+                format!("async for {} in {}:", item_code, iter_code)
+            } else {
+                format!("for {} in {}:", item_code, iter_code)
+            };
+            code.push(first_line);
+            code.extend(build_block(generator, &for_expr.body)?);
+            Ok(code)
+        }
+        _ => Ok(vec![]),
+    }
+}
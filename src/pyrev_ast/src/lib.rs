@@ -1,8 +1,10 @@
-#![feature(concat_idents)]
-
+mod codegen;
+mod precedence;
 mod query;
 mod querymutable;
 
+pub use codegen::*;
+pub use precedence::*;
 pub use pyrev_ast_derive::*;
 pub use query::*;
 pub use querymutable::*;
@@ -13,10 +15,16 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 pub trait Expression {}
 
 /// 导入
+/// 例如: import a.b as c, from ..a import b as c, d
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Import {
+    /// 点分的模块路径, `from . import x` 时为空
     pub module: String,
-    pub bk_module: Option<String>,
+    /// 相对导入的层数, 例如 `from ..a import b` 是 2
+    pub level: usize,
+    /// from 导入的名字 (BaseValue 或 Alias), None 表示没有 from
+    pub names: Option<Vec<ExpressionEnum>>,
+    /// IMPORT_FROM 取出但还没有保存的名字
     pub fragment: Option<String>,
     pub alias: Option<String>,
     pub start_line: usize,
@@ -30,6 +38,12 @@ pub struct Class {
     pub mark: String,
     pub name: String,
     pub members: Vec<ExpressionEnum>,
+    pub bases: Vec<ExpressionEnum>,
+    /// 例如: metaclass=Meta
+    pub keywords: Vec<Keyword>,
+    /// 类型参数, 例如: class C[T]
+    pub type_params: Vec<ExpressionEnum>,
+    pub decorators: Vec<ExpressionEnum>,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
@@ -51,9 +65,21 @@ pub struct FastVariable {
 pub struct Function {
     pub mark: String,
     pub name: String,
+    /// 按顺序: 普通参数, 仅限关键字参数, *args, **kwargs
     pub args: Vec<FastVariable>,
     pub bodys: Vec<ExpressionEnum>,
-    pub defaults: Vec<String>,
+    pub defaults: Vec<ExpressionEnum>,
+    /// 仅限关键字参数的默认值
+    pub kw_defaults: Vec<Keyword>,
+    /// 类型参数, 例如: def f[T](x: T)
+    pub type_params: Vec<ExpressionEnum>,
+    /// `/` 之前的参数个数
+    pub pos_only_count: usize,
+    /// `*` 之后的参数个数
+    pub kw_only_count: usize,
+    pub has_varargs: bool,
+    pub has_varkeywords: bool,
+    pub decorators: Vec<ExpressionEnum>,
     pub is_async: bool,
     pub start_line: usize,
     pub end_line: usize,
@@ -78,10 +104,21 @@ pub struct Yield {
     pub end_offset: usize,
 }
 
+/// yield from
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct YieldFrom {
+    pub value: Box<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
 /// 赋值
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Assign {
     pub target: Box<ExpressionEnum>,
+    /// 链式赋值里前面的目标, 例如: a = b = 1 里的 a
+    pub chain: Vec<ExpressionEnum>,
     pub values: Box<ExpressionEnum>,
     pub operator: String,
     pub start_line: usize,
@@ -89,6 +126,105 @@ pub struct Assign {
     pub end_offset: usize,
 }
 
+/// 文档字符串, value 是 dis 打印的字符串常量
+/// 例如: """Docstring."""
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Docstring {
+    pub value: String,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 注释, 每一项是一行, 不带 `#`
+/// 例如: 反编译失败的函数体里的反汇编
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Comment {
+    pub lines: Vec<String>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 赋值表达式(海象运算符)
+/// 例如: (n := len(x))
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct NamedExpr {
+    pub target: Box<ExpressionEnum>,
+    pub value: Box<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 带注解的赋值
+/// 例如: x: int = 1, 没有值的时候是 x: int
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct AnnAssign {
+    pub target: Box<ExpressionEnum>,
+    pub annotation: Box<ExpressionEnum>,
+    pub value: Option<Box<ExpressionEnum>>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 类型别名
+/// 例如: type Pair[K, V] = tuple[K, V]
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct TypeAlias {
+    pub name: String,
+    pub type_params: Vec<ExpressionEnum>,
+    /// 值是惰性求值的, 所以是一个函数
+    pub value: Box<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 类型参数, 例如: T: int, *Ts, **P, T = int
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct TypeParam {
+    pub name: String,
+    pub kind: TypeParamKind,
+    /// 上界或者约束, 和默认值一样是惰性求值的函数
+    pub bound: Option<Box<ExpressionEnum>>,
+    pub default_value: Option<Box<ExpressionEnum>>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// global 声明
+/// 例如: global a, b
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Global {
+    pub names: Vec<String>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// nonlocal 声明
+/// 例如: nonlocal a, b
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Nonlocal {
+    pub names: Vec<String>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 删除
+/// 例如: del a, b[0], c.x
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Delete {
+    pub targets: Vec<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
 /// Alias, like Assign but only for `as`
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Alias {
@@ -105,7 +241,9 @@ pub struct Try {
     pub body: Vec<ExpressionEnum>,
     /// this is the exception which will be caught
     pub except: Vec<ExpressionEnum>,
-    pub finally: Box<ExpressionEnum>,
+    /// the `else` block, runs when no exception is raised
+    pub or_else: Vec<ExpressionEnum>,
+    pub finally: Option<Box<ExpressionEnum>>,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
@@ -114,8 +252,11 @@ pub struct Try {
 /// Except
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Except {
+    /// empty for a bare `except:`
     pub exception: Box<ExpressionEnum>,
     pub body: Vec<ExpressionEnum>,
+    /// `except*`
+    pub is_star: bool,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
@@ -143,15 +284,23 @@ pub struct Assert {
 /// 抛出异常
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Raise {
-    pub exception: Box<ExpressionEnum>,
+    /// 没有的时候是重新抛出当前的异常, 例如: raise
+    pub exception: Option<Box<ExpressionEnum>>,
+    /// 例如: raise ValueError from e
+    pub cause: Option<Box<ExpressionEnum>>,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
 }
 
+/// 格式化字符串中的一个值
+/// 例如: f"{a!r:>{width}}" 中的 `a!r:>{width}`
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct FormatValue {
     pub value: Box<ExpressionEnum>,
+    pub conversion: Conversion,
+    /// 格式说明, 是字符串常量或者(包含嵌套值的)格式化字符串
+    pub format_spec: Option<Box<ExpressionEnum>>,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
@@ -178,6 +327,41 @@ pub struct BinaryOperation {
     pub end_offset: usize,
 }
 
+/// 布尔运算
+/// `and` / `or` 的短路求值, 例如: a and b or c
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct BoolOp {
+    pub operator: String,
+    pub values: Vec<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 链式比较
+/// 例如: 0 < x < 10
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Compare {
+    pub left: Box<ExpressionEnum>,
+    pub operators: Vec<String>,
+    pub comparators: Vec<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 条件表达式
+/// 例如: x if cond else y
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct IfExp {
+    pub test: Box<ExpressionEnum>,
+    pub body: Box<ExpressionEnum>,
+    pub or_else: Box<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
 /// 下标
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Subscr {
@@ -202,6 +386,41 @@ pub struct UnaryOperation {
 pub struct Call {
     pub func: Box<ExpressionEnum>,
     pub args: Vec<ExpressionEnum>,
+    pub keywords: Vec<Keyword>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 关键字参数
+/// 例如: f(a=1), 如果 arg 为 None 则是 f(**a)
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Keyword {
+    pub arg: Option<String>,
+    pub value: Box<ExpressionEnum>,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// 解包
+/// 例如: *a, **a
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct Starred {
+    pub value: Box<ExpressionEnum>,
+    pub double_star: bool,
+    pub start_line: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// with 语句中的一项
+/// 例如: with a() as x, b(): 中的 `a() as x` 和 `b()`
+#[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
+pub struct WithItem {
+    pub context_expr: Box<ExpressionEnum>,
+    /// `as` 之后的赋值目标, 可以是名字, 属性, 下标或者(嵌套的)元组
+    pub target: Option<Box<ExpressionEnum>>,
     pub start_line: usize,
     pub start_offset: usize,
     pub end_offset: usize,
@@ -209,7 +428,7 @@ pub struct Call {
 
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct With {
-    pub item: Box<ExpressionEnum>,
+    pub items: Vec<WithItem>,
     pub body: Vec<ExpressionEnum>,
     pub is_async: bool,
     pub start_line: usize,
@@ -282,10 +501,11 @@ pub struct Attribute {
     pub end_offset: usize,
 }
 
-/// 切片
+/// 切片, 作为Subscr的index
+/// 例如: 1:2, ::2
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct Slice {
-    pub origin: Box<ExpressionEnum>,
+    /// start, stop 和可选的 step
     pub slice: Vec<ExpressionEnum>,
     pub start_line: usize,
     pub start_offset: usize,
@@ -301,7 +521,7 @@ pub struct BaseValue {
     pub end_offset: usize,
 }
 
-/// Deprecated
+// Deprecated
 /* /// None
 #[derive(Expression, Clone, Debug, PartialEq, Eq, Query, Default)]
 pub struct NoneValue {
@@ -322,7 +542,17 @@ pub enum ExpressionEnum {
     Function(Function),
     Return(Return),
     Yield(Yield),
+    YieldFrom(YieldFrom),
     Assign(Assign),
+    NamedExpr(NamedExpr),
+    Docstring(Docstring),
+    Comment(Comment),
+    AnnAssign(AnnAssign),
+    TypeAlias(TypeAlias),
+    TypeParam(TypeParam),
+    Global(Global),
+    Nonlocal(Nonlocal),
+    Delete(Delete),
     Alias(Alias),
     Try(Try),
     Except(Except),
@@ -333,9 +563,13 @@ pub enum ExpressionEnum {
     FormatValue(FormatValue),
     Format(Format),
     BinaryOperation(BinaryOperation),
+    BoolOp(BoolOp),
+    Compare(Compare),
+    IfExp(IfExp),
     Subscr(Subscr),
     UnaryOperation(UnaryOperation),
     Call(Call),
+    Starred(Starred),
     With(With),
     For(For),
     If(If),
@@ -383,6 +617,36 @@ impl Query for ContainerType {
     }
 }
 
+/// 格式化字符串中值的转换, 例如: f"{a!r}"
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum Conversion {
+    #[default]
+    None,
+    Str,
+    Repr,
+    Ascii,
+}
+
+impl Query for Conversion {
+    fn query<T: 'static>(&self) -> Vec<&T> {
+        vec![]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum TypeParamKind {
+    #[default]
+    TypeVar,
+    TypeVarTuple,
+    ParamSpec,
+}
+
+impl Query for TypeParamKind {
+    fn query<T: 'static>(&self) -> Vec<&T> {
+        vec![]
+    }
+}
+
 /// `Vec<ExpressionEnum>`的封装
 #[derive(Clone, Debug, PartialEq, Eq, Query, Expression)]
 pub struct Expr {
@@ -392,7 +656,7 @@ pub struct Expr {
 impl Class {
     pub fn new<S: AsRef<str>>(object_mark: S) -> Result<Self> {
         let reg = Regex::new(
-            r#"(?x)<code\ object\ (?P<name>\S+)\ at[\S\ ]+\ line\ (?P<start_line>\d+)>"#,
+            r#"(?x)<code\ object\ (?P<name><[^>]+>|\S+)\ at[\S\ ]+\ line\ (?P<start_line>\d+)>"#,
         )?;
         let cap = reg
             .captures(object_mark.as_ref())
@@ -412,7 +676,7 @@ impl Class {
 impl Function {
     pub fn new<S: AsRef<str>>(object_mark: S) -> Result<Self> {
         let reg = Regex::new(
-            r#"(?x)<code\ object\ (?P<name>\S+)\ at[\S\ ]+\ line\ (?P<start_line>\d+)>"#,
+            r#"(?x)<code\ object\ (?P<name><[^>]+>|\S+)\ at[\S\ ]+\ line\ (?P<start_line>\d+)>"#,
         )?;
         let cap = reg
             .captures(object_mark.as_ref())
@@ -477,533 +741,3 @@ impl Expr {
         self.bodys.clone().into_iter()
     }
 }
-
-/// 递归遍历表达式树, 生成代码
-impl ExpressionEnum {
-    pub fn build(&self) -> Result<Vec<String>> {
-        match self {
-            ExpressionEnum::Class(class) => {
-                let mut code = Vec::new();
-                code.push(format!("class {}:", class.name));
-
-                let mut class_members = class.members.iter();
-                let filter_members = ["__module__", "__qualname__"];
-
-                let mut next_expr = class_members.next();
-                // expect to skip the __module__ and __qualname__ assignment
-                loop {
-                    if let Some(ExpressionEnum::Assign(assign)) = next_expr {
-                        if let ExpressionEnum::BaseValue(name) = assign.target.as_ref() {
-                            if filter_members.contains(&name.value.as_str()) {
-                                next_expr = class_members.next();
-                            } else {
-                                break;
-                            }
-                        }
-                    } else {
-                        // unexpected
-                        // but no warning
-                        break;
-                    }
-                }
-
-                // check whether the docstring is exist
-                let mut has_doc = false;
-
-                if let Some(ExpressionEnum::Assign(assign)) = next_expr {
-                    if let ExpressionEnum::BaseValue(name) = assign.target.as_ref() {
-                        if name.value == "__doc__" {
-                            has_doc = true;
-                            let docstring = assign.values.build()?.join("").replace("\\n", "\n");
-                            let docstring = docstring.trim_matches('\'');
-                            code.push("    \"\"\"".to_string());
-                            for line in docstring.lines().filter(|l| !l.trim().is_empty()) {
-                                code.push(line.to_string());
-                            }
-                            code.push("    \"\"\"".to_string());
-                            code.push("".to_string());
-                        }
-                    }
-                }
-                let re = Regex::new(r"def [A-Za-z_]+\((?P<args>[\S_]*)\)")?;
-                let add_self_to_no_arg_func = |line: String| {
-                    if let Some(caps) = re.captures(&line) {
-                        if let Some(args) = caps.name("args") {
-                            if args.is_empty() {
-                                line.replace("()", "(self, *args)")
-                            } else {
-                                line.replace(args.as_str(), &format!("{}, *args", args.as_str()))
-                            }
-                        } else {
-                            line
-                        }
-                    } else {
-                        line
-                    }
-                };
-
-                if !has_doc {
-                    if let Some(expr) = next_expr {
-                        let expr_code = expr.build()?;
-                        for line in expr_code {
-                            code.push(format!("    {}", add_self_to_no_arg_func(line)));
-                        }
-                        if !code.last().unwrap().trim().is_empty() {
-                            code.push("".to_string());
-                        }
-                    }
-                    // if None
-                    // it may be an empty class
-                    // it doesn't report error
-                    else {
-                        code.push("    pass".to_string());
-                    }
-                }
-
-                for expr in class_members {
-                    let expr_code = expr.build()?;
-                    for line in expr_code {
-                        code.push(format!("    {}", add_self_to_no_arg_func(line)));
-                    }
-                    if !code.last().unwrap().trim().is_empty() {
-                        code.push("".to_string());
-                    }
-                }
-
-                Ok(code)
-            }
-            ExpressionEnum::Function(function) => {
-                let mut code = Vec::new();
-                let mut args_code = String::new();
-                let mut ret_code = String::new();
-                let mut defaults_iter = function.defaults.iter();
-                let mut default_offset = function.args.len() - function.defaults.len();
-                for (arg, anno) in function.args_iter() {
-                    if arg == "return" {
-                        ret_code.push_str(&format!(
-                            " -> {}",
-                            anno.as_ref().unwrap_or(&"None".to_string())
-                        ));
-                        continue;
-                    }
-                    if anno.is_none() {
-                        args_code.push_str(arg);
-                    } else {
-                        args_code.push_str(&format!("{}: {}", arg, anno.as_ref().unwrap()));
-                    }
-
-                    if default_offset == 0 {
-                        args_code.push_str(&format!(
-                            " = {}",
-                            defaults_iter.next().ok_or("No default! Iter error")?
-                        ));
-                    } else {
-                        // 还不是有默认值的参数
-                        default_offset -= 1;
-                    }
-                    args_code.push_str(", ")
-                }
-                match function.name.as_str() {
-                    "<lambda>" => {
-                        let lambda_args = args_code.trim_end_matches(", ");
-                        let lambda_body = function
-                            .bodys
-                            .first()
-                            .ok_or("No lambda body")?
-                            .build()?
-                            .join("");
-                        #[cfg(debug_assertions)]
-                        {
-                            // dbg!(&lambda_body);
-                        }
-                        let lambda_body = lambda_body.trim_start_matches("return ");
-                        if lambda_body.starts_with("yield") {
-                            code.push(format!("lambda {}: ({})", lambda_args, lambda_body));
-                        } else {
-                            code.push(format!("lambda {}: {}", lambda_args, lambda_body));
-                        }
-                    }
-                    "<listcomp>" => {
-                        code.push(format!(
-                            "[{} for {} in {}]",
-                            function.bodys[0].build()?.join(""),
-                            args_code.trim_end_matches(", "),
-                            function.bodys[1].build()?.join(""),
-                        ));
-                    }
-                    _ => {
-                        #[cfg(debug_assertions)]
-                        {
-                            //dbg!(&args_code);
-                        }
-                        let first_line = if function.is_async {
-                            format!(
-                                "async def {}({}){}:",
-                                function.name,
-                                args_code.trim_end_matches(", "),
-                                ret_code
-                            )
-                        } else {
-                            format!(
-                                "def {}({}){}:",
-                                function.name,
-                                args_code.trim_end_matches(", "),
-                                ret_code
-                            )
-                        };
-                        code.push(first_line);
-                        for expr in function.bodys.iter() {
-                            let expr_code = expr.build()?;
-                            for line in expr_code.iter() {
-                                code.push(format!("    {}", line));
-                            }
-                        }
-                        if code.len() == 1 {
-                            code.push("    pass".to_string());
-                        }
-                        code.push("".to_string());
-                    }
-                }
-                Ok(code)
-            }
-            ExpressionEnum::FastVariable(fast_var) => {
-                if fast_var.name == "None" {
-                    Ok(vec!["".to_string()])
-                } else if fast_var.name == "0" {
-                    Ok(vec![])
-                } else {
-                    Ok(vec![fast_var.name.clone()])
-                }
-            }
-            ExpressionEnum::Return(r) => {
-                let value_code = r.value.build()?.join("");
-                if value_code.is_empty() {
-                    Ok(vec![])
-                } else {
-                    Ok(vec![format!("return {}", value_code)])
-                }
-            }
-            ExpressionEnum::Yield(y) => {
-                let value_code = y.value.build()?.join("");
-                if value_code.is_empty() {
-                    Ok(vec!["yield".to_string()])
-                } else {
-                    Ok(vec![format!("yield {}", value_code)])
-                }
-            }
-            ExpressionEnum::Assign(a) => {
-                let mut code = Vec::new();
-                let target_code = a.target.build()?;
-                let value_code = a.values.build()?;
-                code.push(format!(
-                    "{} {} {}",
-                    target_code.join(""),
-                    a.operator,
-                    value_code.join("")
-                ));
-                Ok(code)
-            }
-            ExpressionEnum::Alias(alias) => {
-                let target_code = alias.target.build()?.join("");
-                let alias_code = alias.alias.build()?.join("");
-                Ok(vec![format!("{} as {}", target_code, alias_code)])
-            }
-            ExpressionEnum::Try(try_expr) => {
-                let mut code = Vec::new();
-                for expr in try_expr.body.iter() {
-                    let expr_code = expr.build()?;
-                    for line in expr_code.iter() {
-                        code.push(format!("    {}", line));
-                    }
-                }
-                for expr in try_expr.except.iter() {
-                    let expr_code = expr.build()?;
-                    code.extend(expr_code);
-                }
-                code.extend(try_expr.finally.build()?);
-                Ok(code)
-            }
-            ExpressionEnum::Except(except) => {
-                let exception_code = except.exception.build()?.join("");
-                let mut code = Vec::new();
-                if exception_code.is_empty() {
-                    code.push("except:".to_string());
-                } else {
-                    code.push(format!("except {}:", exception_code));
-                }
-                for expr in except.body.iter() {
-                    let expr_code = expr.build()?;
-                    for line in expr_code.iter() {
-                        code.push(format!("    {}", line));
-                    }
-                }
-                Ok(code)
-            }
-            ExpressionEnum::Finally(finally) => {
-                let mut code = Vec::new();
-                code.push("finally:".to_string());
-                for expr in finally.body.iter() {
-                    let expr_code = expr.build()?;
-                    for line in expr_code.iter() {
-                        code.push(format!("    {}", line));
-                    }
-                }
-                Ok(code)
-            }
-            ExpressionEnum::Assert(assert) => {
-                let test_code = assert.test.build()?.join("");
-                match &assert.msg {
-                    Some(msg) => {
-                        let msg_code = msg.build()?.join("");
-                        Ok(vec![format!("assert {}, {}", test_code, msg_code)])
-                    }
-                    None => Ok(vec![test_code]),
-                }
-            }
-            ExpressionEnum::Raise(raise) => {
-                let exception_code = raise.exception.build()?.join("");
-                Ok(vec![format!("raise {}", exception_code)])
-            }
-            ExpressionEnum::Await(await_expr) => {
-                let awaitable_code = await_expr.awaitable_expr.build()?.join("");
-                Ok(vec![format!("await {}", awaitable_code)])
-            }
-            ExpressionEnum::BaseValue(base_value) => {
-                if base_value.value == "None" {
-                    Ok(vec!["".to_string()])
-                }
-                /* else if base_value.value == "0" {
-                    Ok(vec![])
-                } */
-                else {
-                    Ok(vec![base_value.value.clone()])
-                }
-            }
-            ExpressionEnum::Call(call) => {
-                let func_code = call.func.build()?.join("");
-                let mut args_code = Vec::new();
-                for arg in call.args.iter() {
-                    let arg_code = arg.build()?;
-                    args_code.push(arg_code.join(""));
-                }
-                if func_code.starts_with("lambda ") {
-                    Ok(vec![format!(
-                        "({})({})",
-                        func_code,
-                        args_code.join(", ").trim_end_matches(", ")
-                    )])
-                } else {
-                    Ok(vec![format!(
-                        "{}({})",
-                        func_code,
-                        args_code.join(", ").trim_end_matches(", ")
-                    )])
-                }
-            }
-            ExpressionEnum::FormatValue(format_value) => {
-                let value_code = format_value.value.build()?.join("");
-                Ok(vec![value_code])
-            }
-            ExpressionEnum::Format(format) => {
-                let mut code = Vec::new();
-                let mut format_string = String::new();
-                for value in format.format_values.iter() {
-                    let value_code = value.build()?;
-                    if value.is_format_value() {
-                        format_string.push_str(&format!("{{{}}}", value_code.join("")));
-                    } else {
-                        format_string.push_str(value_code.join("").trim_matches('\''));
-                    }
-                }
-                code.push(format!("f\"{}\"", format_string.replace('"', "\\\"")));
-                Ok(code)
-            }
-            ExpressionEnum::BinaryOperation(binary_operation) => Ok(vec![format!(
-                "{} {} {}",
-                binary_operation.left.build()?.join(""),
-                binary_operation.operator,
-                binary_operation.right.build()?.join("")
-            )]),
-            ExpressionEnum::UnaryOperation(unary_operation) => Ok(vec![format!(
-                "{}{}",
-                match unary_operation.unary_type {
-                    UnaryType::Negative => "-",
-                    UnaryType::Invert => "~",
-                    UnaryType::Not => "not ",
-                    UnaryType::Positive => unreachable!(),
-                },
-                unary_operation.target.build()?.join("")
-            )]),
-            ExpressionEnum::Import(import) => {
-                if import.bk_module.is_none() {
-                    //没from
-                    if import.alias.is_none() {
-                        //没from，没as
-                        Ok(vec![format!("import {}", import.module)])
-                    } else {
-                        //没from，有as
-                        Ok(vec![format!(
-                            "import {} as {}",
-                            import.module,
-                            import
-                                .alias
-                                .as_ref()
-                                .expect("[No from Have as] Alias missed")
-                                .trim_end_matches(", ")
-                        )])
-                    }
-                } else {
-                    //有from
-
-                    Ok(vec![format!(
-                        "from {} import {}",
-                        import.module,
-                        import
-                            .bk_module
-                            .as_ref()
-                            .expect("[Have from No as] Bk_module missed")
-                            .trim_end_matches(", ")
-                    )])
-                }
-            }
-            ExpressionEnum::Container(container) => {
-                let mut code = Vec::new();
-                let mut values_code = Vec::new();
-                for value in container.values.iter() {
-                    let mut value_code = value.build()?;
-                    values_code.append(&mut value_code);
-                }
-                values_code.iter_mut().for_each(|s| {
-                    if s.is_empty() {
-                        *s = "None".to_string();
-                    }
-                });
-                match container.container_type {
-                    ContainerType::List => {
-                        code.push(format!("[{}]", values_code.join(", ")));
-                    }
-                    ContainerType::Tuple => {
-                        code.push(format!("({})", values_code.join(", ")));
-                    }
-                    ContainerType::Set => {
-                        code.push(format!("{{ {} }}", values_code.join(", ")));
-                    }
-                    ContainerType::Dict => {
-                        let mut dict_code = Vec::new();
-                        for (i, value) in values_code.iter().enumerate() {
-                            if i % 2 == 0 {
-                                dict_code.push(format!("{}: {}", value, values_code[i + 1]));
-                            }
-                        }
-                        code.push(format!("{{ {} }}", dict_code.join(", ")));
-                    }
-                }
-                Ok(code)
-            }
-            ExpressionEnum::Subscr(subscr) => {
-                let index_code = subscr.index.build()?.join("");
-                let target_code = subscr.target.build()?.join("");
-                Ok(vec![format!("{}[{}]", target_code, index_code)])
-            }
-            ExpressionEnum::Slice(slice) => {
-                let origin_code = slice.origin.build()?;
-                let slice_code = slice
-                    .slice
-                    .iter()
-                    .map(|s| Ok(s.build()?.join("")))
-                    .collect::<Result<Vec<String>>>()?;
-                Ok(vec![format!(
-                    "{}[{}]",
-                    origin_code.join(""),
-                    slice_code.join(":")
-                )])
-            }
-            ExpressionEnum::Attribute(attribute) => Ok(vec![format!(
-                "{}.{}",
-                attribute.parent.build()?.join(""),
-                attribute.attr.build()?.join("")
-            )]),
-            ExpressionEnum::With(with) => {
-                let item_code = with.item.build()?.join("");
-                let mut code = Vec::new();
-                let first_line = if with.is_async {
-                    format!("async with {}:", item_code)
-                } else {
-                    format!("with {}:", item_code)
-                };
-                code.push(first_line);
-                if with.body.is_empty() {
-                    code.push("    pass".to_string());
-                } else {
-                    for expr in with.body.iter() {
-                        let expr_code = expr.build()?;
-                        for line in expr_code.iter() {
-                            code.push(format!("    {}", line));
-                        }
-                    }
-                }
-                Ok(code)
-            }
-            ExpressionEnum::If(if_else) => {
-                let mut code = Vec::new();
-                if let Some(test) = if_else.test.as_ref() {
-                    let test_code = test.build()?.join("");
-                    code.push(format!("if {}:", test_code));
-                } else {
-                    code.push("else:".to_string());
-                }
-
-                for expr in if_else.body.iter() {
-                    if let ExpressionEnum::Jump(jump) = expr {
-                        if jump.is_backward {
-                            code.push("    continue".to_string());
-                        }
-                    } else {
-                        let expr_code = expr.build()?;
-                        for line in expr_code.iter() {
-                            code.push(format!("    {}", line));
-                        }
-                    }
-                }
-
-                if let Some(or_else) = if_else.or_else.as_ref() {
-                    let or_else_code = or_else.build()?;
-                    // dbg!(&or_else_code);
-                    if or_else_code[0].starts_with("if ") {
-                        // elif
-                        code.push(format!("el{}", or_else_code[0]));
-                        code.extend(or_else_code.into_iter().skip(1));
-                    } else {
-                        // starts with "else:"
-                        code.extend(or_else_code);
-                    }
-                }
-
-                Ok(code)
-            }
-            ExpressionEnum::For(for_expr) => {
-                let iter_code = for_expr.iterator.build()?.join("");
-                let item_code = for_expr.items.build()?.join("");
-                let mut code = Vec::new();
-                let first_line = if for_expr.is_async {
-                    format!("async for {} in {}:", item_code, iter_code)
-                } else {
-                    format!("for {} in {}:", item_code, iter_code)
-                };
-                code.push(first_line);
-                if for_expr.body.is_empty() {
-                    code.push("    pass".to_string());
-                } else {
-                    for expr in for_expr.body.iter() {
-                        let expr_code = expr.build()?;
-                        for line in expr_code.iter() {
-                            code.push(format!("    {}", line));
-                        }
-                    }
-                }
-                Ok(code)
-            }
-            _ => Ok(vec![]),
-        }
-    }
-}
@@ -0,0 +1,111 @@
+use crate::*;
+
+/// 运算符优先级, 从低到高
+/// 参考: https://docs.python.org/3/reference/expressions.html#operator-precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    /// 语句, yield, 赋值
+    Lowest,
+    Lambda,
+    IfExp,
+    Or,
+    And,
+    Not,
+    /// in, not in, is, is not, <, <=, >, >=, !=, ==
+    Compare,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    /// +, -
+    Arith,
+    /// *, @, /, //, %
+    Term,
+    /// +x, -x, ~x
+    Unary,
+    Power,
+    Await,
+    /// x[index], x(args), x.attr
+    Primary,
+    Atom,
+}
+
+impl Precedence {
+    /// 二元运算符的优先级, 就地运算 (例如: +=) 是语句
+    pub fn of_operator(operator: &str) -> Self {
+        match operator {
+            "or" => Precedence::Or,
+            "and" => Precedence::And,
+            "in" | "not in" | "is" | "is not" | "<" | "<=" | ">" | ">=" | "!=" | "==" => {
+                Precedence::Compare
+            }
+            "|" => Precedence::BitOr,
+            "^" => Precedence::BitXor,
+            "&" => Precedence::BitAnd,
+            "<<" | ">>" => Precedence::Shift,
+            "+" | "-" => Precedence::Arith,
+            "*" | "@" | "/" | "//" | "%" => Precedence::Term,
+            "**" => Precedence::Power,
+            _ => Precedence::Lowest,
+        }
+    }
+
+    /// 比它高一级的优先级, 左结合的运算符的右操作数要比它高
+    pub fn next(self) -> Self {
+        match self {
+            Precedence::Lowest => Precedence::Lambda,
+            Precedence::Lambda => Precedence::IfExp,
+            Precedence::IfExp => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Not,
+            Precedence::Not => Precedence::Compare,
+            Precedence::Compare => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Arith,
+            Precedence::Arith => Precedence::Term,
+            Precedence::Term => Precedence::Unary,
+            Precedence::Unary => Precedence::Power,
+            Precedence::Power => Precedence::Await,
+            Precedence::Await => Precedence::Primary,
+            Precedence::Primary | Precedence::Atom => Precedence::Atom,
+        }
+    }
+}
+
+impl ExpressionEnum {
+    /// 表达式作为操作数时的优先级
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            ExpressionEnum::BinaryOperation(binary_operation) => {
+                Precedence::of_operator(&binary_operation.operator)
+            }
+            ExpressionEnum::BoolOp(bool_op) => Precedence::of_operator(&bool_op.operator),
+            ExpressionEnum::Compare(_) => Precedence::Compare,
+            ExpressionEnum::UnaryOperation(UnaryOperation {
+                unary_type: UnaryType::Not,
+                ..
+            }) => Precedence::Not,
+            ExpressionEnum::UnaryOperation(_) => Precedence::Unary,
+            ExpressionEnum::IfExp(_) => Precedence::IfExp,
+            ExpressionEnum::Function(function) if function.name == "<lambda>" => Precedence::Lambda,
+            ExpressionEnum::Await(_) => Precedence::Await,
+            ExpressionEnum::Call(_) | ExpressionEnum::Attribute(_) | ExpressionEnum::Subscr(_) => {
+                Precedence::Primary
+            }
+            // a negative constant, e.g. (-1) ** 2
+            ExpressionEnum::BaseValue(base_value) if base_value.value.starts_with('-') => {
+                Precedence::Unary
+            }
+            ExpressionEnum::BaseValue(_)
+            | ExpressionEnum::FastVariable(_)
+            | ExpressionEnum::Container(_)
+            | ExpressionEnum::Format(_)
+            | ExpressionEnum::FormatValue(_)
+            | ExpressionEnum::NamedExpr(_)
+            | ExpressionEnum::Slice(_) => Precedence::Atom,
+            _ => Precedence::Lowest,
+        }
+    }
+}
@@ -67,7 +67,7 @@ pub trait Query {
     }
 }
 
-/// The following implementations are used to search for the type T in the Struct
+// The following implementations are used to search for the type T in the Struct
 
 impl Query for String {
     fn query<U: 'static>(&self) -> Vec<&U> {
@@ -151,9 +151,9 @@ pub fn derive_unwrap(input: TokenStream) -> TokenStream {
     }
 }
 
-/// Implement the get_offset function for each variant
+/// Implement the get_offset and get_start_line function for each variant
 ///
-/// the structure must have start_offset and end_offset fields
+/// the structure must have start_line, start_offset and end_offset fields
 #[proc_macro_derive(Offset)]
 pub fn derive_get_offset(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -174,8 +174,20 @@ pub fn derive_get_offset(input: TokenStream) -> TokenStream {
                 }
             }
         });
+        let line_variants = data_enum.variants.iter().map(|variant| {
+            let variant_name = &variant.ident;
+            quote! {
+                #name::#variant_name(inner) => inner.start_line,
+            }
+        });
         let gen = quote! {
             impl #name {
+                pub fn get_start_line(&self) -> usize {
+                    match self {
+                        #( #line_variants )*
+                    }
+                }
+
                 pub fn get_offset(&self) -> (usize, usize) {
                     match self {
                         #( #get_variants )*
@@ -0,0 +1,402 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use super::prelude::*;
+use pyrev_ast::*;
+
+/// 注释的详细程度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Annotation {
+    /// 只标出行号和偏移范围
+    #[default]
+    Offsets,
+    /// 还列出语句对应的每一条字节码
+    Instructions,
+}
+
+/// 每条指令属于哪条语句 (用语句的偏移范围表示), None 是没有用到的
+type Owners = Vec<Option<(usize, usize)>>;
+
+/// 在每条语句前面用注释标出它来自的行号和字节码偏移范围的代码生成器,
+/// 没有被任何语句用到的字节码会以 `# unconsumed:` 的形式列出来
+pub struct AnnotatedGenerator<'a> {
+    style: CodeStyle,
+    annotation: Annotation,
+    code_objects: &'a CodeObjectMap,
+    /// 每个字节码对象里的指令属于哪条语句
+    owners: RefCell<HashMap<String, Owners>>,
+    /// 正在生成的代码所在的字节码对象
+    marks: RefCell<Vec<String>>,
+    /// 已经列出来的没有用到的指令, (字节码对象, 下标)
+    reported: RefCell<HashSet<(String, usize)>>,
+}
+
+impl<'a> AnnotatedGenerator<'a> {
+    pub fn new(code_objects: &'a CodeObjectMap, style: CodeStyle, annotation: Annotation) -> Self {
+        Self {
+            style,
+            annotation,
+            code_objects,
+            owners: RefCell::new(HashMap::new()),
+            marks: RefCell::new(vec!["<main>".to_string()]),
+            reported: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn current_mark(&self) -> String {
+        self.marks.borrow().last().cloned().unwrap_or_default()
+    }
+
+    /// 在 lambda 或者推导式里面, 它们是表达式的一部分, 不能插入注释
+    fn in_expression(&self) -> bool {
+        self.marks.borrow().iter().any(|mark| is_inline(mark))
+    }
+
+    fn format_instruction(instruction: &OpcodeInstruction) -> String {
+        format!("#   {}", instruction.disassemble())
+    }
+
+    /// 列出 `mark` 里 `range` 中还没有列出来的没有用到的指令
+    fn unconsumed(&self, mark: &str, range: std::ops::Range<usize>) -> Vec<String> {
+        let (Some(code_object), Some(owners)) = (
+            self.code_objects.get(mark),
+            self.owners.borrow().get(mark).cloned(),
+        ) else {
+            return Vec::new();
+        };
+        let mut reported = self.reported.borrow_mut();
+        let mut code = Vec::new();
+        let mut index = range.start;
+        while index < range.end.min(owners.len()) {
+            if owners[index].is_some() || reported.contains(&(mark.to_string(), index)) {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < owners.len() && owners[index].is_none() {
+                reported.insert((mark.to_string(), index));
+                index += 1;
+            }
+            let run = &code_object[start..index];
+            if (start..index).all(|index| {
+                is_bookkeeping(&code_object[index].opname) || is_implicit_return(code_object, index)
+            }) {
+                continue;
+            }
+            let first = run.first().map_or(0, |instruction| instruction.offset);
+            let last = run.last().map_or(0, |instruction| instruction.offset);
+            if mark == self.current_mark() {
+                code.push(format!("# unconsumed: offset {}-{}", first, last));
+            } else {
+                code.push(format!(
+                    "# unconsumed in {}: offset {}-{}",
+                    mark, first, last
+                ));
+            }
+            code.extend(run.iter().map(Self::format_instruction));
+        }
+        code
+    }
+}
+
+impl CodeGenerator for AnnotatedGenerator<'_> {
+    fn style(&self) -> &CodeStyle {
+        &self.style
+    }
+
+    fn generate(&self, expr: &ExpressionEnum) -> Result<Vec<String>> {
+        let mark = match expr {
+            ExpressionEnum::Function(function) => Some(function.mark.clone()),
+            ExpressionEnum::Class(class) => Some(class.mark.clone()),
+            _ => None,
+        };
+        if let Some(mark) = mark.clone() {
+            self.marks.borrow_mut().push(mark);
+        }
+        let code = emit(self, expr);
+        if mark.is_some() {
+            self.marks.borrow_mut().pop();
+        }
+        code
+    }
+
+    fn generate_module(&self, bodys: &[ExpressionEnum]) -> Result<Vec<String>> {
+        let mut statements = HashMap::new();
+        collect_statements(bodys, "<main>", &mut statements);
+        let mut owners = self.owners.borrow_mut();
+        for (mark, code_object) in self.code_objects.iter() {
+            let spans = statements.remove(mark).unwrap_or_default();
+            owners.insert(mark.clone(), assign_owners(code_object, spans));
+        }
+        drop(owners);
+
+        let mut code = emit_module(self, bodys)?;
+        for mark in self.code_objects.keys().filter(|mark| !is_inline(mark)) {
+            code.extend(self.unconsumed(mark, 0..usize::MAX));
+        }
+        Ok(code)
+    }
+
+    fn annotate(&self, statement: &ExpressionEnum) -> Vec<String> {
+        if self.in_expression() {
+            return Vec::new();
+        }
+        let mark = self.current_mark();
+        let key = statement.get_offset();
+        let owned = match self.owners.borrow().get(&mark) {
+            Some(owners) => owners
+                .iter()
+                .enumerate()
+                .filter(|(_, owner)| **owner == Some(key))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>(),
+            None => return Vec::new(),
+        };
+        let (Some(&first), Some(&last)) = (owned.first(), owned.last()) else {
+            return Vec::new();
+        };
+        let Some(code_object) = self.code_objects.get(&mark) else {
+            return Vec::new();
+        };
+
+        // only the instructions right before the statement, the nested statements are
+        // generated before the statements around them
+        let unowned = self.owners.borrow().get(&mark).map_or(0, |owners| {
+            owners[..first]
+                .iter()
+                .rev()
+                .take_while(|owner| owner.is_none())
+                .count()
+        });
+        let mut code = self.unconsumed(&mark, first - unowned..first);
+        let start = code_object[first].offset.min(key.0);
+        let end = code_object[last].offset.max(key.1);
+        let line = statement.get_start_line();
+        if line > 0 {
+            code.push(format!("# line {}, offset {}-{}", line, start, end));
+        } else {
+            code.push(format!("# offset {}-{}", start, end));
+        }
+        if self.annotation == Annotation::Instructions {
+            code.extend(
+                owned
+                    .iter()
+                    .map(|&index| Self::format_instruction(&code_object[index])),
+            );
+        }
+        code
+    }
+}
+
+/// 语句自己的偏移范围和行号
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+    line: usize,
+    /// `return` 语句, 只有它能拥有 `return None` 的指令
+    is_return: bool,
+}
+
+impl Span {
+    fn contains(&self, other: &Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn same(&self, other: &Span) -> bool {
+        self.start == other.start && self.end == other.end
+    }
+}
+
+/// 按字节码对象收集所有的语句, 包括嵌套在代码块里面的
+fn collect_statements(
+    bodys: &[ExpressionEnum],
+    mark: &str,
+    statements: &mut HashMap<String, Vec<Span>>,
+) {
+    for expr in bodys {
+        let (start, end) = expr.get_offset();
+        if end > 0 {
+            statements.entry(mark.to_string()).or_default().push(Span {
+                start,
+                end,
+                line: expr.get_start_line(),
+                is_return: matches!(expr, ExpressionEnum::Return(_)),
+            });
+        }
+        match expr {
+            ExpressionEnum::Function(function) => {
+                collect_statements(&function.bodys, &function.mark, statements)
+            }
+            ExpressionEnum::Class(class) => {
+                collect_statements(&class.members, &class.mark, statements)
+            }
+            ExpressionEnum::If(if_expr) => {
+                collect_statements(&if_expr.body, mark, statements);
+                // `elif` and `else` are nested `If`s
+                if let Some(or_else) = if_expr.or_else.as_ref() {
+                    collect_statements(std::slice::from_ref(or_else), mark, statements);
+                }
+            }
+            ExpressionEnum::For(for_expr) => collect_statements(&for_expr.body, mark, statements),
+            ExpressionEnum::With(with) => collect_statements(&with.body, mark, statements),
+            ExpressionEnum::Try(try_expr) => {
+                collect_statements(&try_expr.body, mark, statements);
+                collect_statements(&try_expr.except, mark, statements);
+                collect_statements(&try_expr.or_else, mark, statements);
+                if let Some(finally) = try_expr.finally.as_ref() {
+                    collect_statements(std::slice::from_ref(finally), mark, statements);
+                }
+            }
+            ExpressionEnum::Except(except) => collect_statements(&except.body, mark, statements),
+            ExpressionEnum::Finally(finally) => collect_statements(&finally.body, mark, statements),
+            _ => {}
+        }
+    }
+}
+
+/// 把每条指令分给一条语句
+///
+/// 语句的节点通常只记录了最后一条指令的偏移, 所以先分给包含它的最内层的语句,
+/// 然后把紧跟在后面的同一行的指令, 以及前面从这一行开始的还没有分出去的指令也分给它
+///
+/// 编译器补上的 `return None` 不属于任何语句, 和 `RESUME` 这些指令一样不列出来
+fn assign_owners(code_object: &CodeObject, mut spans: Vec<Span>) -> Owners {
+    let mut owners: Vec<Option<Span>> = vec![None; code_object.len()];
+    let implicit = (0..code_object.len())
+        .map(|index| is_implicit_return(code_object, index))
+        .collect::<Vec<_>>();
+    // the inner statements are assigned later, so they overwrite the outer ones
+    spans.sort_by_key(|span| std::cmp::Reverse(span.end - span.start));
+    for span in spans.iter() {
+        for (index, instruction) in code_object.iter().enumerate() {
+            if span.start <= instruction.offset
+                && instruction.offset <= span.end
+                && (span.is_return || !implicit[index])
+            {
+                owners[index] = Some(*span);
+            }
+        }
+    }
+
+    // e.g. the `POP_TOP` after an expression statement, even if it is in the range of the
+    // enclosing statement
+    extend_forward(code_object, &mut owners, &implicit, true);
+    spans.sort_by_key(|span| span.start);
+    for span in spans.iter() {
+        let Some(mut index) = owners.iter().position(|owner| {
+            owner.is_some_and(|owner| owner.start == span.start && owner.end == span.end)
+        }) else {
+            continue;
+        };
+        while index > 0 {
+            let previous = &code_object[index - 1];
+            let claimable = match owners[index - 1] {
+                None => !implicit[index - 1],
+                // the instructions up to the start of the enclosing statement are its own,
+                // e.g. the test of an `if`
+                Some(owner) => {
+                    owner.contains(span) && !span.contains(&owner) && previous.offset > owner.start
+                }
+            };
+            let same_statement = previous.starts_line.is_none_or(|line| line >= span.line);
+            if !claimable || !same_statement {
+                break;
+            }
+            owners[index - 1] = Some(*span);
+            index -= 1;
+        }
+    }
+
+    // e.g. the `STORE_NAME` of the target after a `FOR_ITER`
+    extend_forward(code_object, &mut owners, &implicit, false);
+
+    owners
+        .into_iter()
+        .map(|owner| owner.map(|owner| (owner.start, owner.end)))
+        .collect()
+}
+
+/// 把紧跟在语句后面的同一行的指令分给它, `bookkeeping_only` 的时候只分不对应源代码的指令,
+/// 这时候也会从外层的语句那里拿过来 (跳转除外, 它们是外层语句的)
+fn extend_forward(
+    code_object: &CodeObject,
+    owners: &mut [Option<Span>],
+    implicit: &[bool],
+    bookkeeping_only: bool,
+) {
+    for index in 1..owners.len() {
+        let instruction = &code_object[index];
+        let Some(owner) = owners[index - 1] else {
+            continue;
+        };
+        let free = match owners[index] {
+            None => true,
+            Some(outer) => {
+                bookkeeping_only
+                    && outer.contains(&owner)
+                    && !outer.same(&owner)
+                    && !instruction.opname.contains("JUMP")
+            }
+        };
+        if free
+            && !implicit[index]
+            && instruction.starts_line == Some(owner.line)
+            && (!bookkeeping_only || is_bookkeeping(&instruction.opname))
+        {
+            owners[index] = Some(owner);
+        }
+    }
+}
+
+/// lambda 和推导式的字节码对象, 例如: `<code object <lambda> at 0x..., file "<dis>", line 1>`
+fn is_inline(mark: &str) -> bool {
+    mark.starts_with("<code object <") && !mark.contains("<generic parameters of ")
+}
+
+/// 编译器补上的 `return None`: `LOAD_CONST None; RETURN_VALUE` 或者 `RETURN_CONST None` (3.12+)
+fn is_implicit_return(code_object: &CodeObject, index: usize) -> bool {
+    let is_none = |index: usize| {
+        code_object.get(index).is_some_and(|instruction| {
+            instruction.opname == "LOAD_CONST" && instruction.argval.as_deref() == Some("None")
+        })
+    };
+    let is_return = |index: usize| {
+        code_object
+            .get(index)
+            .is_some_and(|instruction| instruction.opname == "RETURN_VALUE")
+    };
+    let instruction = &code_object[index];
+    match instruction.opname.as_str() {
+        "RETURN_CONST" => instruction.argval.as_deref() == Some("None"),
+        "RETURN_VALUE" => index > 0 && is_none(index - 1),
+        "LOAD_CONST" => is_none(index) && is_return(index + 1),
+        _ => false,
+    }
+}
+
+/// 不对应任何源代码的指令
+fn is_bookkeeping(opname: &str) -> bool {
+    [
+        "RESUME",
+        "NOP",
+        "CACHE",
+        "EXTENDED_ARG",
+        "POP_TOP",
+        "PUSH_NULL",
+        "PRECALL",
+        "KW_NAMES",
+        "MAKE_CELL",
+        "COPY_FREE_VARS",
+        "RETURN_GENERATOR",
+        "GEN_START",
+        "POP_BLOCK",
+        "NOT_TAKEN",
+        "JUMP",
+        "JUMP_FORWARD",
+        "JUMP_BACKWARD",
+        "JUMP_BACKWARD_NO_INTERRUPT",
+        "JUMP_ABSOLUTE",
+    ]
+    .contains(&opname)
+}
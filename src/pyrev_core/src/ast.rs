@@ -1,6 +1,8 @@
+use super::parse_opcode::{
+    copy_end, handler_region, unconditional_jump_target, HandlerKind, HandlerRegion,
+};
 use super::prelude::*;
 use pyrev_ast::*;
-use regex::Regex;
 
 use std::cmp::Ordering;
 
@@ -12,19 +14,31 @@ impl ExprParser for Expr {
     /// 用于解析一段字节码指令为AST
     fn parse(opcode_instructions: &[OpcodeInstruction]) -> Result<Box<Self>> {
         let mut exprs_stack = Vec::<ExpressionEnum>::new();
+        // the keyword names of the next call, set by `KW_NAMES`
+        let mut kw_names = None;
+        // the offsets of the `KW_NAMES` whose names are unknown
+        let mut unknown_kw_names = Vec::new();
         let mut offset = 0;
         loop {
             if offset == opcode_instructions.len() {
                 break;
             }
+            if let Some((exprs, end)) = parse_try(opcode_instructions, offset)? {
+                exprs_stack.extend(exprs);
+                offset = end;
+                continue;
+            }
             let instruction = opcode_instructions
                 .get(offset)
                 .ok_or("[Parse] No instruction")?;
 
             let opcode = instruction.opcode();
             match opcode {
-                Opcode::LoadConst | Opcode::LoadName | Opcode::LoadGlobal => {
-                    exprs_stack.push(ExpressionEnum::BaseValue(BaseValue {
+                Opcode::LoadConst
+                | Opcode::LoadName
+                | Opcode::LoadGlobal
+                | Opcode::LoadCommonConstant => {
+                    let value = ExpressionEnum::BaseValue(BaseValue {
                         value: instruction
                             .argval
                             .as_ref()
@@ -37,9 +51,43 @@ impl ExprParser for Expr {
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
                         ..Default::default()
+                    });
+                    // 3.14 folds the constant slices, e.g. slice(1, None, None)
+                    exprs_stack.push(if opcode == Opcode::LoadConst {
+                        const_slice(value)
+                    } else {
+                        value
+                    });
+                }
+                // 3.12 的类体和注解作用域会从 `locals()` 或者 `__classdict__` 里读取名字
+                Opcode::LoadLocals => {
+                    exprs_stack.push(ExpressionEnum::BaseValue(BaseValue {
+                        value: "locals()".to_string(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    }));
+                }
+                Opcode::LoadFromDictOrDeref | Opcode::LoadFromDictOrGlobals => {
+                    exprs_stack.pop().ok_or(format!(
+                        "[LoadFromDict] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::BaseValue(BaseValue {
+                        value: instruction
+                            .argval
+                            .as_ref()
+                            .ok_or(format!(
+                                "[LoadFromDict] No argval, deviation is {}",
+                                instruction.offset
+                            ))?
+                            .clone(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
                     }));
                 }
-                Opcode::LoadFast => {
+                Opcode::LoadFast | Opcode::LoadDeref | Opcode::LoadClosure => {
                     let name = instruction
                         .argval
                         .as_ref()
@@ -68,7 +116,8 @@ impl ExprParser for Expr {
                     exprs_stack.push(ExpressionEnum::Attribute(Attribute {
                         parent: Box::new(parent),
                         attr: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                            value: attr.clone(),
+                            // 3.12 loads a method by LOAD_ATTR, e.g. NULL|self + append
+                            value: attr.trim_start_matches("NULL|self + ").to_string(),
                             ..Default::default()
                         })),
                         start_offset: instruction.offset,
@@ -124,81 +173,24 @@ impl ExprParser for Expr {
                                     })),
                                     values: Box::new(ExpressionEnum::Function(function)),
                                     operator: "=".to_string(),
+                                    start_line: instruction.starts_line.unwrap_or_default(),
+                                    start_offset: instruction.offset,
+                                    end_offset: instruction.offset,
                                     ..Default::default()
                                 }));
                             } else {
                                 exprs_stack.push(ExpressionEnum::Function(function));
                             }
                         }
+                        // a decorated class
+                        ExpressionEnum::Class(_) | ExpressionEnum::TypeAlias(_) => {
+                            exprs_stack.push(value)
+                        }
                         ExpressionEnum::Import(import) => {
-                            if import.bk_module.is_none() {
-                                //没from
-                                if import.module == name {
-                                    //没from，没as
-                                    exprs_stack.push(ExpressionEnum::Import(Import {
-                                        module: import.module,
-                                        bk_module: None,
-                                        alias: None,
-                                        fragment: None,
-                                        start_line: import.start_line,
-                                        start_offset: import.start_offset,
-                                        end_offset: import.end_offset,
-                                    }))
-                                } else {
-                                    //没from，有as
-                                    exprs_stack.push(ExpressionEnum::Import(Import {
-                                        module: import.module,
-                                        bk_module: None,
-                                        alias: Some(name),
-                                        fragment: None,
-                                        start_line: import.start_line,
-                                        start_offset: import.start_offset,
-                                        end_offset: import.end_offset,
-                                    }))
-                                }
-                            } else {
-                                //有from
-
-                                if import.fragment.as_ref().ok_or(format!(
-                                    "[StoreName-import-HaveFrom], deviation is {}",
-                                    instruction.offset
-                                ))? == &name
-                                {
-                                    //有from，无as
-                                    exprs_stack.push(ExpressionEnum::Import(Import {
-                                        module: import.module,
-                                        bk_module: Some(
-                                            import.bk_module.expect("") + &name.clone() + ", ",
-                                        ),
-                                        alias: None,
-                                        fragment: None,
-                                        start_line: import.start_line,
-                                        start_offset: import.start_offset,
-                                        end_offset: import.end_offset,
-                                    }))
-                                } else {
-                                    //有from，有as
-                                    exprs_stack.push(ExpressionEnum::Import(Import {
-                                        module: import.module,
-                                        bk_module: Some(
-                                            import.bk_module.expect("")
-                                                + import.fragment.as_ref().ok_or(format!(
-                                                    "[StoreName-import-HaveFromAs], deviation is {}",
-                                                    instruction.offset
-                                                ))?
-                                                + " as "
-                                                + &name.clone()
-                                                + ", ",
-                                        ),
-                                        alias: None,
-                                        fragment: None,
-                                        start_line: import.start_line,
-                                        start_offset: import.start_offset,
-                                        end_offset: import.end_offset,
-                                    }))
-                                }
-                            }
+                            exprs_stack.push(ExpressionEnum::Import(store_import(import, name)))
                         }
+                        // e.g. a += 1
+                        _ if is_inplace_of(&value, &name) => exprs_stack.push(value),
                         _ => {
                             exprs_stack.push(ExpressionEnum::Assign(Assign {
                                 target: Box::new(ExpressionEnum::BaseValue(BaseValue {
@@ -207,14 +199,15 @@ impl ExprParser for Expr {
                                 })),
                                 values: Box::new(value),
                                 operator: "=".to_string(),
+                                chain: Vec::new(),
+                                start_line: instruction.starts_line.unwrap_or_default(),
                                 start_offset: instruction.offset,
                                 end_offset: instruction.offset,
-                                ..Default::default()
                             }));
                         }
                     }
                 }
-                Opcode::StoreFast => {
+                Opcode::StoreFast | Opcode::StoreDeref => {
                     let name = instruction
                         .argval
                         .as_ref()
@@ -226,9 +219,16 @@ impl ExprParser for Expr {
                     ))?;
 
                     match value {
-                        ExpressionEnum::Function(_) => {
+                        ExpressionEnum::Function(_)
+                        | ExpressionEnum::Class(_)
+                        | ExpressionEnum::TypeAlias(_) => {
                             exprs_stack.push(value);
                         }
+                        ExpressionEnum::Import(import) => {
+                            exprs_stack.push(ExpressionEnum::Import(store_import(import, name)))
+                        }
+                        // e.g. a += 1
+                        _ if is_inplace_of(&value, &name) => exprs_stack.push(value),
                         _ => exprs_stack.push(ExpressionEnum::Assign(Assign {
                             target: Box::new(ExpressionEnum::BaseValue(BaseValue {
                                 value: name,
@@ -236,6 +236,7 @@ impl ExprParser for Expr {
                             })),
                             values: Box::new(value),
                             operator: "=".to_string(),
+                            chain: Vec::new(),
                             start_line: instruction.starts_line.unwrap_or_default(),
                             start_offset: instruction.offset,
                             end_offset: instruction.offset,
@@ -271,73 +272,269 @@ impl ExprParser for Expr {
                     if let ExpressionEnum::BinaryOperation(BinaryOperation {
                         left, operator, ..
                     }) = value.clone()
+                        && let ExpressionEnum::Attribute(Attribute { parent, attr, .. }) = *left
                     {
-                        if let ExpressionEnum::Attribute(Attribute { parent, attr, .. }) = *left {
-                            // expect an attribute expression
-                            let target = target.unwrap_attribute();
-                            if parent == target.parent
-                                && attr == target.attr
-                                && operator.ends_with('=')
-                            {
-                                // it will continue soon
-                                // so move (instead clone) the value to the exprs_stack
-                                exprs_stack.push(value);
-                                offset += 1;
-                                continue;
-                            }
+                        // expect an attribute expression
+                        let target = target.unwrap_attribute();
+                        if parent == target.parent && attr == target.attr && operator.ends_with('=')
+                        {
+                            // it will continue soon
+                            // so move (instead clone) the value to the exprs_stack
+                            exprs_stack.push(value);
+                            offset += 1;
+                            continue;
                         }
                     }
                     exprs_stack.push(ExpressionEnum::Assign(Assign {
                         target: Box::new(target),
                         values: Box::new(value),
                         operator: "=".to_string(),
+                        chain: Vec::new(),
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
                     }));
                     //dbg!(&exprs_stack);
                 }
-                Opcode::LoadBuildClass => {
-                    let mark = opcode_instructions
-                        .get(offset + 1)
-                        .cloned()
-                        .ok_or(format!(
-                            "[LoadBuildClass] No mark, deviation is {}",
-                            instruction.offset
-                        ))?
-                        .argval
-                        .ok_or(format!(
-                            "[LoadBuildClass] No argval, deviation is {}",
+                // x.y: int = 1, 注解只会被计算, 然后丢掉
+                Opcode::PopTop if is_annotation_of_target(&exprs_stack, instruction) => {
+                    let annotation = exprs_stack.pop().ok_or(format!(
+                        "[PopTop] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let Some(ExpressionEnum::Assign(assign)) = exprs_stack.pop() else {
+                        return Err(format!(
+                            "[PopTop] Expect an assignment, deviation is {}",
                             instruction.offset
-                        ))?;
-                    let class = Class::new(mark)?;
-                    exprs_stack.push(ExpressionEnum::Class(class));
-
-                    // skip build class
-                    loop {
-                        offset += 1;
-                        if let Some(next_instruction) = opcode_instructions.get(offset) {
-                            if next_instruction.starts_line.unwrap()
-                                != instruction.starts_line.unwrap()
+                        )
+                        .into());
+                    };
+                    exprs_stack.push(ExpressionEnum::AnnAssign(AnnAssign {
+                        target: assign.target,
+                        annotation: Box::new(annotation),
+                        value: Some(assign.values),
+                        start_line: assign.start_line,
+                        start_offset: assign.start_offset,
+                        end_offset: instruction.offset,
+                    }));
+                }
+                Opcode::StoreSubscr => {
+                    let index = exprs_stack.pop().ok_or(format!(
+                        "[StoreSubscr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let target = exprs_stack.pop().ok_or(format!(
+                        "[StoreSubscr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[StoreSubscr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    // 模块和类里的注解会存到 __annotations__ 里, 例如: x: int = 1
+                    if let ExpressionEnum::BaseValue(dict) = &target
+                        && dict.value == "__annotations__"
+                        && let ExpressionEnum::BaseValue(name) = &index
+                    {
+                        let name = name.value.trim_matches('\'').to_string();
+                        // the value is stored just before the annotation
+                        let assign = match exprs_stack.last() {
+                            Some(ExpressionEnum::Assign(assign))
+                                if assign.operator == "="
+                                    && assign.start_line
+                                        == instruction.starts_line.unwrap_or_default()
+                                    && matches!(assign.target.as_ref(), ExpressionEnum::BaseValue(x) if x.value == name) =>
                             {
-                                break;
+                                Some(assign.clone())
                             }
+                            _ => None,
+                        };
+                        if assign.is_some() {
+                            exprs_stack.pop();
                         }
+                        exprs_stack.push(ExpressionEnum::AnnAssign(AnnAssign {
+                            target: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                                value: name,
+                                ..Default::default()
+                            })),
+                            annotation: Box::new(value),
+                            value: assign.map(|assign| assign.values),
+                            start_line: instruction.starts_line.unwrap_or_default(),
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                        }));
+                        offset += 1;
+                        continue;
                     }
-                    offset -= 1;
+                    let target = Subscr {
+                        target: Box::new(target),
+                        index: Box::new(index),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    };
+                    exprs_stack.push(assign_subscr(target, value, instruction)?);
+                }
+                // 3.12: a[start:end] = value
+                Opcode::StoreSlice => {
+                    let mut values = Vec::with_capacity(4);
+                    for _ in 0..4 {
+                        values.push(exprs_stack.pop().ok_or(format!(
+                            "[StoreSlice] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?);
+                    }
+                    let [end, start, target, value] = <[ExpressionEnum; 4]>::try_from(values)
+                        .map_err(|_| "[StoreSlice] Unreachable")?;
+                    let target = Subscr {
+                        target: Box::new(target),
+                        index: Box::new(ExpressionEnum::Slice(Slice {
+                            slice: vec![start, end],
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        })),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    };
+                    exprs_stack.push(assign_subscr(target, value, instruction)?);
+                }
+                Opcode::LoadBuildClass => {
+                    // the class is made when `__build_class__` is called
+                    exprs_stack.push(ExpressionEnum::BaseValue(BaseValue {
+                        value: "__build_class__".to_string(),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }));
+                }
+                Opcode::LoadSuperAttr => {
+                    let this = exprs_stack.pop().ok_or(format!(
+                        "[LoadSuperAttr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let class = exprs_stack.pop().ok_or(format!(
+                        "[LoadSuperAttr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let function = exprs_stack.pop().ok_or(format!(
+                        "[LoadSuperAttr] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let attr = instruction.argval.as_ref().ok_or(format!(
+                        "[LoadSuperAttr] No argval, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    // the second-low bit means `super(cls, self)`, otherwise `super()`
+                    let args = if instruction.arg.is_some_and(|x| x & 2 == 2) {
+                        vec![class, this]
+                    } else {
+                        vec![]
+                    };
+                    exprs_stack.push(ExpressionEnum::Attribute(Attribute {
+                        parent: Box::new(ExpressionEnum::Call(Call {
+                            func: Box::new(function),
+                            args,
+                            start_line: instruction.starts_line.unwrap_or_default(),
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        })),
+                        attr: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                            value: attr.trim_start_matches("NULL|self + ").to_string(),
+                            ..Default::default()
+                        })),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }));
                 }
                 Opcode::FormatValue => {
-                    let format_value = exprs_stack.pop().ok_or(format!(
+                    let flags = instruction.arg.ok_or(format!(
+                        "[FormatValue] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    // 0x04: the format spec is on the top of the stack
+                    let format_spec = if flags & 0x04 != 0 {
+                        Some(Box::new(exprs_stack.pop().ok_or(format!(
+                            "[FormatValue] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?))
+                    } else {
+                        None
+                    };
+                    let value = exprs_stack.pop().ok_or(format!(
                         "[FormatValue] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
                     exprs_stack.push(ExpressionEnum::FormatValue(FormatValue {
-                        value: Box::new(format_value),
+                        value: Box::new(value),
+                        conversion: conversion(flags & 0x03),
+                        format_spec,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }));
+                }
+                Opcode::ConvertValue => {
+                    // 3.13: the conversion is applied before the value is formatted
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[ConvertValue] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let oparg = instruction.arg.ok_or(format!(
+                        "[ConvertValue] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::FormatValue(FormatValue {
+                        value: Box::new(value),
+                        conversion: conversion(oparg),
+                        start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
                         ..Default::default()
                     }));
                 }
+                Opcode::FormatSimple | Opcode::FormatWithSpec => {
+                    let format_spec = if opcode == Opcode::FormatWithSpec {
+                        Some(Box::new(exprs_stack.pop().ok_or(format!(
+                            "[FormatWithSpec] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?))
+                    } else {
+                        None
+                    };
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[FormatSimple] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    // the value converted by `CONVERT_VALUE` hasn't been formatted yet
+                    let format_value = match value {
+                        ExpressionEnum::FormatValue(converted)
+                            if opcode_instructions.iter().any(|x| {
+                                x.offset == converted.start_offset
+                                    && x.opcode() == Opcode::ConvertValue
+                            }) =>
+                        {
+                            FormatValue {
+                                format_spec,
+                                ..converted
+                            }
+                        }
+                        value => FormatValue {
+                            value: Box::new(value),
+                            format_spec,
+                            ..Default::default()
+                        },
+                    };
+                    exprs_stack.push(ExpressionEnum::FormatValue(FormatValue {
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..format_value
+                    }));
+                }
                 Opcode::BuildString => {
                     let size = instruction.arg.ok_or(format!(
                         "[BuildString] No arg, deviation is {}",
@@ -433,21 +630,10 @@ impl ExprParser for Expr {
                         ..
                     }) = list
                     {
-                        extend.iter_mut().for_each(|x| {
-                            if let ExpressionEnum::BaseValue(value) = x {
-                                // deprecated trim_start_matches('\'').trim_end_matches('\'')
-                                // because it may trim more than one matches
-                                // e.g. "((1, 2))" -> "1, 2"
-                                value.value = Regex::new(r"\((.*)\)")
-                                    .unwrap()
-                                    .captures(&value.value)
-                                    .unwrap()
-                                    .get(1)
-                                    .unwrap()
-                                    .as_str()
-                                    .to_string();
-                            }
-                        });
+                        let mut extend = extend
+                            .into_iter()
+                            .flat_map(|x| unpack_display(x, instruction))
+                            .collect::<Vec<_>>();
                         list.append(&mut extend);
                         exprs_stack.push(ExpressionEnum::Container(Container {
                             values: list,
@@ -460,61 +646,329 @@ impl ExprParser for Expr {
                         return Err("[ListExtend] Invalid list".into());
                     }
                 }
-                Opcode::BuildSet => {
-                    let size = instruction.arg.ok_or(format!(
-                        "[BuildSet] No arg, deviation is {}",
+                Opcode::ListAppend => {
+                    // e.g. f(*a, b), the comprehensions append to the list under the iterator
+                    let index = instruction.arg.ok_or(format!(
+                        "[ListAppend] No arg, deviation is {}",
                         instruction.offset
                     ))?;
-                    let mut set = Vec::with_capacity(size);
-                    for _ in 0..size {
-                        set.push(exprs_stack.pop().ok_or(format!(
-                            "[BuildSet] Stack is empty, deviation is {}",
+                    let list_idx = exprs_stack.len().checked_sub(2).filter(|_| index == 1);
+                    if let Some(ExpressionEnum::Container(Container {
+                        container_type: ContainerType::List,
+                        ..
+                    })) = list_idx.and_then(|i| exprs_stack.get(i))
+                    {
+                        let value = exprs_stack.pop().ok_or(format!(
+                            "[ListAppend] Stack is empty, deviation is {}",
                             instruction.offset
-                        ))?);
+                        ))?;
+                        if let Some(ExpressionEnum::Container(list)) =
+                            list_idx.and_then(|i| exprs_stack.get_mut(i))
+                        {
+                            list.values.push(value);
+                        }
                     }
-                    set.reverse();
-                    exprs_stack.push(ExpressionEnum::Container(Container {
-                        values: set,
-                        container_type: ContainerType::Set,
-                        start_offset: instruction.offset,
-                        end_offset: instruction.offset,
-                        ..Default::default()
-                    }));
                 }
-                Opcode::BuildMap => {
-                    let size = instruction.arg.ok_or(format!(
-                        "[BuildMap] No arg, deviation is {}",
+                Opcode::ListToTuple => {
+                    let list = exprs_stack.pop().ok_or(format!(
+                        "[ListToTuple] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-                    if size == 0 {
-                        exprs_stack.push(ExpressionEnum::Container(Container {
-                            values: vec![],
-                            container_type: ContainerType::Dict,
-                            start_offset: instruction.offset,
-                            end_offset: instruction.offset,
-                            ..Default::default()
-                        }));
-                    } else {
-                        let mut map = Vec::with_capacity(size * 2);
-                        for _ in 0..size {
-                            let value = exprs_stack.pop().ok_or(format!(
-                                "[BuildMap] Stack is empty, deviation is {}",
+                    exprs_stack.push(list_to_tuple(list));
+                }
+                Opcode::CallIntrinsic1 => {
+                    let kind = match instruction.argval.as_deref() {
+                        Some("INTRINSIC_LIST_TO_TUPLE") => {
+                            let list = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic1] Stack is empty, deviation is {}",
                                 instruction.offset
                             ))?;
-                            let key = exprs_stack.pop().ok_or(format!(
-                                "[BuildMap] Stack is empty, deviation is {}",
+                            exprs_stack.push(list_to_tuple(list));
+                            offset += 1;
+                            continue;
+                        }
+                        // type Alias = ..., 参数是 (name, type_params, value)
+                        Some("INTRINSIC_TYPEALIAS") => {
+                            let args = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic1] Stack is empty, deviation is {}",
                                 instruction.offset
                             ))?;
-                            map.push(value);
-                            map.push(key);
+                            let [name, type_params, value] = <[ExpressionEnum; 3]>::try_from(
+                                tuple_values(args),
+                            )
+                            .map_err(|_| {
+                                format!(
+                                    "[CallIntrinsic1] Invalid type alias, deviation is {}",
+                                    instruction.offset
+                                )
+                            })?;
+                            exprs_stack.push(ExpressionEnum::TypeAlias(TypeAlias {
+                                name: name.build()?.join("").trim_matches('\'').to_string(),
+                                type_params: match type_params {
+                                    ExpressionEnum::BaseValue(none) if none.value == "None" => {
+                                        vec![]
+                                    }
+                                    type_params => tuple_values(type_params),
+                                },
+                                value: Box::new(value),
+                                start_line: instruction.starts_line.unwrap_or_default(),
+                                start_offset: instruction.offset,
+                                end_offset: instruction.offset,
+                            }));
+                            offset += 1;
+                            continue;
                         }
-                        map.reverse();
-                        exprs_stack.push(ExpressionEnum::Container(Container {
-                            values: map,
-                            container_type: ContainerType::Dict,
-                            start_offset: instruction.offset,
-                            end_offset: instruction.offset,
-                            ..Default::default()
+                        Some("INTRINSIC_TYPEVAR") => TypeParamKind::TypeVar,
+                        Some("INTRINSIC_TYPEVARTUPLE") => TypeParamKind::TypeVarTuple,
+                        Some("INTRINSIC_PARAMSPEC") => TypeParamKind::ParamSpec,
+                        // the `Generic[T]` base of a generic class is stored in `.generic_base`,
+                        // and it's removed from the bases when the class is resolved
+                        Some("INTRINSIC_SUBSCRIPT_GENERIC") => {
+                            exprs_stack.pop();
+                            // 3.13: STORE_FAST_LOAD_FAST (.generic_base, .generic_base)
+                            if opcode_instructions
+                                .get(offset + 1)
+                                .is_some_and(|x| x.opname == "STORE_FAST_LOAD_FAST")
+                            {
+                                exprs_stack.push(ExpressionEnum::BaseValue(BaseValue {
+                                    value: ".generic_base".to_string(),
+                                    ..Default::default()
+                                }));
+                                offset += 2;
+                                continue;
+                            }
+                            offset = skip_hidden_store(opcode_instructions, offset + 1);
+                            continue;
+                        }
+                        _ => {
+                            offset += 1;
+                            continue;
+                        }
+                    };
+                    let name = exprs_stack.pop().ok_or(format!(
+                        "[CallIntrinsic1] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::TypeParam(TypeParam {
+                        name: name.build()?.join("").trim_matches('\'').to_string(),
+                        kind,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    }));
+                    offset = skip_hidden_store(opcode_instructions, offset + 1);
+                    continue;
+                }
+                Opcode::CallIntrinsic2 => {
+                    match instruction.argval.as_deref() {
+                        // T: int 或者 T: (int, str)
+                        Some("INTRINSIC_TYPEVAR_WITH_BOUND")
+                        | Some("INTRINSIC_TYPEVAR_WITH_CONSTRAINTS") => {
+                            let bound = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic2] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            let name = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic2] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            exprs_stack.push(ExpressionEnum::TypeParam(TypeParam {
+                                name: name.build()?.join("").trim_matches('\'').to_string(),
+                                bound: Some(Box::new(bound)),
+                                start_line: instruction.starts_line.unwrap_or_default(),
+                                start_offset: instruction.offset,
+                                end_offset: instruction.offset,
+                                ..Default::default()
+                            }));
+                        }
+                        // 3.13 T = int
+                        Some("INTRINSIC_SET_TYPEPARAM_DEFAULT") => {
+                            let default_value = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic2] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            if let Some(ExpressionEnum::TypeParam(param)) = exprs_stack.last_mut() {
+                                param.default_value = Some(Box::new(default_value));
+                            }
+                        }
+                        // def f[T](): ...
+                        Some("INTRINSIC_SET_FUNCTION_TYPE_PARAMS") => {
+                            let type_params = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic2] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            let function = exprs_stack.pop().ok_or(format!(
+                                "[CallIntrinsic2] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            let ExpressionEnum::Function(mut function) = function else {
+                                return Err(format!(
+                                    "[CallIntrinsic2] Expect a function, deviation is {}",
+                                    instruction.offset
+                                )
+                                .into());
+                            };
+                            function.type_params = tuple_values(type_params);
+                            exprs_stack.push(ExpressionEnum::Function(function));
+                        }
+                        _ => {}
+                    }
+                    offset = skip_hidden_store(opcode_instructions, offset + 1);
+                    continue;
+                }
+                Opcode::DictMerge | Opcode::DictUpdate => {
+                    let index = instruction.arg.ok_or(format!(
+                        "[DictMerge] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[DictMerge] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let dict = exprs_stack
+                        .len()
+                        .checked_sub(index)
+                        .and_then(|i| exprs_stack.get_mut(i));
+                    if let Some(ExpressionEnum::Container(Container {
+                        values,
+                        container_type: ContainerType::Dict,
+                        ..
+                    })) = dict
+                    {
+                        // e.g. {**a, 'k': v}, the items after `**a` are built as a new dict
+                        if let ExpressionEnum::Container(Container {
+                            values: items,
+                            container_type: ContainerType::Dict,
+                            ..
+                        }) = value
+                        {
+                            values.extend(items);
+                            offset += 1;
+                            continue;
+                        }
+                        values.push(ExpressionEnum::Starred(Starred {
+                            value: Box::new(value),
+                            double_star: true,
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        }));
+                    } else {
+                        return Err(format!(
+                            "[DictMerge] Invalid dict, deviation is {}",
+                            instruction.offset
+                        )
+                        .into());
+                    }
+                }
+                // e.g. {*a, *b}, {1, 2, 3} is `frozenset({1, 2, 3})` + `SET_UPDATE`
+                Opcode::SetUpdate => {
+                    let index = instruction.arg.ok_or(format!(
+                        "[SetUpdate] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[SetUpdate] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let set = exprs_stack
+                        .len()
+                        .checked_sub(index)
+                        .and_then(|i| exprs_stack.get_mut(i));
+                    if let Some(ExpressionEnum::Container(Container {
+                        values,
+                        container_type: ContainerType::Set,
+                        ..
+                    })) = set
+                    {
+                        values.extend(unpack_display(value, instruction));
+                    } else {
+                        return Err(format!(
+                            "[SetUpdate] Invalid set, deviation is {}",
+                            instruction.offset
+                        )
+                        .into());
+                    }
+                }
+                // e.g. {*a, 1}
+                Opcode::SetAdd => {
+                    let index = instruction.arg.ok_or(format!(
+                        "[SetAdd] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let set_idx = exprs_stack.len().checked_sub(2).filter(|_| index == 1);
+                    if let Some(ExpressionEnum::Container(Container {
+                        container_type: ContainerType::Set,
+                        ..
+                    })) = set_idx.and_then(|i| exprs_stack.get(i))
+                    {
+                        let value = exprs_stack.pop().ok_or(format!(
+                            "[SetAdd] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        if let Some(ExpressionEnum::Container(set)) =
+                            set_idx.and_then(|i| exprs_stack.get_mut(i))
+                        {
+                            set.values.push(value);
+                        }
+                    }
+                }
+                Opcode::BuildSet => {
+                    let size = instruction.arg.ok_or(format!(
+                        "[BuildSet] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let mut set = Vec::with_capacity(size);
+                    for _ in 0..size {
+                        set.push(exprs_stack.pop().ok_or(format!(
+                            "[BuildSet] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?);
+                    }
+                    set.reverse();
+                    exprs_stack.push(ExpressionEnum::Container(Container {
+                        values: set,
+                        container_type: ContainerType::Set,
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    }));
+                }
+                Opcode::BuildMap => {
+                    let size = instruction.arg.ok_or(format!(
+                        "[BuildMap] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    if size == 0 {
+                        exprs_stack.push(ExpressionEnum::Container(Container {
+                            values: vec![],
+                            container_type: ContainerType::Dict,
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        }));
+                    } else {
+                        let mut map = Vec::with_capacity(size * 2);
+                        for _ in 0..size {
+                            let value = exprs_stack.pop().ok_or(format!(
+                                "[BuildMap] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            let key = exprs_stack.pop().ok_or(format!(
+                                "[BuildMap] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            map.push(value);
+                            map.push(key);
+                        }
+                        map.reverse();
+                        exprs_stack.push(ExpressionEnum::Container(Container {
+                            values: map,
+                            container_type: ContainerType::Dict,
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
                         }));
                     }
                 }
@@ -540,6 +994,7 @@ impl ExprParser for Expr {
                         for (k, v) in key
                             .trim_start_matches('(')
                             .trim_end_matches(')')
+                            .trim_end_matches(',')
                             .split(", ")
                             .zip(values)
                         {
@@ -569,84 +1024,99 @@ impl ExprParser for Expr {
                         ))?);
                     }
                     slice.reverse();
-                    let origin = exprs_stack.pop().ok_or(format!(
-                        "[BuildSlice] Stack is empty, deviation is {}",
-                        instruction.offset
-                    ))?;
                     exprs_stack.push(ExpressionEnum::Slice(Slice {
-                        origin: Box::new(origin),
                         slice,
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
                         ..Default::default()
                     }));
                 }
+                // 3.12: container[start:end]
+                Opcode::BinarySlice => {
+                    let end = exprs_stack.pop().ok_or(format!(
+                        "[BinarySlice] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let start = exprs_stack.pop().ok_or(format!(
+                        "[BinarySlice] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let target = exprs_stack.pop().ok_or(format!(
+                        "[BinarySlice] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::Subscr(Subscr {
+                        target: Box::new(target),
+                        index: Box::new(ExpressionEnum::Slice(Slice {
+                            slice: vec![start, end],
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        })),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    }));
+                }
                 Opcode::MakeFunction => {
-                    let mark = exprs_stack.pop().ok_or(format!(
+                    let mut mark = exprs_stack.pop().ok_or(format!(
                         "[MakeFunction] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
+                    // before 3.11, the qualified name is on the top of the code object
+                    if let ExpressionEnum::BaseValue(BaseValue { value, .. }) = &mark
+                        && !value.starts_with("<code object")
+                    {
+                        mark = exprs_stack.pop().ok_or(format!(
+                            "[MakeFunction] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                    }
                     let mut function = Function::from(mark)?;
-                    if let Some(argval) = instruction.argval.as_ref() {
-                        if argval.contains("annotations") {
-                            let values = exprs_stack.pop().ok_or(format!(
-                                "[MakeFunction] Stack is empty, deviation is {}",
-                                instruction.offset
-                            ))?;
-                            if let ExpressionEnum::Container(container) = values {
-                                #[cfg(debug_assertions)]
-                                {
-                                    assert_eq!(container.container_type, ContainerType::Tuple);
-                                }
-
-                                for (idx, exprs) in container.values.chunks(2).enumerate() {
-                                    if exprs.iter().all(|e| e.is_base_value()) {
-                                        let arg = FastVariable {
-                                            index: idx,
-                                            name: exprs
-                                                .first()
-                                                .unwrap()
-                                                .unwrap_base_value()
-                                                .value
-                                                .trim_start_matches('\'')
-                                                .trim_end_matches('\'')
-                                                .to_string(),
-                                            annotation: Some(
-                                                exprs
-                                                    .get(1)
-                                                    .as_ref()
-                                                    .unwrap()
-                                                    .unwrap_base_value()
-                                                    .value,
-                                            ),
-                                            ..Default::default()
-                                        };
-                                        function.args.push(arg);
-                                    }
-                                }
-                            }
-                        }
-                        if argval.contains("defaults") {
-                            let defaults = exprs_stack.pop().ok_or(format!(
+                    // 3.13 sets the attributes by `SET_FUNCTION_ATTRIBUTE`, and the arg is None
+                    let flags = instruction.arg.unwrap_or_default();
+                    for flag in [
+                        FUNCTION_ANNOTATE,
+                        FUNCTION_CLOSURE,
+                        FUNCTION_ANNOTATIONS,
+                        FUNCTION_KWDEFAULTS,
+                        FUNCTION_DEFAULTS,
+                    ] {
+                        if flags & flag != 0 {
+                            let value = exprs_stack.pop().ok_or(format!(
                                 "[MakeFunction] Stack is empty, deviation is {}",
                                 instruction.offset
                             ))?;
-                            if let ExpressionEnum::BaseValue(BaseValue { value, .. }) = defaults {
-                                let defaults = value
-                                    .trim_start_matches('(')
-                                    .trim_end_matches(')')
-                                    .trim_end_matches(',')
-                                    .split(", ")
-                                    .map(|x| x.to_string())
-                                    .collect::<Vec<_>>();
-                                function.defaults = defaults;
-                            }
+                            set_function_attribute(&mut function, flag, value)?;
                         }
                     }
                     function.start_offset = instruction.offset;
                     function.end_offset = instruction.offset;
                     exprs_stack.push(ExpressionEnum::Function(function));
                 }
+                Opcode::SetFunctionAttribute => {
+                    let flag = instruction.arg.ok_or(format!(
+                        "[SetFunctionAttribute] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let function = exprs_stack.pop().ok_or(format!(
+                        "[SetFunctionAttribute] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[SetFunctionAttribute] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let ExpressionEnum::Function(mut function) = function else {
+                        return Err(format!(
+                            "[SetFunctionAttribute] Expect a function, deviation is {}",
+                            instruction.offset
+                        )
+                        .into());
+                    };
+                    set_function_attribute(&mut function, flag, value)?;
+                    exprs_stack.push(ExpressionEnum::Function(function));
+                }
                 // BinaryOperation
                 Opcode::BinaryOp | Opcode::CompareOp => {
                     let right = exprs_stack.pop().ok_or(format!(
@@ -657,6 +1127,18 @@ impl ExprParser for Expr {
                         "[BinaryOp] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
+                    // 3.14 replaces BINARY_SUBSCR with BINARY_OP 26 ([])
+                    if instruction.argval.as_deref() == Some("[]") {
+                        exprs_stack.push(ExpressionEnum::Subscr(Subscr {
+                            target: Box::new(left),
+                            index: Box::new(right),
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        }));
+                        offset += 1;
+                        continue;
+                    }
                     exprs_stack.push(ExpressionEnum::BinaryOperation(BinaryOperation {
                         left: Box::new(left),
                         right: Box::new(right),
@@ -785,10 +1267,28 @@ impl ExprParser for Expr {
                         instruction.offset
                     ))?;
                     if count == 0 {
-                        let last = exprs_stack.pop().ok_or(format!(
+                        let mut last = exprs_stack.pop().ok_or(format!(
                             "[Call] Stack is empty, deviation is {}",
                             instruction.offset
                         ))?;
+                        if let ExpressionEnum::Function(wrapper) = &mut last
+                            && is_generic_wrapper(wrapper)
+                        {
+                            wrapper.end_offset = instruction.offset;
+                            exprs_stack.push(last);
+                            offset += 1;
+                            continue;
+                        }
+                        // 3.11+ calls the decorator with the definition as `self`
+                        if is_definition(&last) && !exprs_stack.is_empty() {
+                            let decorator = exprs_stack.pop().ok_or(format!(
+                                "[Call] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            exprs_stack.push(decorate(last, decorator));
+                            offset += 1;
+                            continue;
+                        }
                         if let ExpressionEnum::BaseValue(base_value) = &last {
                             if base_value.value.contains(' ') {
                                 // not a function call
@@ -822,24 +1322,48 @@ impl ExprParser for Expr {
                         ))?);
                     }
                     args.reverse();
+                    let keywords = match kw_names.take() {
+                        Some(names) => split_keywords(&mut args, names, instruction)?,
+                        None => vec![],
+                    };
+                    // before 3.11, the decorator is called with the definition as the only argument
+                    if args.len() == 1 && keywords.is_empty() && is_definition(&args[0]) {
+                        let decorator = exprs_stack.pop().ok_or(format!(
+                            "[Call] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        exprs_stack.push(decorate(args.remove(0), decorator));
+                        offset += 1;
+                        continue;
+                    }
                     match exprs_stack.pop() {
+                        // the defaults of a generic function are passed to the wrapper
+                        Some(ExpressionEnum::Function(mut wrapper))
+                            if is_generic_wrapper(&wrapper) =>
+                        {
+                            wrapper.defaults = args;
+                            wrapper.end_offset = instruction.offset;
+                            exprs_stack.push(ExpressionEnum::Function(wrapper));
+                        }
                         Some(ExpressionEnum::BaseValue(function_name)) => {
                             //dbg!(&function_name);
                             let function_name = function_name.value.trim_start_matches("NULL + ");
-                            exprs_stack.push(ExpressionEnum::Call(Call {
+                            exprs_stack.push(build_class(Call {
                                 func: Box::new(ExpressionEnum::BaseValue(BaseValue {
                                     value: function_name.to_string(),
                                     ..Default::default()
                                 })),
                                 args,
+                                keywords,
                                 start_line: instruction.starts_line.unwrap_or_default(),
                                 start_offset: instruction.offset,
                                 end_offset: instruction.offset,
-                            }))
+                            })?)
                         }
                         Some(function) => exprs_stack.push(ExpressionEnum::Call(Call {
                             func: Box::new(function),
                             args,
+                            keywords,
                             start_line: instruction.starts_line.unwrap_or_default(),
                             start_offset: instruction.offset,
                             end_offset: instruction.offset,
@@ -847,23 +1371,116 @@ impl ExprParser for Expr {
                         None => return Err("[Call] Stack is empty".into()),
                     }
                 }
-                Opcode::ReturnValue => {
-                    //dbg!(&exprs_stack);
-                    let value = exprs_stack.pop().ok_or(format!(
-                        "[ReturnValue] Stack is empty, deviation is {}",
+                Opcode::KwNames => {
+                    // 3.11 only shows the index of the names, they come from `dis.code_info`,
+                    // without it the keywords are passed as positional arguments
+                    match instruction.argval.as_deref() {
+                        Some(names) => kw_names = Some(parse_kw_names(names)),
+                        None => unknown_kw_names.push(instruction.offset),
+                    }
+                }
+                Opcode::CallKw | Opcode::CallFunctionKw => {
+                    let count = instruction.arg.ok_or(format!(
+                        "[CallKw] No arg, deviation is {}",
                         instruction.offset
                     ))?;
-                    exprs_stack.push(ExpressionEnum::Return(Return {
-                        value: Box::new(value),
+                    let names = match exprs_stack.pop() {
+                        Some(ExpressionEnum::BaseValue(names)) => parse_kw_names(&names.value),
+                        _ => {
+                            return Err(format!(
+                                "[CallKw] Invalid keyword names, deviation is {}",
+                                instruction.offset
+                            )
+                            .into())
+                        }
+                    };
+                    let mut args = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        args.push(exprs_stack.pop().ok_or(format!(
+                            "[CallKw] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?);
+                    }
+                    args.reverse();
+                    let keywords = split_keywords(&mut args, names, instruction)?;
+                    let function = exprs_stack.pop().ok_or(format!(
+                        "[CallKw] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(build_class(Call {
+                        func: Box::new(function),
+                        args,
+                        keywords,
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
-                    }));
+                    })?);
                 }
-                Opcode::YieldValue => {
-                    let value = exprs_stack.pop().ok_or(format!(
-                        "[YieldValue] Stack is empty, deviation is {}",
-                        instruction.offset,
+                Opcode::CallFunctionEx => {
+                    let flags = instruction.arg.ok_or(format!(
+                        "[CallFunctionEx] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let keywords = if flags & 1 == 1 {
+                        let kwargs = exprs_stack.pop().ok_or(format!(
+                            "[CallFunctionEx] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        unpack_call_kwargs(kwargs, instruction)?
+                    } else {
+                        vec![]
+                    };
+                    let args = exprs_stack.pop().ok_or(format!(
+                        "[CallFunctionEx] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let function = exprs_stack.pop().ok_or(format!(
+                        "[CallFunctionEx] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(build_class(Call {
+                        func: Box::new(function),
+                        args: unpack_call_args(args),
+                        keywords,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    })?);
+                }
+                Opcode::ReturnValue => {
+                    //dbg!(&exprs_stack);
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[ReturnValue] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::Return(Return {
+                        value: Box::new(value),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }));
+                }
+                Opcode::ReturnConst => {
+                    let value = instruction.argval.as_ref().ok_or(format!(
+                        "[ReturnConst] No argval, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::Return(Return {
+                        value: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                            value: value.clone(),
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                            ..Default::default()
+                        })),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }));
+                }
+                Opcode::YieldValue => {
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[YieldValue] Stack is empty, deviation is {}",
+                        instruction.offset,
                     ))?;
                     exprs_stack.push(ExpressionEnum::Yield(Yield {
                         value: Box::new(value),
@@ -873,125 +1490,110 @@ impl ExprParser for Expr {
                     }))
                 }
                 Opcode::ImportFrom => {
+                    let name = instruction
+                        .argval
+                        .as_ref()
+                        .ok_or(format!(
+                            "[ImportFrom] No argval, deviation is {}",
+                            instruction.offset
+                        ))?
+                        .clone();
                     let value = exprs_stack.pop().ok_or(format!(
                         "[ImportFrom] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-                    if let ExpressionEnum::Import(import) = value {
-                        if import.bk_module.is_none() {
-                            //没from
-                            import
-                                .with_mut_unchecked()
-                                .patch_by(|mut i| i.fragment = None)
-                                .unwrap();
-                            exprs_stack.push(ExpressionEnum::Import(import))
-                        } else {
-                            //有from
-                            let new_fragment = Some(
-                                instruction
-                                    .argval
-                                    .as_ref()
-                                    .ok_or(format!(
-                                        "[ImportName] No argval, deviation is {}",
-                                        instruction.offset
-                                    ))?
-                                    .clone(),
-                            );
-                            import
-                                .with_mut_unchecked()
-                                .patch_by(|mut i| i.fragment = new_fragment)
-                                .unwrap();
-                            exprs_stack.push(ExpressionEnum::Import(import))
-                        }
+                    if let ExpressionEnum::Import(mut import) = value {
+                        import.fragment = Some(name);
+                        exprs_stack.push(ExpressionEnum::Import(import));
+                    }
+                    // import a.b.c as d: IMPORT_FROM b, SWAP 2 (ROT_TWO), POP_TOP, IMPORT_FROM c
+                    while opcode_instructions
+                        .get(offset + 1)
+                        .is_some_and(|x| matches!(x.opname.as_str(), "SWAP" | "ROT_TWO"))
+                        && opcode_instructions
+                            .get(offset + 2)
+                            .is_some_and(|x| x.opcode() == Opcode::PopTop)
+                    {
+                        offset += 2;
                     }
                 }
                 Opcode::ImportName => {
-                    let module = exprs_stack.pop().ok_or(format!(
+                    let from_list = exprs_stack.pop().ok_or(format!(
                         "[ImportName] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-
-                    // remove the "0"
-                    // when import a module, it will load a const "0" first
-                    if let Some(ExpressionEnum::BaseValue(base_value)) = exprs_stack.last() {
-                        if base_value.value == "0" {
-                            exprs_stack.pop();
+                    // the level of relative import is loaded before the from list
+                    let level = match exprs_stack.last() {
+                        Some(ExpressionEnum::BaseValue(base_value)) => {
+                            base_value.value.parse::<usize>().ok()
                         }
+                        _ => None,
+                    };
+                    if level.is_some() {
+                        exprs_stack.pop();
                     }
 
-                    if !module.build()?.join("").is_empty() {
-                        //需要from
-                        let origin_len = module.build()?.join("").len();
-                        if origin_len                  //有“ * ”
-                        - module
-                            .build()?
-                            .join("")
-                            .replace('*', "")
-                            .len()
-                            == 1
-                        {
-                            exprs_stack.push(ExpressionEnum::Import(Import {
-                                module: instruction
-                                    .argval
-                                    .as_ref()
-                                    .ok_or(format!(
-                                        "[ImportName] No argval, deviation is {}",
-                                        instruction.offset
-                                    ))?
-                                    .clone(),
-                                bk_module: Some('*'.to_string()),
-                                fragment: None,
-                                alias: None,
-                                start_line: instruction.starts_line.unwrap_or_default(),
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                            }))
-                        } else {
-                            //没 “ * ”
-                            exprs_stack.push(ExpressionEnum::Import(Import {
-                                module: instruction
-                                    .argval
-                                    .as_ref()
-                                    .ok_or(format!(
-                                        "[ImportName] No argval, deviation is {}",
-                                        instruction.offset
-                                    ))?
-                                    .clone(),
-                                bk_module: Some("".to_string()),
-                                fragment: None,
-                                alias: None,
-                                start_line: instruction.starts_line.unwrap_or_default(),
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                            }))
-                        }
+                    let from_list_code = from_list.build()?.join("");
+                    let names = if from_list_code == "None" {
+                        None
+                    } else if from_list_code == "('*',)" {
+                        Some(vec![ExpressionEnum::BaseValue(BaseValue {
+                            value: "*".to_string(),
+                            ..Default::default()
+                        })])
                     } else {
-                        //不需要from
-                        exprs_stack.push(ExpressionEnum::Import(Import {
-                            module: instruction
-                                .argval
-                                .as_ref()
-                                .ok_or(format!(
-                                    "[ImportName] No argval, deviation is {}",
-                                    instruction.offset
-                                ))?
-                                .clone(),
-                            bk_module: None,
-                            fragment: None,
-                            alias: None,
-                            start_line: instruction.starts_line.unwrap_or_default(),
-                            start_offset: instruction.offset,
-                            end_offset: instruction.offset,
-                        }))
-                    }
+                        Some(vec![])
+                    };
+                    exprs_stack.push(ExpressionEnum::Import(Import {
+                        // `from . import x` has no module name
+                        module: instruction.argval.clone().unwrap_or_default(),
+                        level: level.unwrap_or_default(),
+                        names,
+                        fragment: None,
+                        alias: None,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                    }))
                 }
                 Opcode::PopJumpIfTrue => {
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        // if next instruction is LoadAssertionError, then it's an assert
-                        if next_instruction.opcode() == Opcode::LoadAssertionError {
-                            offset += 1;
-                            continue;
-                        }
+                    // the AssertionError is raised when the test fails
+                    if opcode_instructions
+                        .get(offset + 1)
+                        .is_some_and(is_assertion_error)
+                    {
+                        let test = exprs_stack.pop().ok_or(format!(
+                            "[Assert] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        let (assert, end) = parse_assert(opcode_instructions, offset, test, false)?;
+                        exprs_stack.push(assert);
+                        offset = end;
+                        continue;
+                    }
+                    // the value-producing branches, like `a and b` or `x if cond else y`
+                    if let Some(end) = bool_chain_end(opcode_instructions, offset) {
+                        let left = exprs_stack.pop().ok_or(format!(
+                            "[BoolOp] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        exprs_stack.push(parse_bool_chain(opcode_instructions, offset, left, end)?);
+                        offset = end;
+                        continue;
+                    }
+                    if let Some(left) = exprs_stack.last()
+                        && let Some((if_exp, end, copied)) = try_parse_if_exp(
+                            opcode_instructions,
+                            offset,
+                            left,
+                            exprs_stack.len() > 1,
+                        )
+                    {
+                        hide_copied_tail(opcode_instructions, copied);
+                        exprs_stack.pop();
+                        exprs_stack.push(if_exp);
+                        offset = end;
+                        continue;
                     }
 
                     let test = exprs_stack.pop().ok_or(format!(
@@ -1014,12 +1616,43 @@ impl ExprParser for Expr {
                     offset -= 1;
                 }
                 Opcode::PopJumpIfFalse => {
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        // if next instruction is LoadAssertionError, then it's an assert
-                        if next_instruction.opcode() == Opcode::LoadAssertionError {
-                            offset += 1;
-                            continue;
-                        }
+                    // the AssertionError is raised when the test fails
+                    if opcode_instructions
+                        .get(offset + 1)
+                        .is_some_and(is_assertion_error)
+                    {
+                        let test = exprs_stack.pop().ok_or(format!(
+                            "[Assert] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        let (assert, end) = parse_assert(opcode_instructions, offset, test, true)?;
+                        exprs_stack.push(assert);
+                        offset = end;
+                        continue;
+                    }
+                    // the value-producing branches, like `a and b` or `x if cond else y`
+                    if let Some(end) = bool_chain_end(opcode_instructions, offset) {
+                        let left = exprs_stack.pop().ok_or(format!(
+                            "[BoolOp] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        exprs_stack.push(parse_bool_chain(opcode_instructions, offset, left, end)?);
+                        offset = end;
+                        continue;
+                    }
+                    if let Some(left) = exprs_stack.last()
+                        && let Some((if_exp, end, copied)) = try_parse_if_exp(
+                            opcode_instructions,
+                            offset,
+                            left,
+                            exprs_stack.len() > 1,
+                        )
+                    {
+                        hide_copied_tail(opcode_instructions, copied);
+                        exprs_stack.pop();
+                        exprs_stack.push(if_exp);
+                        offset = end;
+                        continue;
                     }
 
                     let test = exprs_stack.pop().ok_or(format!(
@@ -1028,15 +1661,7 @@ impl ExprParser for Expr {
                     ))?;
                     // if test false, then jump to target
                     // now offset + 1 to target is this branch block
-                    let jump_target = instruction
-                        .argval
-                        .as_ref()
-                        .ok_or(format!(
-                            "[PopJumpIfFalse] No argval, deviation is {}",
-                            instruction.offset
-                        ))?
-                        .trim_start_matches("to ")
-                        .parse::<usize>()?;
+                    let jump_target = instruction.jump_target()?;
 
                     // judge whether the if-expr have multiple test
                     // if the jump_target points to the previous opcode is PopJumpIfFalse or similar
@@ -1050,8 +1675,10 @@ impl ExprParser for Expr {
                             "[PopJumpIfFalse] No jump target {}, deviation is {}",
                             jump_target, instruction.offset
                         ))?;
-                    let prev_instruction =
-                        opcode_instructions.get(jump_target_idx - 1).ok_or(format!(
+                    let prev_instruction = jump_target_idx
+                        .checked_sub(1)
+                        .and_then(|idx| opcode_instructions.get(idx))
+                        .ok_or(format!(
                             "[PopJumpIfFalse] No prev instruction, deviation is {}",
                             instruction.offset
                         ))?;
@@ -1061,7 +1688,13 @@ impl ExprParser for Expr {
                         Opcode::PopJumpIfNone,
                         Opcode::PopJumpIfNotNone,
                     ];
-                    let (test, block_end_idx) = if pop_jump.contains(&prev_instruction.opcode()) {
+                    let (test, block_end_idx) = if pop_jump.contains(&prev_instruction.opcode())
+                        && let Some((test, then_start, else_start)) =
+                            parse_if_test(opcode_instructions, offset, &test)
+                    {
+                        offset = then_start - 1;
+                        (test, Some(else_start))
+                    } else if pop_jump.contains(&prev_instruction.opcode()) {
                         // if prev opcode is contained in pop_jump
                         // it may have multiple test
                         // and then we need to get all test
@@ -1083,23 +1716,20 @@ impl ExprParser for Expr {
                                 let this_test_instructions =
                                     &opcode_instructions[last_test_idx + 1.._idx];
 
-                                let mut this_test_expr = Self::parse(this_test_instructions)?.bodys;
-                                assert!(this_test_expr.len() == 1);
-                                let mut this_test_expr = this_test_expr.pop().unwrap();
-                                let this_jump_target = prev_instruction
-                                    .argval
-                                    .as_ref()
-                                    .ok_or(format!(
-                                        "[PopJumpIfFalse] No argval, deviation is {}",
-                                        instruction.offset
-                                    ))?
-                                    .trim_start_matches("to ")
-                                    .parse::<usize>()?;
+                                let [mut this_test_expr] = <[ExpressionEnum; 1]>::try_from(
+                                    Self::parse(this_test_instructions)?.bodys,
+                                )
+                                .map_err(|_| {
+                                    format!(
+                                        "[PopJumpIfFalse] Invalid test, deviation is {}",
+                                        prev_instruction.offset
+                                    )
+                                })?;
+                                let this_jump_target = prev_instruction.jump_target()?;
 
                                 this_test_expr.set_offset(
-                                    opcode_instructions[exprs_and_jumps.last().unwrap().2 + 1]
-                                        .offset,
-                                    opcode_instructions[_idx].offset,
+                                    opcode_instructions[last_test_idx + 1].offset,
+                                    prev_instruction.offset,
                                 );
                                 exprs_and_jumps.push((this_test_expr, this_jump_target, _idx));
 
@@ -1118,21 +1748,21 @@ impl ExprParser for Expr {
 
                         // dbg!(&exprs_and_jumps);
 
-                        let block_start_idx = exprs_and_jumps.last().unwrap().2 + 1;
+                        let block_start_idx = last_test_idx + 1;
                         // println!("block_start_idx: {}, block_end_idx: {}", block_start_idx, block_end_idx);
-                        #[cfg(debug_assertions)]
+                        if block_start_idx > block_end_idx
+                            || block_end_idx >= opcode_instructions.len()
                         {
-                            assert!(
-                                block_start_idx <= block_end_idx,
-                                "block_start_idx: {}, offset: {}",
-                                block_start_idx,
-                                offset
-                            );
+                            return Err(format!(
+                                "[PopJumpIfFalse] Invalid block, deviation is {}",
+                                instruction.offset
+                            )
+                            .into());
                         }
 
                         offset = block_start_idx - 1;
                         // combine all test
-                        let combined_test = exprs_and_jumps.first().unwrap().0.clone();
+                        let combined_test = exprs_and_jumps[0].0.clone();
 
                         pub fn combine(
                             combined_test: ExpressionEnum,
@@ -1142,10 +1772,13 @@ impl ExprParser for Expr {
                             arr_end_idx: usize,
                             block_start_idx: usize,
                             block_end_idx: usize,
-                        ) -> ExpressionEnum {
+                        ) -> Result<ExpressionEnum> {
                             // this idx is the index of the exprs_and_jumps (not the opcode_instructions)
                             let this_idx = arr_start_idx;
-                            let this_jump_target = exprs_and_jumps[this_idx].1;
+                            let this_jump_target = exprs_and_jumps
+                                .get(this_idx)
+                                .ok_or("[PopJumpIfFalse] No test to combine")?
+                                .1;
                             // this _idx is the index of the exprs_and_jumps (not the opcode_instructions)
                             if let Some(_idx) = exprs_and_jumps
                                 .iter()
@@ -1156,11 +1789,9 @@ impl ExprParser for Expr {
                                 // cannot jump to the next test
                                 // due to the next test may be run after this test
                                 // it's unnecessary to jump
-                                assert_ne!(
-                                    this_idx + 1,
-                                    _idx,
-                                    "[PopJumpIfFalse] The next test is the jump target, which is not expected"
-                                );
+                                if this_idx + 1 == _idx {
+                                    return Err("[PopJumpIfFalse] The next test is the jump target, which is not expected".into());
+                                }
 
                                 if this_idx + 2 == _idx {
                                     // if skip one test, then use 'and' to combine this test and the skip test
@@ -1179,14 +1810,14 @@ impl ExprParser for Expr {
 
                                     // the next test of the next test
                                     let next_test = exprs_and_jumps[_idx].0.clone();
-                                    ExpressionEnum::BinaryOperation(BinaryOperation {
+                                    Ok(ExpressionEnum::BinaryOperation(BinaryOperation {
                                         left: Box::new(combined_and_test),
                                         right: Box::new(next_test),
                                         operator: "or".to_string(),
                                         start_offset: opcode_instructions[block_start_idx].offset,
                                         end_offset: opcode_instructions[_idx].offset,
                                         ..Default::default()
-                                    })
+                                    }))
                                 } else {
                                     // if skip more than one test
                                     // it means the previous test is 'and' with all of tests between this and the test pointed by _idx
@@ -1199,16 +1830,16 @@ impl ExprParser for Expr {
                                         _idx - 1,
                                         block_start_idx,
                                         block_end_idx,
-                                    );
+                                    )?;
 
-                                    ExpressionEnum::BinaryOperation(BinaryOperation {
+                                    Ok(ExpressionEnum::BinaryOperation(BinaryOperation {
                                         left: Box::new(first_and_test),
                                         right: Box::new(combined_test),
                                         operator: "and".to_string(),
                                         start_offset: opcode_instructions[block_start_idx].offset,
                                         end_offset: opcode_instructions[_idx].offset,
                                         ..Default::default()
-                                    })
+                                    }))
                                 }
                             } else if this_jump_target
                                 == opcode_instructions[block_start_idx].offset
@@ -1226,16 +1857,16 @@ impl ExprParser for Expr {
                                     arr_end_idx,
                                     block_start_idx,
                                     block_end_idx,
-                                );
+                                )?;
 
-                                ExpressionEnum::BinaryOperation(BinaryOperation {
+                                Ok(ExpressionEnum::BinaryOperation(BinaryOperation {
                                     left: Box::new(this_test.clone()),
                                     right: Box::new(later_test_combined),
                                     operator: "or".to_string(),
                                     start_offset: opcode_instructions[block_start_idx].offset,
                                     end_offset: opcode_instructions[block_end_idx].offset,
                                     ..Default::default()
-                                })
+                                }))
                             } else {
                                 // jump to the end of the block
                                 // it means this test is `and` with all of later tests
@@ -1243,14 +1874,14 @@ impl ExprParser for Expr {
 
                                 if this_idx + 1 == arr_end_idx {
                                     let next_test = exprs_and_jumps[this_idx + 1].0.clone();
-                                    ExpressionEnum::BinaryOperation(BinaryOperation {
+                                    Ok(ExpressionEnum::BinaryOperation(BinaryOperation {
                                         left: Box::new(this_test.clone()),
                                         right: Box::new(next_test),
                                         operator: "and".to_string(),
                                         start_offset: opcode_instructions[block_start_idx].offset,
                                         end_offset: opcode_instructions[block_end_idx].offset,
                                         ..Default::default()
-                                    })
+                                    }))
                                 } else {
                                     let later_test_combined = combine(
                                         this_test.clone(),
@@ -1260,16 +1891,16 @@ impl ExprParser for Expr {
                                         arr_end_idx,
                                         block_start_idx,
                                         block_end_idx,
-                                    );
+                                    )?;
 
-                                    ExpressionEnum::BinaryOperation(BinaryOperation {
+                                    Ok(ExpressionEnum::BinaryOperation(BinaryOperation {
                                         left: Box::new(this_test),
                                         right: Box::new(later_test_combined),
                                         operator: "and".to_string(),
                                         start_offset: opcode_instructions[block_start_idx].offset,
                                         end_offset: opcode_instructions[block_end_idx].offset,
                                         ..Default::default()
-                                    })
+                                    }))
                                 }
                             }
                         }
@@ -1283,7 +1914,7 @@ impl ExprParser for Expr {
                                 exprs_and_jumps.len() - 1,
                                 block_start_idx,
                                 block_end_idx,
-                            ),
+                            )?,
                             Some(block_end_idx),
                         )
                     } else {
@@ -1314,7 +1945,7 @@ impl ExprParser for Expr {
                             //
                             // In now version, we will change the sub-if jump target to parent-if jump target
                             // In other words, the block_end_first_idx default is the end of the parent-if block
-                            sub_instructions.len().checked_sub(1).unwrap_or_default()
+                            sub_instructions.len().saturating_sub(1)
                         }
                     };
 
@@ -1328,7 +1959,9 @@ impl ExprParser for Expr {
                         body: sub_expr.bodys,
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
-                        end_offset: sub_instructions.last().unwrap().offset,
+                        end_offset: sub_instructions
+                            .last()
+                            .map_or(instruction.offset, |x| x.offset),
                         ..Default::default()
                     };
 
@@ -1339,27 +1972,31 @@ impl ExprParser for Expr {
 
                     // get jump target
                     // it is the next one of the last instruction of the block
-                    let else_block_end_later = if let ExpressionEnum::Jump(this_block_jumps) =
+                    let else_block_end_idx = if let ExpressionEnum::Jump(this_block_jumps) =
                         if_expr.body.last().ok_or(format!(
                             "[PopJumpIfFalse] No last expr, deviation is {}",
                             instruction.offset
                         ))?
                         && !this_block_jumps.is_backward
                     {
-                        this_block_jumps.target
+                        opcode_instructions
+                            .iter()
+                            .position(|x| x.offset == this_block_jumps.target)
                     } else if let ExpressionEnum::Return(_) = if_expr.body.last().unwrap() {
                         // if the last instruction is Return
                         // find the next jump or return as else block end
                         let else_block_end_later_idx = opcode_instructions
                             .iter()
                             .skip(offset + 1)
-                            .position(|x| {
-                                x.opcode() == Opcode::JumpForward
-                                    || x.opcode() == Opcode::ReturnValue
-                            })
+                            .position(|x| x.opcode() == Opcode::JumpForward || is_return(x))
                             .map(|x| x + offset + 1);
                         if let Some(else_block_end_later_idx) = else_block_end_later_idx {
-                            opcode_instructions[else_block_end_later_idx].offset
+                            // the else block returns too, the return belongs to it
+                            if is_return(&opcode_instructions[else_block_end_later_idx]) {
+                                Some(else_block_end_later_idx + 1)
+                            } else {
+                                Some(else_block_end_later_idx)
+                            }
                         } else {
                             // if not have jump target and Return in the block last
                             // it may only one branch (no elif/else)
@@ -1383,12 +2020,21 @@ impl ExprParser for Expr {
 
                     // If find the jump target in the rest instructions, then it's an elif or else
                     // if not, that may be the end of parent if-expr
-                    if let Some(else_block_end_idx) = opcode_instructions
-                        .iter()
-                        .position(|x| x.offset == else_block_end_later)
-                    {
-                        let remain_instructions =
-                            &opcode_instructions[offset + 1..else_block_end_idx];
+                    if let Some(else_block_end_idx) = else_block_end_idx {
+                        let remain_instructions = opcode_instructions
+                            .get(offset + 1..else_block_end_idx)
+                            .ok_or(format!(
+                                "[PopJumpIfFalse] Invalid else block, deviation is {}",
+                                instruction.offset
+                            ))?;
+                        let (start_line, start_offset, end_offset) = match remain_instructions {
+                            [first, .., last] | [first @ last] => (
+                                first.starts_line.unwrap_or_default(),
+                                first.offset,
+                                last.offset,
+                            ),
+                            [] => (0, instruction.offset, instruction.offset),
+                        };
 
                         let remain_exprs = Self::parse(remain_instructions)?;
                         // dbg!(&remain_exprs.bodys);
@@ -1400,13 +2046,9 @@ impl ExprParser for Expr {
                                 let else_expr = If {
                                     test: None,
                                     body: remain_exprs.collect(),
-                                    start_line: remain_instructions
-                                        .first()
-                                        .unwrap()
-                                        .starts_line
-                                        .unwrap_or_default(),
-                                    start_offset: remain_instructions.first().unwrap().offset,
-                                    end_offset: remain_instructions.last().unwrap().offset,
+                                    start_line,
+                                    start_offset,
+                                    end_offset,
                                     ..Default::default()
                                 };
                                 remain_first_expr.or_else =
@@ -1418,13 +2060,9 @@ impl ExprParser for Expr {
                             if_expr.or_else = Some(Box::new(ExpressionEnum::If(If {
                                 test: None,
                                 body: remain_first_expr.into_iter().chain(remain_exprs).collect(),
-                                start_line: remain_instructions
-                                    .first()
-                                    .unwrap()
-                                    .starts_line
-                                    .unwrap_or_default(),
-                                start_offset: remain_instructions.first().unwrap().offset,
-                                end_offset: remain_instructions.last().unwrap().offset,
+                                start_line,
+                                start_offset,
+                                end_offset,
                                 ..Default::default()
                             })));
                         }
@@ -1450,16 +2088,21 @@ impl ExprParser for Expr {
                         exprs_stack.push(ExpressionEnum::If(if_expr));
                     }
                 }
+                Opcode::JumpIfFalseOrPop | Opcode::JumpIfTrueOrPop => {
+                    let end = bool_chain_end(opcode_instructions, offset).ok_or(format!(
+                        "[JumpIfOrPop] Invalid jump, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    let left = exprs_stack.pop().ok_or(format!(
+                        "[JumpIfOrPop] Stack is empty, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(parse_bool_chain(opcode_instructions, offset, left, end)?);
+                    offset = end;
+                    continue;
+                }
                 Opcode::JumpForward => {
-                    let jump_target = instruction
-                        .argval
-                        .as_ref()
-                        .ok_or(format!(
-                            "[JumpForward] No argval, deviation is {}",
-                            instruction.offset
-                        ))?
-                        .trim_start_matches("to ")
-                        .parse::<usize>()?;
+                    let jump_target = instruction.jump_target()?;
                     exprs_stack.push(ExpressionEnum::Jump(Jump {
                         target: jump_target,
                         start_line: instruction.starts_line.unwrap_or_default(),
@@ -1469,15 +2112,7 @@ impl ExprParser for Expr {
                     }));
                 }
                 Opcode::JumpBackward => {
-                    let jump_target = instruction
-                        .argval
-                        .as_ref()
-                        .ok_or(format!(
-                            "[JumpBackward] No argval, deviation is {}",
-                            instruction.offset
-                        ))?
-                        .trim_start_matches("to ")
-                        .parse::<usize>()?;
+                    let jump_target = instruction.jump_target()?;
                     exprs_stack.push(ExpressionEnum::Jump(Jump {
                         target: jump_target,
                         is_backward: true,
@@ -1486,350 +2121,76 @@ impl ExprParser for Expr {
                         end_offset: instruction.offset,
                     }));
                 }
-                Opcode::LoadAssertionError => {
-                    let test = exprs_stack.pop().ok_or(format!(
-                        "[LoadAssertionError] Stack is empty, deviation is {}",
+                // the compiler re-raises by `RERAISE` in the cleanup, a written `raise` is `RAISE_VARARGS 0`
+                Opcode::Reraise => {}
+                // raise, raise exc, raise exc from cause
+                Opcode::RaiseVarargs => {
+                    let count = instruction.arg.ok_or(format!(
+                        "[RaiseVarargs] No arg, deviation is {}",
                         instruction.offset
                     ))?;
-
-                    exprs_stack.push(ExpressionEnum::Assert(Assert {
-                        test: Box::new(test),
-                        msg: None,
+                    let mut args = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        args.push(exprs_stack.pop().ok_or(format!(
+                            "[RaiseVarargs] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?);
+                    }
+                    let exception = args.pop().map(Box::new);
+                    let cause = args.pop().map(Box::new);
+                    exprs_stack.push(ExpressionEnum::Raise(Raise {
+                        exception,
+                        cause,
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
                         end_offset: instruction.offset,
-                    }))
-                }
-                Opcode::RaiseVarargs => {
-                    let expr = exprs_stack.pop().ok_or(format!(
-                        "[RaiseVarargs] Stack is empty, deviation is {}",
-                        instruction.offset
-                    ))?;
-                    if expr.is_base_value() {
-                        let exception = expr;
-                        if let Some(expr) = exprs_stack.pop() {
-                            let mut has_assert = false;
-                            if let Ok(assert) = expr.query_singleton::<Assert>() {
-                                has_assert = true;
-                                assert
-                                    .with_mut_unchecked()
-                                    .patch_by(|mut a| a.msg = Some(Box::new(exception.clone())))?;
-                            }
-                            exprs_stack.push(expr);
-
-                            // if hasn't assert, then it just a raise
-                            if !has_assert {
-                                exprs_stack.push(ExpressionEnum::Raise(Raise {
-                                    exception: Box::new(exception),
-                                    start_line: instruction.starts_line.unwrap_or_default(),
-                                    start_offset: instruction.offset,
-                                    end_offset: instruction.offset,
-                                }))
-                            }
-                        } else {
-                            exprs_stack.push(ExpressionEnum::Raise(Raise {
-                                exception: Box::new(exception),
-                                start_line: instruction.starts_line.unwrap_or_default(),
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                            }))
-                        }
-                    } else {
-                        #[cfg(debug_assertions)]
-                        {
-                            assert!(expr.is_assert());
-                        }
-                        //
-                    }
+                    }));
                 }
-                Opcode::CheckExcMatch => {
-                    let err = exprs_stack.pop().ok_or(format!(
-                        "[CheckExcMatch] Stack is empty, deviation is {}",
+                Opcode::BeforeWith | Opcode::BeforeAsyncWith => {
+                    let context_expr = exprs_stack.pop().ok_or(format!(
+                        "[BeforeWith] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-                    if let Some(store_name_instruction_idx) = opcode_instructions[offset..]
-                        .iter()
-                        .position(|x| x.opcode() == Opcode::StoreName)
-                    {
-                        let store_name_instruction =
-                            &opcode_instructions[offset + store_name_instruction_idx];
-                        let alias = store_name_instruction.argval.as_ref().ok_or(format!(
-                            "[CheckExcMatch] No argval, deviation is {}",
-                            instruction.offset
-                        ))?;
-
-                        offset += store_name_instruction_idx;
-
-                        exprs_stack.push(ExpressionEnum::Except(Except {
-                            exception: Box::new(ExpressionEnum::Alias(Alias {
-                                target: Box::new(err),
-                                alias: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                                    value: alias.clone(),
-                                    ..Default::default()
-                                })),
-                                ..Default::default()
-                            })),
-                            body: vec![],
-                            start_line: instruction.starts_line.unwrap_or_default(),
-                            start_offset: instruction.offset,
-                            end_offset: instruction.offset,
-                        }));
-                    } else {
-                        exprs_stack.push(ExpressionEnum::Except(Except {
-                            exception: Box::new(err),
-                            body: vec![],
-                            start_line: instruction.starts_line.unwrap_or_default(),
-                            start_offset: instruction.offset,
-                            end_offset: instruction.offset,
-                        }))
-                    }
+                    let (with, end) = parse_with(opcode_instructions, offset, context_expr)?;
+                    exprs_stack.push(ExpressionEnum::With(with));
+                    offset = end;
+                    continue;
                 }
-                Opcode::BeforeWith => {
-                    let expr = exprs_stack.pop().ok_or(format!(
-                        "[BeforeWith] Stack is empty, deviation is {}",
-                        instruction.offset
+                Opcode::ForIter => {
+                    let iter = exprs_stack.pop().ok_or(format!(
+                        "[ForIter] Stack is empty, deviation is {}",
+                        instruction.offset,
                     ))?;
-                    let mut with = With {
+                    let jump_target = instruction.jump_target()?;
+                    let (items, end) = parse_target(opcode_instructions, offset + 1)?;
+                    exprs_stack.push(ExpressionEnum::For(For {
+                        iterator: Box::new(iter),
+                        items: Box::new(items),
+                        from: instruction.offset,
+                        to: jump_target,
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
+                        end_offset: instruction.offset,
                         ..Default::default()
-                    };
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::StoreName
-                            || next_instruction.opcode() == Opcode::StoreFast
-                        {
-                            let name = next_instruction.argval.as_ref().ok_or(format!(
-                                "[BeforeWith] No argval, deviation is {}",
-                                instruction.offset
-                            ))?;
-                            with.item = Box::new(ExpressionEnum::Alias(Alias {
-                                target: Box::new(expr),
-                                alias: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                                    value: name.clone(),
-                                    ..Default::default()
-                                })),
-                                ..Default::default()
-                            }));
-                        } else {
-                            with.item = Box::new(expr);
+                    }));
+                    offset = end;
+                    continue;
+                }
+                Opcode::GetAiter => {
+                    let aiter = exprs_stack.pop().ok_or(format!(
+                        "[GetAiter] Stack is empty, deviation is {}",
+                        instruction.offset,
+                    ))?;
+
+                    // find SEND instruction
+                    let mut send_to = 0;
+                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
+                        if next_instruction.opcode() == Opcode::Send {
+                            offset += 1;
+                            send_to = next_instruction.jump_target()?;
+                            break;
                         }
-                    }
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 2) {
-                        with.end_offset = next_instruction.offset;
-                    }
-
-                    // get with block
-                    let sub_instructions = &opcode_instructions[offset + 2..];
-                    #[cfg(debug_assertions)]
-                    {
-                        //dbg!(&sub_instructions);
-                    }
-                    let block_end_idxs = sub_instructions
-                        .iter()
-                        .enumerate()
-                        .filter(|(_, x)| x.starts_line == Some(with.start_line))
-                        .map(|(i, _)| i)
-                        .collect::<Vec<_>>();
-                    let block_end_first_idx = *block_end_idxs.first().ok_or(format!(
-                        "[BeforeWith] No block end, deviation is {}",
-                        instruction.offset
-                    ))?;
-                    let block_end_last_idx = *block_end_idxs.last().unwrap();
-                    let sub_instructions =
-                        &opcode_instructions[offset + 2..offset + 2 + block_end_first_idx];
-                    #[cfg(debug_assertions)]
-                    {
-                        //dbg!(&sub_instructions);
-                    }
-                    let sub_expr = Self::parse(sub_instructions)?;
-                    with.body = sub_expr.bodys;
-                    // skip the offset to the end of with block
-                    offset += 2 + block_end_last_idx;
-
-                    exprs_stack.push(ExpressionEnum::With(with));
-                }
-                Opcode::BeforeAsyncWith => {
-                    let expr = exprs_stack.pop().ok_or(format!(
-                        "[BeforeWith] Stack is empty, deviation is {}",
-                        instruction.offset
-                    ))?;
-                    let mut async_with = With {
-                        start_line: instruction.starts_line.unwrap_or_default(),
-                        start_offset: instruction.offset,
-                        is_async: true,
-                        ..Default::default()
-                    };
-
-                    // find SEND instruction
-                    let mut send_to = 0;
-                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::Send {
-                            offset += 1;
-                            send_to = next_instruction
-                                .argval
-                                .as_ref()
-                                .ok_or(format!(
-                                    "[BeforeAsyncWith] No argval, deviation is {}",
-                                    next_instruction.offset
-                                ))?
-                                .trim_start_matches("to ")
-                                .parse::<usize>()?;
-                            break;
-                        }
-                        offset += 1;
-                    }
-
-                    // skip the offset to the <send_to>
-                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        match next_instruction.offset.cmp(&send_to) {
-                            Ordering::Equal => break,
-                            Ordering::Greater => {
-                                return Err(format!(
-                                    "[BeforeAsyncWith] Send target not found, deviation is {}",
-                                    next_instruction.offset
-                                )
-                                .into());
-                            }
-                            _ => {}
-                        }
-                        offset += 1;
-                    }
-
-                    // check has alias
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::StoreName
-                            || next_instruction.opcode() == Opcode::StoreFast
-                        {
-                            let name = next_instruction.argval.as_ref().ok_or(format!(
-                                "[BeforeAsyncWith] No argval, deviation is {}",
-                                instruction.offset
-                            ))?;
-                            async_with.item = Box::new(ExpressionEnum::Alias(Alias {
-                                target: Box::new(expr),
-                                alias: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                                    value: name.clone(),
-                                    ..Default::default()
-                                })),
-                                ..Default::default()
-                            }));
-                        } else {
-                            async_with.item = Box::new(expr);
-                        }
-                    }
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 2) {
-                        async_with.end_offset = next_instruction.offset;
-                    }
-
-                    // get <with> block
-                    let sub_instructions = &opcode_instructions[offset + 2..];
-                    let block_end_idxs = sub_instructions
-                        .iter()
-                        .enumerate()
-                        .filter(|(_, x)| x.starts_line == Some(async_with.start_line))
-                        .map(|(i, _)| i)
-                        .collect::<Vec<_>>();
-                    let block_end_first_idx = *block_end_idxs.first().ok_or(format!(
-                        "[BeforeAsyncWith] No block end, deviation is {}",
-                        instruction.offset
-                    ))?;
-                    let block_end_last_idx = *block_end_idxs.last().unwrap();
-                    let sub_instructions =
-                        &opcode_instructions[offset + 2..offset + 2 + block_end_first_idx];
-                    #[cfg(debug_assertions)]
-                    {
-                        //dbg!(&sub_instructions);
-                    }
-                    let sub_expr = Self::parse(sub_instructions)?;
-                    async_with.body = sub_expr.bodys;
-                    // skip the offset to the end of with block
-                    offset += 2 + block_end_last_idx;
-
-                    exprs_stack.push(ExpressionEnum::With(async_with));
-                }
-                Opcode::ForIter => {
-                    let iter = exprs_stack.pop().ok_or(format!(
-                        "[ForIter] Stack is empty, deviation is {}",
-                        instruction.offset,
-                    ))?;
-                    let jump_target = instruction
-                        .argval
-                        .as_ref()
-                        .ok_or(format!(
-                            "[ForIter] No argval, deviation is {}",
-                            instruction.offset
-                        ))?
-                        .split("to ")
-                        .last()
-                        .ok_or(format!(
-                            "[ForIter] Invalid argval, expect \"to /* usize */\", but got {:?}",
-                            instruction.argval.as_ref().unwrap()
-                        ))?
-                        .parse::<usize>()?;
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::StoreName
-                            || next_instruction.opcode() == Opcode::StoreFast
-                        {
-                            exprs_stack.push(ExpressionEnum::For(For {
-                                iterator: Box::new(iter),
-                                items: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                                    value: next_instruction
-                                        .argval
-                                        .as_ref()
-                                        .ok_or(format!(
-                                            "[ForIter] No argval, deviation is {}",
-                                            next_instruction.offset
-                                        ))?
-                                        .clone(),
-                                    ..Default::default()
-                                })),
-                                from: instruction.offset,
-                                to: jump_target,
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                                ..Default::default()
-                            }));
-                            offset += 1;
-                        } else {
-                            exprs_stack.push(ExpressionEnum::For(For {
-                                iterator: Box::new(iter),
-                                from: instruction.offset,
-                                to: jump_target,
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                                ..Default::default()
-                            }))
-                        }
-                    } else {
-                        return Err(format!(
-                            "[ForIter] No next instruction, deviation is {}",
-                            instruction.offset,
-                        )
-                        .into());
-                    }
-                }
-                Opcode::GetAiter => {
-                    let aiter = exprs_stack.pop().ok_or(format!(
-                        "[GetAiter] Stack is empty, deviation is {}",
-                        instruction.offset,
-                    ))?;
-
-                    // find SEND instruction
-                    let mut send_to = 0;
-                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::Send {
-                            offset += 1;
-                            send_to = next_instruction
-                                .argval
-                                .as_ref()
-                                .ok_or(format!(
-                                    "[GetAiter] No argval, deviation is {}",
-                                    next_instruction.offset
-                                ))?
-                                .trim_start_matches("to ")
-                                .parse::<usize>()?;
-                            break;
-                        }
-                        offset += 1;
+                        offset += 1;
                     }
 
                     // skip the offset to the <send_to>
@@ -1848,49 +2209,27 @@ impl ExprParser for Expr {
                         offset += 1;
                     }
 
-                    if let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::StoreName
-                            || next_instruction.opcode() == Opcode::StoreFast
-                        {
-                            exprs_stack.push(ExpressionEnum::For(For {
-                                iterator: Box::new(aiter),
-                                items: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                                    value: next_instruction
-                                        .argval
-                                        .as_ref()
-                                        .ok_or(format!(
-                                            "[GetAiter] No argval, deviation is {}",
-                                            next_instruction.offset
-                                        ))?
-                                        .clone(),
-                                    ..Default::default()
-                                })),
-                                from: instruction.offset,
-                                is_async: true,
-                                start_line: instruction.starts_line.unwrap_or_default(),
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                                ..Default::default()
-                            }));
-                            offset += 1;
-                        } else {
-                            exprs_stack.push(ExpressionEnum::For(For {
-                                iterator: Box::new(aiter),
-                                from: instruction.offset,
-                                is_async: true,
-                                start_line: instruction.starts_line.unwrap_or_default(),
-                                start_offset: instruction.offset,
-                                end_offset: instruction.offset,
-                                ..Default::default()
-                            }))
-                        }
-                    } else {
-                        return Err(format!(
-                            "[GetAiter] No next instruction, deviation is {}",
-                            instruction.offset,
-                        )
-                        .into());
+                    // 3.12: the target is stored after `END_SEND`
+                    let mut target_start = offset + 1;
+                    if opcode_instructions
+                        .get(target_start)
+                        .is_some_and(|x| x.opcode() == Opcode::EndSend)
+                    {
+                        target_start += 1;
                     }
+                    let (items, end) = parse_target(opcode_instructions, target_start)?;
+                    exprs_stack.push(ExpressionEnum::For(For {
+                        iterator: Box::new(aiter),
+                        items: Box::new(items),
+                        from: instruction.offset,
+                        is_async: true,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: instruction.offset,
+                        ..Default::default()
+                    }));
+                    offset = end;
+                    continue;
                 }
                 Opcode::EndAsyncFor => {
                     let mut async_for_block = vec![];
@@ -1916,503 +2255,5547 @@ impl ExprParser for Expr {
                         "[GetAwaitable] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
+                    let end = send_loop_end(opcode_instructions, offset).ok_or(format!(
+                        "[GetAwaitable] No send loop, deviation is {}",
+                        instruction.offset
+                    ))?;
                     exprs_stack.push(ExpressionEnum::Await(Await {
                         awaitable_expr: Box::new(awaitable_expr),
                         start_line: instruction.starts_line.unwrap_or_default(),
                         start_offset: instruction.offset,
-                        end_offset: instruction.offset,
+                        end_offset: opcode_instructions[end].offset,
                     }));
-
-                    // find SEND instruction
-                    let mut send_to = 0;
-                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        if next_instruction.opcode() == Opcode::Send {
-                            offset += 1;
-                            send_to = next_instruction
-                                .argval
-                                .as_ref()
-                                .ok_or(format!(
-                                    "[GetAwaitable] No argval, deviation is {}",
-                                    next_instruction.offset
-                                ))?
-                                .trim_start_matches("to ")
-                                .parse::<usize>()?;
-                            break;
-                        }
-                        offset += 1;
-                    }
-
-                    // skip the offset to the <send_to>
-                    while let Some(next_instruction) = opcode_instructions.get(offset + 1) {
-                        match next_instruction.offset.cmp(&send_to) {
-                            Ordering::Equal => break,
-                            Ordering::Greater => {
-                                return Err(format!(
-                                    "[GetAwaitable] Send target not found, deviation is {}",
-                                    next_instruction.offset
-                                )
-                                .into())
-                            }
-                            _ => {}
-                        }
-                        offset += 1;
-                    }
+                    offset = end;
                 }
-                Opcode::UnpackSequence => {
-                    let mut count = instruction.arg.ok_or(format!(
-                        "[UnpackSequence] No arg, deviation is {}",
+                Opcode::GetYieldFromIter => {
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[GetYieldFromIter] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-                    let mut sequence = Vec::with_capacity(count);
-                    loop {
-                        if count == 0 {
-                            break;
+                    let end = send_loop_end(opcode_instructions, offset).ok_or(format!(
+                        "[GetYieldFromIter] No send loop, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    exprs_stack.push(ExpressionEnum::YieldFrom(YieldFrom {
+                        value: Box::new(value),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: opcode_instructions[end].offset,
+                    }));
+                    offset = end;
+                }
+                Opcode::DeleteName
+                | Opcode::DeleteFast
+                | Opcode::DeleteGlobal
+                | Opcode::DeleteDeref
+                | Opcode::DeleteAttr
+                | Opcode::DeleteSubscr => {
+                    if is_exception_cleanup(opcode_instructions, offset) {
+                        // drop the `e = None` before it
+                        if exprs_stack.last().is_some_and(|x| x.is_assign()) {
+                            exprs_stack.pop();
                         }
-                        let next_instruction =
-                            opcode_instructions.get(offset + 1).ok_or(format!(
-                                "[UnpackSequence] No next instruction, diviation is {}",
+                        offset += 1;
+                        continue;
+                    }
+                    let target = match instruction.opcode() {
+                        Opcode::DeleteAttr => {
+                            let parent = exprs_stack.pop().ok_or(format!(
+                                "[DeleteAttr] Stack is empty, deviation is {}",
                                 instruction.offset
                             ))?;
-                        match next_instruction.opcode() {
-                            Opcode::StoreName | Opcode::StoreFast => {
-                                let name = next_instruction
-                                    .argval
-                                    .as_ref()
-                                    .ok_or(format!(
-                                        "[UnpackSequence] No argval, diviation is {}",
-                                        next_instruction.offset
-                                    ))?
-                                    .clone();
-                                if let Some(ExpressionEnum::Container(sub_seq)) = sequence.last() {
-                                    if sub_seq.values.len() < sub_seq.values.capacity() {
-                                        sub_seq.with_mut_unchecked().patch_by(|mut container| {
-                                            container.values.push(ExpressionEnum::BaseValue(
-                                                BaseValue {
-                                                    value: name,
-                                                    start_offset: next_instruction.offset,
-                                                    end_offset: next_instruction.offset,
-                                                    ..Default::default()
-                                                },
-                                            ))
-                                        })?;
-                                    }
-                                    if sub_seq.values.len() == sub_seq.values.capacity() {
-                                        count -= 1;
-                                    }
-                                } else {
-                                    sequence.push(ExpressionEnum::BaseValue(BaseValue {
-                                        value: name,
-                                        start_offset: next_instruction.offset,
-                                        end_offset: next_instruction.offset,
-                                        ..Default::default()
-                                    }));
-                                    count -= 1;
-                                }
-                            }
-                            Opcode::UnpackSequence => {
-                                let sub_count = next_instruction.arg.ok_or(format!(
-                                    "[UnpackSequence] No arg, diviation is {}",
-                                    next_instruction.offset
-                                ))?;
-                                let sub_sequence = Vec::with_capacity(sub_count);
-                                sequence.push(ExpressionEnum::Container(Container {
-                                    values: sub_sequence,
-                                    start_offset: next_instruction.offset,
-                                    end_offset: next_instruction.offset,
+                            ExpressionEnum::Attribute(Attribute {
+                                parent: Box::new(parent),
+                                attr: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                                    value: instruction.argval.clone().ok_or(format!(
+                                        "[DeleteAttr] No argval, deviation is {}",
+                                        instruction.offset
+                                    ))?,
                                     ..Default::default()
-                                }))
-                            }
-                            _ => {
-                                return Err(format!(
-                                "[UnpackSequence] Expect StoreName, StoreFast or UnpackSequence, but got {:?}",
-                                next_instruction.opcode()
-                            )
-                                .into())
-                            }
+                                })),
+                                start_offset: instruction.offset,
+                                end_offset: instruction.offset,
+                                ..Default::default()
+                            })
                         }
-                        offset += 1;
-                    }
-                    if let Some(ExpressionEnum::For(for_expr)) = exprs_stack.last() {
-                        for_expr.with_mut_unchecked().patch_by(|mut f| {
-                            f.items = Box::new(ExpressionEnum::Container(Container {
-                                values: sequence,
+                        Opcode::DeleteSubscr => {
+                            let index = exprs_stack.pop().ok_or(format!(
+                                "[DeleteSubscr] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            let target = exprs_stack.pop().ok_or(format!(
+                                "[DeleteSubscr] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            ExpressionEnum::Subscr(Subscr {
+                                target: Box::new(target),
+                                index: Box::new(index),
                                 start_offset: instruction.offset,
                                 end_offset: instruction.offset,
                                 ..Default::default()
-                            }))
-                        })?;
+                            })
+                        }
+                        _ => ExpressionEnum::BaseValue(BaseValue {
+                            value: instruction.argval.clone().ok_or(format!(
+                                "[Delete] No argval, deviation is {}",
+                                instruction.offset
+                            ))?,
+                            ..Default::default()
+                        }),
+                    };
+                    let start_line = instruction.starts_line.unwrap_or_default();
+                    // del a, b[0], c.x 在同一行里依次删除
+                    if let Some(ExpressionEnum::Delete(delete)) = exprs_stack.last_mut()
+                        && delete.start_line == start_line
+                    {
+                        delete.targets.push(target);
+                        delete.end_offset = instruction.offset;
                     } else {
-                        return Err(format!(
-                            "[UnpackSequence] Expect <For> expr, but got {:?}",
-                            exprs_stack.last()
-                        )
-                        .into());
+                        exprs_stack.push(ExpressionEnum::Delete(Delete {
+                            targets: vec![target],
+                            start_line,
+                            start_offset: instruction.offset,
+                            end_offset: instruction.offset,
+                        }));
                     }
                 }
-                Opcode::Copy => {
-                    let count = instruction.arg.ok_or(format!(
-                        "[Copy] No arg, deviation is {}",
+                Opcode::UnpackSequence | Opcode::UnpackEx => {
+                    let value = exprs_stack.pop().ok_or(format!(
+                        "[UnpackSequence] Stack is empty, deviation is {}",
                         instruction.offset
                     ))?;
-
-                    if count <= exprs_stack.len() {
-                        let copy_exprs = exprs_stack
-                            .iter()
-                            .skip(exprs_stack.len() - count)
-                            .cloned()
-                            .collect::<Vec<_>>();
-                        exprs_stack.extend(copy_exprs);
-                    } else {
-                        return Err(format!(
-                            "[Copy] Stack is empty, deviation is {}",
-                            instruction.offset
-                        )
-                        .into());
-                    }
+                    let (target, end) = parse_target(opcode_instructions, offset)?;
+                    exprs_stack.push(ExpressionEnum::Assign(Assign {
+                        target: Box::new(target),
+                        values: Box::new(value),
+                        operator: "=".to_string(),
+                        chain: Vec::new(),
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: instruction.offset,
+                        end_offset: opcode_instructions[end - 1].offset,
+                    }));
+                    offset = end;
+                    continue;
                 }
-                Opcode::Swap => {
+                Opcode::Copy => {
                     let count = instruction.arg.ok_or(format!(
-                        "[Swap] No arg, deviation is {}",
+                        "[Copy] No arg, deviation is {}",
                         instruction.offset
                     ))?;
-                    if count <= exprs_stack.len() {
-                        let mut swap_exprs = exprs_stack
-                            .iter()
-                            .skip(exprs_stack.len() - count)
-                            .cloned()
-                            .collect::<Vec<_>>();
-                        swap_exprs.reverse();
-                        exprs_stack.truncate(exprs_stack.len() - count);
-                        exprs_stack.extend(swap_exprs);
-                    } else {
-                        return Err(format!(
-                            "[Swap] Stack is empty, deviation is {}",
+
+                    // 3.12: `COPY 1` + `POP_JUMP_IF_*` + `POP_TOP` is the short-circuit of `and` / `or`
+                    if let Some(end) = bool_chain_end(opcode_instructions, offset) {
+                        let left = exprs_stack.pop().ok_or(format!(
+                            "[BoolOp] Stack is empty, deviation is {}",
                             instruction.offset
-                        )
-                        .into());
+                        ))?;
+                        exprs_stack.push(parse_bool_chain(opcode_instructions, offset, left, end)?);
+                        offset = end;
+                        continue;
                     }
-                }
+
+                    // `COPY 1` + store is a chained assignment or a walrus, e.g. a = b = 1, (n := 1)
+                    if count == 1
+                        && let Some((target, next)) = copy_target(opcode_instructions, offset + 1)
+                    {
+                        let mut targets = vec![target];
+                        let mut end = next;
+                        while opcode_instructions
+                            .get(end)
+                            .is_some_and(|x| x.opcode() == Opcode::Copy && x.arg == Some(1))
+                            && let Some((target, next)) = copy_target(opcode_instructions, end + 1)
+                        {
+                            targets.push(target);
+                            end = next;
+                        }
+                        let last = copy_target(opcode_instructions, end);
+                        // only a name can be the target of a walrus
+                        if last.is_some() || targets.iter().all(|x| x.is_base_value()) {
+                            let value = exprs_stack.pop().ok_or(format!(
+                                "[Copy] Stack is empty, deviation is {}",
+                                instruction.offset
+                            ))?;
+                            if let Some((target, next)) = last {
+                                exprs_stack.push(ExpressionEnum::Assign(Assign {
+                                    target: Box::new(target),
+                                    chain: targets,
+                                    values: Box::new(value),
+                                    operator: "=".to_string(),
+                                    start_line: instruction.starts_line.unwrap_or_default(),
+                                    start_offset: instruction.offset,
+                                    end_offset: opcode_instructions[next - 1].offset,
+                                }));
+                                offset = next;
+                            } else {
+                                let value = targets.into_iter().fold(value, |value, target| {
+                                    ExpressionEnum::NamedExpr(NamedExpr {
+                                        target: Box::new(target),
+                                        value: Box::new(value),
+                                        start_line: instruction.starts_line.unwrap_or_default(),
+                                        start_offset: instruction.offset,
+                                        end_offset: opcode_instructions[end - 1].offset,
+                                    })
+                                });
+                                exprs_stack.push(value);
+                                offset = end;
+                            }
+                            continue;
+                        }
+                    }
+
+                    // push a copy of the count-th item (counted from the top)
+                    if count <= exprs_stack.len() && count > 0 {
+                        exprs_stack.push(exprs_stack[exprs_stack.len() - count].clone());
+                    } else {
+                        return Err(format!(
+                            "[Copy] Stack is empty, deviation is {}",
+                            instruction.offset
+                        )
+                        .into());
+                    }
+                }
+                Opcode::Swap => {
+                    let count = instruction.arg.ok_or(format!(
+                        "[Swap] No arg, deviation is {}",
+                        instruction.offset
+                    ))?;
+                    if is_compare_chain_start(opcode_instructions, offset) {
+                        let first = exprs_stack.pop().ok_or(format!(
+                            "[Compare] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        let left = exprs_stack.pop().ok_or(format!(
+                            "[Compare] Stack is empty, deviation is {}",
+                            instruction.offset
+                        ))?;
+                        let (compare, end) =
+                            parse_compare_chain(opcode_instructions, offset, left, first)?;
+                        exprs_stack.push(compare);
+                        offset = end;
+                        continue;
+                    }
+                    // swap the top item with the count-th item
+                    if count <= exprs_stack.len() && count > 0 {
+                        let len = exprs_stack.len();
+                        exprs_stack.swap(len - 1, len - count);
+                    } else {
+                        return Err(format!(
+                            "[Swap] Stack is empty, deviation is {}",
+                            instruction.offset
+                        )
+                        .into());
+                    }
+                }
                 _ => {}
             }
 
             offset += 1;
         }
 
+        // note the statement whose call lost its keyword names
+        for kw_offset in unknown_kw_names.into_iter().rev() {
+            let idx = exprs_stack
+                .iter()
+                .position(|expr| expr.get_offset().1 >= kw_offset)
+                .unwrap_or(exprs_stack.len());
+            exprs_stack.insert(
+                idx,
+                ExpressionEnum::Comment(Comment {
+                    lines: vec![format!(
+                        "pyrev: unknown keyword names at offset {}, \
+                         the keywords are shown as positional arguments",
+                        kw_offset
+                    )],
+                    ..Default::default()
+                }),
+            );
+        }
+
         Ok(Box::new(Self::from(exprs_stack)))
     }
 }
 
-pub fn get_trace(opcode_instructions: &[OpcodeInstruction]) -> Result<TraceBack> {
-    let mut traceback = TraceBack::default();
+/// 条件跳转
+///
+/// 3.12 把 `JUMP_IF_FALSE_OR_POP` / `JUMP_IF_TRUE_OR_POP` 拆成了 `COPY 1` + `POP_JUMP_IF_*` + `POP_TOP`
+/// 这里把两种形式统一起来
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CondJump {
+    /// jump if the value is true (`or`), otherwise jump if the value is false (`and`)
+    if_true: bool,
+    /// the value is left on the stack when jumping
+    keep: bool,
+    /// the index of the jump target
+    target: usize,
+    /// the index range of the instructions which make up this jump
+    start: usize,
+    end: usize,
+}
 
-    for instruction in opcode_instructions {
-        match instruction.opcode() {
-            Opcode::StoreFast => {
-                let arg = instruction.arg.as_ref().ok_or(format!(
-                    "[Trace] No arg, deviation is {}",
-                    instruction.offset
-                ))?;
-                let name = instruction.argval.as_ref().ok_or(format!(
-                    "[Trace] No argval, deviation is {}",
-                    instruction.offset
-                ))?;
+/// 一个短路求值链的节点
+struct ChainNode {
+    expr: ExpressionEnum,
+    /// (if_true, target)
+    jump: Option<(bool, usize)>,
+    start: usize,
+}
 
-                if let Some(local) = traceback.get_mut_local(arg) {
-                    if !local.is_store {
-                        // do not store fast before, it's an arguement for function
-                        local.is_arg = true;
-                    } else {
-                        // store fast after load fast, not arguement for function
-                        local.is_arg = false;
-                    }
-                } else {
-                    // store fast before load fast, not arguement for function
-                    traceback.insert_local(
-                        *arg,
-                        Local {
-                            name: name.clone(),
-                            is_store: true,
-                            is_arg: false,
-                        },
-                    );
+/// 找到跳转目标在指令中的下标
+/// 如果跳转到了这段指令的后面, 则返回指令的长度
+fn jump_target_idx(opcode_instructions: &[OpcodeInstruction], target: usize) -> Option<usize> {
+    opcode_instructions
+        .iter()
+        .position(|x| x.offset == target)
+        .or_else(|| {
+            opcode_instructions
+                .last()
+                .filter(|x| x.offset < target)
+                .map(|_| opcode_instructions.len())
+        })
+}
+
+fn cond_jump_at(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<CondJump> {
+    // the backward jumps belong to loops, never to expressions
+    let is_pop_jump = |x: &OpcodeInstruction| {
+        matches!(
+            x.opname.as_str(),
+            "POP_JUMP_IF_FALSE"
+                | "POP_JUMP_IF_TRUE"
+                | "POP_JUMP_FORWARD_IF_FALSE"
+                | "POP_JUMP_FORWARD_IF_TRUE"
+        )
+    };
+    let target_of =
+        |x: &OpcodeInstruction| jump_target_idx(opcode_instructions, x.jump_target().ok()?);
+    let instruction = opcode_instructions.get(idx)?;
+    match instruction.opname.as_str() {
+        "JUMP_IF_FALSE_OR_POP" | "JUMP_IF_TRUE_OR_POP" => Some(CondJump {
+            if_true: instruction.opname == "JUMP_IF_TRUE_OR_POP",
+            keep: true,
+            target: target_of(instruction)?,
+            start: idx,
+            end: idx + 1,
+        }),
+        "COPY" if instruction.arg == Some(1) => {
+            let jump = opcode_instructions
+                .get(idx + 1)
+                .filter(|x| is_pop_jump(x))?;
+            opcode_instructions
+                .get(idx + 2)
+                .filter(|x| x.opname == "POP_TOP")?;
+            Some(CondJump {
+                if_true: jump.opname.ends_with("TRUE"),
+                keep: true,
+                target: target_of(jump)?,
+                start: idx,
+                end: idx + 3,
+            })
+        }
+        _ if is_pop_jump(instruction) => {
+            if idx > 0
+                && let Some(jump) = cond_jump_at(opcode_instructions, idx - 1)
+                && jump.end == idx + 2
+            {
+                // it's a part of `COPY 1` + `POP_JUMP_IF_*` + `POP_TOP`
+                return Some(jump);
+            }
+            Some(CondJump {
+                if_true: instruction.opname.ends_with("TRUE"),
+                keep: false,
+                target: target_of(instruction)?,
+                start: idx,
+                end: idx + 1,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// 3.12 中 `a and b or c` 的 `and` 会跳到 `or` 的 `COPY 1` 上
+/// 这里把跳转目标修正为真正继续执行的位置
+fn resolve_cond_jump(opcode_instructions: &[OpcodeInstruction], jump: CondJump) -> CondJump {
+    let mut jump = jump;
+    while jump.keep
+        && let Some(next) = cond_jump_at(opcode_instructions, jump.target)
+        && next.keep
+        && next.start == jump.target
+    {
+        if next.if_true == jump.if_true {
+            jump.target = next.target;
+        } else {
+            jump.target = next.end;
+        }
+    }
+    jump
+}
+
+fn cond_jump_ending_at(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<CondJump> {
+    [1, 3]
+        .iter()
+        .filter_map(|d| idx.checked_sub(*d))
+        .filter_map(|i| cond_jump_at(opcode_instructions, i))
+        .find(|jump| jump.end == idx)
+        .map(|jump| resolve_cond_jump(opcode_instructions, jump))
+}
+
+/// 判断`idx`处的条件跳转是否是一个有值的短路求值链(and/or)的开始
+/// 如果是, 返回链结束的位置(也就是使用这个值的指令)
+fn bool_chain_end(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<usize> {
+    let mut jump = resolve_cond_jump(opcode_instructions, cond_jump_at(opcode_instructions, idx)?);
+    if jump.start != idx {
+        return None;
+    }
+    let mut has_keep = jump.keep;
+    while let Some(prev) = cond_jump_ending_at(opcode_instructions, jump.target) {
+        if prev.start <= jump.start {
+            return None;
+        }
+        has_keep |= prev.keep;
+        jump = prev;
+    }
+    has_keep.then_some(jump.target)
+}
+
+/// 合并链上的节点, 直到只剩一个节点
+///
+/// - 相邻的两个节点跳转到同一个地方, 说明它们是同一种运算
+/// - 一个节点刚好跳过了下一个节点, 说明它和下一个节点是一组
+fn reduce_chain(mut nodes: Vec<ChainNode>, end: usize) -> Option<ChainNode> {
+    while nodes.len() > 1 {
+        let i = (0..nodes.len() - 1).find(|&i| {
+            let Some(jump) = nodes[i].jump else {
+                return false;
+            };
+            nodes[i + 1].jump == Some(jump) || jump.1 == nodes.get(i + 2).map_or(end, |n| n.start)
+        })?;
+        let right = nodes.remove(i + 1);
+        let (if_true, _) = nodes[i].jump?;
+        let left = std::mem::take(&mut nodes[i].expr);
+        nodes[i].expr = combine_bool_op(if if_true { "or" } else { "and" }, left, right.expr);
+        nodes[i].jump = right.jump;
+    }
+    nodes.pop()
+}
+
+/// 合并 if 的条件链上的节点, 和 [`reduce_chain`] 不同的是跳转不会带着值,
+/// 跳到 `end` (if 代码块的开始) 表示条件成立, 最后一个节点的跳转表示条件不成立
+///
+/// 返回合并后的条件, 以及它在什么时候跳转 (`if_true`) 和跳转的目标
+fn reduce_condition(
+    mut nodes: Vec<ChainNode>,
+    end: usize,
+) -> Option<(ExpressionEnum, bool, usize)> {
+    while nodes.len() > 1 {
+        let i = (0..nodes.len() - 1).find(|&i| {
+            let Some(jump) = nodes[i].jump else {
+                return false;
+            };
+            nodes[i + 1].jump == Some(jump) || jump.1 == nodes.get(i + 2).map_or(end, |n| n.start)
+        })?;
+        let right = nodes.remove(i + 1);
+        let (if_true, target) = nodes[i].jump?;
+        let (right_if_true, _) = right.jump?;
+        let left = std::mem::take(&mut nodes[i].expr);
+        nodes[i].expr = if right.jump == Some((if_true, target)) {
+            // both jump to the same place
+            combine_bool_op(if if_true { "or" } else { "and" }, left, right.expr)
+        } else if right_if_true {
+            // `left` skips `right` when it is `if_true`, so `right` jumps only when `left` isn't
+            combine_bool_op("and", negate_if(left, if_true), right.expr)
+        } else {
+            combine_bool_op("or", negate_if(left, !if_true), right.expr)
+        };
+        nodes[i].jump = right.jump;
+    }
+    let node = nodes.pop()?;
+    let (if_true, target) = node.jump?;
+    Some((node.expr, if_true, target))
+}
+
+/// `negate` 为 true 的时候取反, `not not x` 直接变成 `x`
+fn negate_if(expr: ExpressionEnum, negate: bool) -> ExpressionEnum {
+    if !negate {
+        return expr;
+    }
+    match expr {
+        ExpressionEnum::UnaryOperation(UnaryOperation {
+            target,
+            unary_type: UnaryType::Not,
+            ..
+        }) => *target,
+        expr => ExpressionEnum::UnaryOperation(UnaryOperation {
+            start_offset: expr.get_offset().0,
+            end_offset: expr.get_offset().1,
+            target: Box::new(expr),
+            unary_type: UnaryType::Not,
+            ..Default::default()
+        }),
+    }
+}
+
+fn combine_bool_op(operator: &str, left: ExpressionEnum, right: ExpressionEnum) -> ExpressionEnum {
+    let start_offset = left.get_offset().0;
+    let end_offset = right.get_offset().1;
+    let mut values = Vec::new();
+    for expr in [left, right] {
+        match expr {
+            ExpressionEnum::BoolOp(bool_op) if bool_op.operator == operator => {
+                values.extend(bool_op.values)
+            }
+            expr => values.push(expr),
+        }
+    }
+    ExpressionEnum::BoolOp(BoolOp {
+        operator: operator.to_string(),
+        values,
+        start_offset,
+        end_offset,
+        ..Default::default()
+    })
+}
+
+/// 解析一段只产生一个值的指令
+fn parse_value(opcode_instructions: &[OpcodeInstruction]) -> Result<ExpressionEnum> {
+    let mut exprs = Expr::parse(opcode_instructions)?.bodys;
+    if exprs.len() == 1 {
+        Ok(exprs.pop().unwrap())
+    } else {
+        Err(format!(
+            "[ParseValue] Expect one value, but got {}, deviation is {}",
+            exprs.len(),
+            opcode_instructions.first().map_or(0, |x| x.offset)
+        )
+        .into())
+    }
+}
+
+fn is_assertion_error(instruction: &OpcodeInstruction) -> bool {
+    match instruction.opcode() {
+        Opcode::LoadAssertionError => true,
+        Opcode::LoadCommonConstant => instruction.argval.as_deref() == Some("AssertionError"),
+        _ => false,
+    }
+}
+
+/// 解析一个断言, `idx` 是测试后面的条件跳转, 跳转到 `RAISE_VARARGS 1` 的后面
+/// 例如: assert x, f"msg {x}" 的消息作为 AssertionError 的参数被调用
+fn parse_assert(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    test: ExpressionEnum,
+    negate: bool,
+) -> Result<(ExpressionEnum, usize)> {
+    let instruction = &opcode_instructions[idx];
+    let end = jump_target_idx(opcode_instructions, instruction.jump_target()?)
+        .unwrap_or(opcode_instructions.len());
+    let raise = end
+        .checked_sub(1)
+        .filter(|&i| i > idx && opcode_instructions[i].opcode() == Opcode::RaiseVarargs)
+        .ok_or(format!(
+            "[Assert] Expect a raise, deviation is {}",
+            instruction.offset
+        ))?;
+    // the message is called by `PRECALL 0` (3.11) + `CALL 0`
+    let mut message_end = raise;
+    while message_end > idx + 2
+        && matches!(
+            opcode_instructions[message_end - 1].opname.as_str(),
+            "CALL" | "PRECALL"
+        )
+    {
+        message_end -= 1;
+    }
+    let msg = if message_end > idx + 2 {
+        Some(Box::new(parse_value(
+            &opcode_instructions[idx + 2..message_end],
+        )?))
+    } else {
+        None
+    };
+    let test = if negate {
+        ExpressionEnum::UnaryOperation(UnaryOperation {
+            target: Box::new(test),
+            unary_type: UnaryType::Not,
+            ..Default::default()
+        })
+    } else {
+        test
+    };
+    Ok((
+        ExpressionEnum::Assert(Assert {
+            test: Box::new(test),
+            msg,
+            start_line: instruction.starts_line.unwrap_or_default(),
+            start_offset: instruction.offset,
+            end_offset: opcode_instructions[raise].offset,
+        }),
+        end,
+    ))
+}
+
+/// 判断一段指令执行完后是否会在栈上留下一个值(而不是一条语句)
+fn is_value_region(opcode_instructions: &[OpcodeInstruction]) -> bool {
+    opcode_instructions.last().is_some_and(|x| {
+        !matches!(
+            x.opcode(),
+            Opcode::PopTop
+                | Opcode::Nop
+                | Opcode::StoreName
+                | Opcode::StoreFast
+                | Opcode::StoreGlobal
+                | Opcode::StoreAttr
+                | Opcode::StoreSubscr
+                | Opcode::StoreSlice
+                | Opcode::StoreDeref
+                | Opcode::ReturnValue
+                | Opcode::ReturnConst
+                | Opcode::RaiseVarargs
+                | Opcode::Reraise
+                | Opcode::PopExcept
+                | Opcode::JumpForward
+                | Opcode::JumpBackward
+                | Opcode::EndFor
+        )
+    })
+}
+
+fn is_statement(expr: &ExpressionEnum) -> bool {
+    matches!(
+        expr,
+        ExpressionEnum::Import(_)
+            | ExpressionEnum::Class(_)
+            | ExpressionEnum::Return(_)
+            | ExpressionEnum::Assign(_)
+            | ExpressionEnum::Delete(_)
+            | ExpressionEnum::Try(_)
+            | ExpressionEnum::Except(_)
+            | ExpressionEnum::Finally(_)
+            | ExpressionEnum::Assert(_)
+            | ExpressionEnum::Raise(_)
+            | ExpressionEnum::With(_)
+            | ExpressionEnum::For(_)
+            | ExpressionEnum::If(_)
+            | ExpressionEnum::Jump(_)
+    )
+}
+
+/// 解析一个短路求值链, `left` 是第一个值, `idx` 是它后面的条件跳转, `end` 是链结束的位置
+fn parse_bool_chain(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    left: ExpressionEnum,
+    end: usize,
+) -> Result<ExpressionEnum> {
+    let mut jumps = Vec::new();
+    let mut i = idx;
+    while i < end {
+        if let Some(jump) = cond_jump_at(opcode_instructions, i)
+            && jump.start == i
+        {
+            jumps.push(resolve_cond_jump(opcode_instructions, jump));
+            i = jump.end;
+        } else {
+            i += 1;
+        }
+    }
+    // only the jumps to the end or to the start of another operand belong to this chain
+    // others are nested in the operands
+    let mut chain_jumps: Vec<CondJump> = Vec::new();
+    for jump in jumps.into_iter().rev() {
+        if jump.target == end || chain_jumps.iter().any(|x| x.end == jump.target) {
+            chain_jumps.push(jump);
+        }
+    }
+    chain_jumps.reverse();
+    let first = chain_jumps.first().ok_or(format!(
+        "[BoolOp] No jump in chain, deviation is {}",
+        opcode_instructions[idx].offset
+    ))?;
+
+    let mut nodes = vec![ChainNode {
+        expr: left,
+        jump: Some((first.if_true, first.target)),
+        start: idx,
+    }];
+    for (i, jump) in chain_jumps.iter().enumerate() {
+        let next = chain_jumps.get(i + 1);
+        let operand_end = next.map_or(end, |x| x.start);
+        nodes.push(ChainNode {
+            expr: parse_value(&opcode_instructions[jump.end..operand_end])?,
+            jump: next.map(|x| (x.if_true, x.target)),
+            start: jump.end,
+        });
+    }
+    match reduce_chain(nodes, end) {
+        Some(ChainNode {
+            expr, jump: None, ..
+        }) => Ok(expr),
+        _ => Err(format!(
+            "[BoolOp] Can't combine the chain, deviation is {}",
+            opcode_instructions[idx].offset
+        )
+        .into()),
+    }
+}
+
+/// 解析 if 语句中用 and/or 组合起来的条件, `idx` 是第一个条件后面的 `POP_JUMP_IF_FALSE`
+/// 返回组合后的条件, if 代码块开始的下标和 else 代码块开始 (if 代码块结束) 的下标
+fn parse_if_test(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    first: &ExpressionEnum,
+) -> Option<(ExpressionEnum, usize, usize)> {
+    let first_target = jump_target_idx(
+        opcode_instructions,
+        opcode_instructions[idx].jump_target().ok()?,
+    )?;
+    // the later tests end with their own jumps, the body doesn't start with a jump
+    let mut tests = Vec::new();
+    // `a` of `a and b or c` jumps to `c`, so the tests may go past the first target
+    let mut i = idx + 1;
+    while let Some(instruction) = opcode_instructions.get(i) {
+        if let Some(jump) = cond_jump_at(opcode_instructions, i) {
+            if jump.keep || jump.start != i {
+                break;
+            }
+            i = jump.end;
+            tests.push(jump);
+            continue;
+        }
+        if matches!(
+            instruction.opcode(),
+            Opcode::JumpForward | Opcode::JumpBackward | Opcode::ReturnValue | Opcode::ReturnConst
+        ) {
+            break;
+        }
+        i += 1;
+    }
+
+    // the longest chain whose jumps all go to the body, the else block or a later test
+    for n in (1..=tests.len()).rev() {
+        let then_start = tests[n - 1].end;
+        let mut nodes = vec![ChainNode {
+            expr: first.clone(),
+            // the `POP_JUMP_IF_TRUE` has been turned into `not` + `POP_JUMP_IF_FALSE`
+            jump: Some((false, first_target)),
+            start: idx,
+        }];
+        let mut start = idx + 1;
+        for test in tests[..n].iter() {
+            // the longer chains may take the statements of the body as a test
+            let Ok(expr) = parse_value(&opcode_instructions[start..test.start]) else {
+                break;
+            };
+            nodes.push(ChainNode {
+                expr,
+                jump: Some((test.if_true, test.target)),
+                start,
+            });
+            start = test.end;
+        }
+        if nodes.len() == n + 1
+            && let Some((test, if_true, else_start)) = reduce_condition(nodes, then_start)
+            && else_start > then_start
+        {
+            // the body runs when the test doesn't jump
+            return Some((negate_if(test, if_true), then_start, else_start));
+        }
+    }
+    None
+}
+
+/// 尝试把`idx`处的条件跳转解析为条件表达式 `body if test else or_else`
+/// `left` 是第一个条件, `pending` 表示栈上还有别的值 (例如: 要调用的函数),
+/// 返回表达式, 表达式结束的位置, 以及 3.12 复制到 else 分支的结尾 (解析成功以后要隐藏掉)
+fn try_parse_if_exp(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    left: &ExpressionEnum,
+    pending: bool,
+) -> Option<(ExpressionEnum, usize, Option<std::ops::Range<usize>>)> {
+    let first = cond_jump_at(opcode_instructions, idx).filter(|x| !x.keep && x.start == idx)?;
+    // collect the jumps which may belong to the test
+    let mut tests = vec![first];
+    'collect: loop {
+        for i in tests.last()?.end..opcode_instructions.len() {
+            if let Some(jump) = cond_jump_at(opcode_instructions, i) {
+                if jump.keep || jump.start != i {
+                    break 'collect;
                 }
+                tests.push(jump);
+                continue 'collect;
             }
-            Opcode::LoadFast => {
-                let arg = instruction.arg.as_ref().ok_or(format!(
-                    "[Trace] No arg, deviation is {}",
-                    instruction.offset
-                ))?;
-                let name = instruction.argval.as_ref().ok_or(format!(
-                    "[Trace] No argval, deviation is {}",
-                    instruction.offset
+            if matches!(
+                opcode_instructions[i].opcode(),
+                Opcode::JumpForward
+                    | Opcode::JumpBackward
+                    | Opcode::ReturnValue
+                    | Opcode::ReturnConst
+            ) {
+                break 'collect;
+            }
+        }
+        break;
+    }
+
+    // try the longest test first
+    for n in (1..=tests.len()).rev() {
+        let then_start = tests[n - 1].end;
+        let mut nodes = vec![ChainNode {
+            expr: left.clone(),
+            jump: Some((tests[0].if_true, tests[0].target)),
+            start: idx,
+        }];
+        for k in 1..n {
+            nodes.push(ChainNode {
+                expr: parse_value(&opcode_instructions[tests[k - 1].end..tests[k].start]).ok()?,
+                jump: Some((tests[k].if_true, tests[k].target)),
+                start: tests[k - 1].end,
+            });
+        }
+        let Some((test, if_true, else_start)) = reduce_condition(nodes, then_start) else {
+            continue;
+        };
+        if else_start <= then_start {
+            continue;
+        }
+        // skip the hidden cleanup of the chained comparison
+        let then_end = opcode_instructions[..else_start]
+            .iter()
+            .rposition(|x| x.opcode() != Opcode::Nop)?
+            + 1;
+        if then_end <= then_start {
+            continue;
+        }
+        let jump_forward = &opcode_instructions[then_end - 1];
+        let mut copied = None;
+        let (body, or_else, end) = if jump_forward.opcode() == Opcode::JumpForward {
+            let end = jump_target_idx(opcode_instructions, jump_forward.jump_target().ok()?)?;
+            let then_block = &opcode_instructions[then_start..then_end - 1];
+            let else_block = &opcode_instructions[else_start..end];
+            let Some((body, or_else)) = parse_if_exp_branches(then_block, else_block) else {
+                continue;
+            };
+            (body, or_else, end)
+        } else if is_return(jump_forward) {
+            // 3.12: the tail which ends with return is copied to both branches
+            let else_end = else_start
+                + opcode_instructions[else_start..]
+                    .iter()
+                    .position(is_return)?
+                + 1;
+            let then_block = &opcode_instructions[then_start..then_end];
+            let else_block = &opcode_instructions[else_start..else_end];
+            let tail_len = (1..then_block.len().min(else_block.len()))
+                .rev()
+                .filter(|&len| {
+                    same_instructions(
+                        &then_block[then_block.len() - len..],
+                        &else_block[else_block.len() - len..],
+                    )
+                })
+                .find(|&len| {
+                    parse_if_exp_branches(
+                        &then_block[..then_block.len() - len],
+                        &else_block[..else_block.len() - len],
+                    )
+                    .is_some()
+                })?;
+            // only a value which is returned or stored, the statements like `g()` in
+            // `if a: g() else: h()` are copied too
+            if !is_value_tail(&then_block[then_block.len() - tail_len..], pending) {
+                continue;
+            }
+            let (body, or_else) = parse_if_exp_branches(
+                &then_block[..then_block.len() - tail_len],
+                &else_block[..else_block.len() - tail_len],
+            )?;
+            // both branches are the implicit `return None`, e.g. `if a: pass`
+            let is_none = |expr: &ExpressionEnum| matches!(expr, ExpressionEnum::BaseValue(none) if none.value == "None");
+            if tail_len == 1 && is_none(&body) && is_none(&or_else) {
+                continue;
+            }
+            // the copied branch is useless, the caller hides it
+            copied = Some(else_start..else_end);
+            (body, or_else, then_start + then_block.len() - tail_len)
+        } else {
+            continue;
+        };
+        let test = negate_if(test, if_true);
+        return Some((
+            ExpressionEnum::IfExp(IfExp {
+                test: Box::new(test),
+                body: Box::new(body),
+                or_else: Box::new(or_else),
+                start_line: opcode_instructions[idx].starts_line.unwrap_or_default(),
+                start_offset: opcode_instructions[idx].offset,
+                end_offset: opcode_instructions[end - 1].offset,
+            }),
+            end,
+            copied,
+        ));
+    }
+    None
+}
+
+/// 3.12 复制到 else 分支的结尾已经合并到条件表达式里了
+fn hide_copied_tail(
+    opcode_instructions: &[OpcodeInstruction],
+    copied: Option<std::ops::Range<usize>>,
+) {
+    if let Some(copied) = copied {
+        opcode_instructions[copied]
+            .iter()
+            .for_each(|x| _ = x.opcode.replace(Opcode::Nop));
+    }
+}
+
+fn parse_if_exp_branches(
+    then_block: &[OpcodeInstruction],
+    else_block: &[OpcodeInstruction],
+) -> Option<(ExpressionEnum, ExpressionEnum)> {
+    if !is_value_region(then_block) || !is_value_region(else_block) {
+        return None;
+    }
+    let body = parse_value(then_block).ok()?;
+    let or_else = parse_value(else_block).ok()?;
+    (!is_statement(&body) && !is_statement(&or_else)).then_some((body, or_else))
+}
+
+/// 条件表达式的值被直接返回或者赋值以后返回, 例如: `return a if b else c`,
+/// `pending` 的时候也可以先作为参数调用, 例如: `return g(a if b else c)`
+fn is_value_tail(tail: &[OpcodeInstruction], pending: bool) -> bool {
+    let opcodes = tail.iter().map(|x| x.opcode()).collect::<Vec<_>>();
+    let (called, opcodes) = match opcodes.as_slice() {
+        [Opcode::Call, rest @ ..] if pending => (true, rest),
+        opcodes => (false, opcodes),
+    };
+    let is_store = |opcode: &Opcode| {
+        matches!(
+            opcode,
+            Opcode::StoreFast | Opcode::StoreName | Opcode::StoreGlobal | Opcode::StoreDeref
+        )
+        // the result of the call is dropped, e.g. `f(a if b else c)`
+        || (called && *opcode == Opcode::PopTop)
+    };
+    match opcodes {
+        [Opcode::ReturnValue] => true,
+        [store, Opcode::ReturnConst] => is_store(store),
+        [store, Opcode::LoadConst, Opcode::ReturnValue] => is_store(store),
+        _ => false,
+    }
+}
+
+fn is_return(instruction: &OpcodeInstruction) -> bool {
+    matches!(
+        instruction.opcode(),
+        Opcode::ReturnValue | Opcode::ReturnConst
+    )
+}
+
+/// 解析从 idx 开始的 try 语句, 返回解析出的表达式和 try 语句结束的下标
+/// 3.12 放在最后的异常处理块已经在 parse_opcode 中被移回了 try 语句的后面
+fn parse_try(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+) -> Result<Option<(Vec<ExpressionEnum>, usize)>> {
+    let instruction = &opcode_instructions[idx];
+    if instruction.opcode() == Opcode::Nop {
+        return Ok(None);
+    }
+    // the try body which can't raise (e.g. `return 1`) is not protected
+    let Some(mut handler) = (instruction.opcode() == Opcode::PushExcInfo)
+        .then_some(instruction.offset)
+        .or(instruction.handler.get())
+        .or_else(|| unprotected_finally(opcode_instructions, idx))
+    else {
+        return Ok(None);
+    };
+    let Some(mut region) = handler_region(opcode_instructions, handler) else {
+        return Ok(None);
+    };
+    // try/except/finally is compiled as a try/finally around a try/except
+    if region.kind != HandlerKind::Finally
+        && let Some(outer) = opcode_instructions[region.cleanup].handler.get()
+        && (idx == 0 || opcode_instructions[idx - 1].handler.get() != Some(outer))
+        && let Some(outer_region) = handler_region(opcode_instructions, outer)
+        && outer_region.kind == HandlerKind::Finally
+    {
+        handler = outer;
+        region = outer_region;
+    }
+    if region.start < idx {
+        return Ok(None);
+    }
+    let protected = &opcode_instructions[idx..region.start];
+    let protected_end = protected
+        .iter()
+        .rposition(|x| x.handler.get() == Some(handler))
+        .map_or(idx, |i| idx + i + 1);
+    let finally_body = &opcode_instructions[region.start + 1..region.cleanup - 1];
+    if region.kind == HandlerKind::Finally {
+        // the finally body is copied before every exit of the try statement
+        hide_copies(protected, finally_body, handler);
+    }
+    // the try statement is parsed here, the body mustn't be taken as a try statement again
+    protected
+        .iter()
+        .filter(|x| x.handler.get() == Some(handler))
+        .for_each(|x| x.handler.set(None));
+    // the normal path jumps over the handlers (3.11)
+    let mut body_end = region.start;
+    let mut skip = None;
+    if let Some(jump) = protected
+        .iter()
+        .rposition(|x| x.opcode() != Opcode::Nop)
+        .map(|i| i + idx)
+        .filter(|&i| {
+            i + 1 >= protected_end && unconditional_jump_target(&opcode_instructions[i]).is_some()
+        })
+    {
+        body_end = jump;
+        skip = Some(jump);
+    }
+    let next = opcode_instructions.get(region.end);
+    let next_offset = next.map_or(opcode_instructions[region.end - 1].offset + 2, |x| x.offset);
+
+    // the line of `try:` is kept by the `NOP` before the body
+    let try_line = idx
+        .checked_sub(1)
+        .map(|i| &opcode_instructions[i])
+        .filter(|x| x.opcode() == Opcode::Nop)
+        .unwrap_or(instruction)
+        .starts_line;
+    let mut try_expr = Try {
+        start_line: try_line.unwrap_or_default(),
+        start_offset: instruction.offset,
+        end_offset: opcode_instructions[region.end - 1].offset,
+        ..Default::default()
+    };
+    if region.kind == HandlerKind::Finally {
+        let finally = ExpressionEnum::Finally(Finally {
+            body: Expr::parse(finally_body)?.bodys,
+            start_line: opcode_instructions[region.start]
+                .starts_line
+                .unwrap_or_default(),
+            start_offset: opcode_instructions[region.start].offset,
+            end_offset: opcode_instructions[region.end - 1].offset,
+        });
+        let mut body = Expr::parse(&opcode_instructions[idx..body_end])?.bodys;
+        if let [ExpressionEnum::Try(inner)] = body.as_mut_slice()
+            && inner.finally.is_none()
+        {
+            try_expr.body = std::mem::take(&mut inner.body);
+            try_expr.except = std::mem::take(&mut inner.except);
+            try_expr.or_else = std::mem::take(&mut inner.or_else);
+        } else {
+            try_expr.body = body;
+        }
+        try_expr.finally = Some(Box::new(finally));
+    } else {
+        // the handlers jump to the end of the try statement
+        let mut join = vec![next_offset];
+        join.extend(skip.and_then(|x| unconditional_jump_target(&opcode_instructions[x])));
+        join.extend(next.and_then(unconditional_jump_target));
+        try_expr.body = Expr::parse(&opcode_instructions[idx..protected_end])?.bodys;
+        try_expr.or_else = Expr::parse(&opcode_instructions[protected_end..body_end])?.bodys;
+        try_expr.except = parse_except_clauses(
+            opcode_instructions,
+            region,
+            &join,
+            &opcode_instructions[region.end..],
+        )?;
+    }
+    let mut exprs = vec![ExpressionEnum::Try(try_expr)];
+    if let Some(skip) = skip
+        && unconditional_jump_target(&opcode_instructions[skip]) != Some(next_offset)
+    {
+        // the jump belongs to the statement around the try statement, e.g. an if-else
+        exprs.extend(Expr::parse(&opcode_instructions[skip..=skip])?.bodys);
+    }
+    Ok(Some((exprs, region.end)))
+}
+
+/// `try: pass` 的 body 不会抛出异常, 没有被保护, 但是 finally 块还是被复制到了 body 的后面:
+/// NOP; <finally 块的副本>; JUMP_FORWARD (或者 return, 3.12 没有); PUSH_EXC_INFO; <finally 块>; ...
+/// 如果 idx 是这样的副本的开始, 返回 finally 块的 `PUSH_EXC_INFO` 的偏移
+fn unprotected_finally(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<usize> {
+    if idx == 0 || opcode_instructions[idx - 1].opname != "NOP" {
+        return None;
+    }
+    let start = (idx..opcode_instructions.len())
+        .find(|&i| opcode_instructions[i].opcode() == Opcode::PushExcInfo)?;
+    let handler = opcode_instructions[start].offset;
+    if opcode_instructions
+        .iter()
+        .any(|x| x.handler.get() == Some(handler))
+    {
+        return None;
+    }
+    let region = handler_region(opcode_instructions, handler)?;
+    if region.kind != HandlerKind::Finally {
+        return None;
+    }
+    let finally_body = &opcode_instructions[region.start + 1..region.cleanup - 1];
+    let end = copy_end(opcode_instructions, idx, finally_body)?;
+    let exit = &opcode_instructions[end..region.start];
+    (exit.is_empty()
+        || exit.len() <= 2
+            && exit
+                .last()
+                .is_some_and(|x| unconditional_jump_target(x).is_some() || is_return(x)))
+    .then_some(handler)
+}
+
+/// 解析 try 语句的所有 except 块
+/// join 是 except 块执行完后跳转的位置, after 是 try 语句后面的指令
+fn parse_except_clauses(
+    opcode_instructions: &[OpcodeInstruction],
+    region: HandlerRegion,
+    join: &[usize],
+    after: &[OpcodeInstruction],
+) -> Result<Vec<ExpressionEnum>> {
+    let is_star = region.kind == HandlerKind::ExceptStar;
+    // except* keeps a list of the raised exceptions:
+    // COPY 1; BUILD_LIST 0; SWAP 2 (3.11) or BUILD_LIST 0; COPY 2 (3.12)
+    let mut idx = match opcode_instructions[region.start + 1].opcode() {
+        _ if !is_star => region.start + 1,
+        Opcode::BuildList => region.start + 3,
+        _ => region.start + 4,
+    };
+    let mut excepts = Vec::new();
+    while idx < region.cleanup {
+        let instruction = &opcode_instructions[idx];
+        let (exception, alias, body_start, body_end) = if !is_star
+            && instruction.opcode() == Opcode::PopTop
+        {
+            // bare `except:`
+            (ExpressionEnum::default(), None, idx + 1, region.cleanup)
+        } else {
+            let check = if is_star {
+                Opcode::CheckEgMatch
+            } else {
+                Opcode::CheckExcMatch
+            };
+            let Some(check) =
+                (idx..region.cleanup).find(|&i| opcode_instructions[i].opcode() == check)
+            else {
+                // RERAISE 0 (or PREP_RERAISE_STAR) when no exception matches
+                break;
+            };
+            let exception = parse_value(&opcode_instructions[idx..check])?;
+            // except* checks the match with: COPY 1; POP_JUMP_IF_NONE
+            let jump = if is_star { check + 2 } else { check + 1 };
+            let target = opcode_instructions[jump].jump_target()?;
+            let body_end = opcode_instructions[..region.cleanup]
+                .iter()
+                .position(|x| x.offset == target)
+                .filter(|&i| i > jump)
+                .ok_or(format!(
+                    "[Except] Invalid jump, deviation is {}",
+                    opcode_instructions[jump].offset
                 ))?;
-                if let Some(local) = traceback.get_mut_local(arg) {
-                    // load fast after store fast, not arguement for function
-                    if local.is_store {
-                        local.is_arg = false;
-                    } else {
-                        // is arg because its not store before
-                        local.is_arg = true;
-                    }
-                } else {
-                    // if load fast before store fast, it's an arguement for function
-                    traceback.insert_local(
-                        *arg,
-                        Local {
-                            name: name.clone(),
-                            is_store: false,
-                            is_arg: true,
-                        },
-                    );
+            let store = &opcode_instructions[jump + 1];
+            let alias = matches!(
+                store.opcode(),
+                Opcode::StoreName | Opcode::StoreFast | Opcode::StoreGlobal | Opcode::StoreDeref
+            )
+            .then(|| store.argval.clone())
+            .flatten();
+            (exception, alias, jump + 2, body_end)
+        };
+        let body_instructions = &opcode_instructions[body_start..body_end];
+        hide_except_cleanup(body_instructions, alias.as_deref(), is_star, join, after);
+        let exception = match alias {
+            Some(alias) => ExpressionEnum::Alias(Alias {
+                target: Box::new(exception),
+                alias: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                    value: alias,
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }),
+            None => exception,
+        };
+        excepts.push(ExpressionEnum::Except(Except {
+            exception: Box::new(exception),
+            body: Expr::parse(body_instructions)?.bodys,
+            is_star,
+            start_line: instruction.starts_line.unwrap_or_default(),
+            start_offset: instruction.offset,
+            end_offset: opcode_instructions[body_end - 1].offset,
+        }));
+        // the unmatched exception group is popped before the next except* block
+        idx = if is_star { body_end + 1 } else { body_end };
+    }
+    Ok(excepts)
+}
+
+/// 隐藏 except 块中编译器生成的代码:
+/// `POP_EXCEPT`, `except ... as e` 结束时的 `e = None; del e`, 以及跳出 try 语句的跳转
+fn hide_except_cleanup(
+    opcode_instructions: &[OpcodeInstruction],
+    alias: Option<&str>,
+    is_star: bool,
+    join: &[usize],
+    after: &[OpcodeInstruction],
+) {
+    let hide = |x: &OpcodeInstruction| {
+        x.opcode.replace(Opcode::Nop);
+    };
+    for (i, instruction) in opcode_instructions.iter().enumerate() {
+        match instruction.opname.as_str() {
+            "POP_EXCEPT" => {
+                hide(instruction);
+                // return in except: SWAP 2; POP_EXCEPT; RETURN_VALUE
+                if let Some(swap) = i.checked_sub(1).map(|i| &opcode_instructions[i])
+                    && swap.opname == "SWAP"
+                    && swap.arg == Some(2)
+                {
+                    hide(swap);
                 }
             }
-            Opcode::ReturnGenerator => {
-                // mark this block is async
-                traceback.mark_async();
+            "LOAD_CONST" if alias.is_some() && instruction.argval.as_deref() == Some("None") => {
+                if let Some([store, delete]) = opcode_instructions.get(i + 1..i + 3)
+                    && store.opname.starts_with("STORE_")
+                    && delete.opname.starts_with("DELETE_")
+                    && store.argval.as_deref() == alias
+                    && delete.argval.as_deref() == alias
+                {
+                    [instruction, store, delete].into_iter().for_each(hide);
+                    // the handler which cleans up `e` when the except block raises
+                    if let Some(reraise) = opcode_instructions.get(i + 3)
+                        && reraise.opname == "RERAISE"
+                    {
+                        hide(reraise);
+                    }
+                }
             }
-            Opcode::PopJumpIfFalse | Opcode::PopJumpIfTrue => {
-                let jump_target = instruction
-                    .argval
-                    .as_ref()
-                    .ok_or(format!(
-                        "[Trace] No argval, deviation is {}",
-                        instruction.offset
-                    ))?
-                    .trim_start_matches("to ")
-                    .parse::<usize>()?;
-                traceback.insert_jump(instruction.offset, jump_target);
+            // except* collects the exception raised in the block: LIST_APPEND 3; POP_TOP
+            "LIST_APPEND" if is_star => {
+                hide(instruction);
+                if let Some(pop_top) = opcode_instructions.get(i + 1) {
+                    hide(pop_top);
+                }
             }
             _ => {}
         }
     }
-
-    Ok(traceback)
+    for instruction in opcode_instructions.iter().rev() {
+        if instruction.opcode() == Opcode::Nop {
+            continue;
+        }
+        match unconditional_jump_target(instruction) {
+            // jump to the end of the try statement
+            // (the end is out of this block when the try statement is the last one)
+            Some(target) if join.contains(&target) || after.is_empty() => hide(instruction),
+            // except* blocks jump over the cleanup code
+            Some(target) if is_star && target > instruction.offset => hide(instruction),
+            _ => break,
+        }
+    }
+    if is_star {
+        opcode_instructions
+            .iter()
+            .filter(|x| unconditional_jump_target(x).is_some_and(|target| target > x.offset))
+            .for_each(hide);
+    }
+    // 3.12 copies the short return after the try statement to the end of the except blocks
+    let visible = opcode_instructions
+        .iter()
+        .filter(|x| x.opcode() != Opcode::Nop)
+        .collect::<Vec<_>>();
+    if let Some(return_idx) = after.iter().position(is_return)
+        && return_idx < 4
+        && let Some(tail) = visible
+            .len()
+            .checked_sub(return_idx + 1)
+            .map(|i| &visible[i..])
+        && tail
+            .iter()
+            .zip(&after[..=return_idx])
+            .all(|(x, y)| x.same_operation(y))
+    {
+        tail.iter().for_each(|x| hide(x));
+    }
 }
 
-#[cfg(test)]
+/// 隐藏 try 语句中 finally 块的副本, 它们没有被 finally 的异常处理块保护
+fn hide_copies(
+    opcode_instructions: &[OpcodeInstruction],
+    finally_body: &[OpcodeInstruction],
+    handler: usize,
+) {
+    let mut idx = 0;
+    while idx < opcode_instructions.len() {
+        let instruction = &opcode_instructions[idx];
+        // the hidden instructions are replaced by NOP
+        let is_hidden = |x: &OpcodeInstruction| x.opcode() == Opcode::Nop && x.opname != "NOP";
+        if !is_hidden(instruction)
+            && unconditional_jump_target(instruction).is_none()
+            && let Some(end) = copy_end(opcode_instructions, idx, finally_body)
+            && opcode_instructions[idx..end]
+                .iter()
+                .all(|x| x.handler.get() != Some(handler) && !is_hidden(x))
+        {
+            opcode_instructions[idx..end].iter().for_each(|x| {
+                x.opcode.replace(Opcode::Nop);
+            });
+            idx = end;
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+/// 格式化字符串中值的转换标志, 1: !s, 2: !r, 3: !a
+fn conversion(flag: usize) -> Conversion {
+    match flag {
+        1 => Conversion::Str,
+        2 => Conversion::Repr,
+        3 => Conversion::Ascii,
+        _ => Conversion::None,
+    }
+}
+
+/// 解析 with 语句 (3.11+), `idx` 是 BEFORE_WITH / BEFORE_ASYNC_WITH 的下标
+/// 返回 with 语句和它后面第一条指令的下标
+///
+/// 多个上下文管理器 `with a() as x, b() as y:` 被编译成嵌套的 with,
+/// 内层的 with 和外层的在同一行, 并且是外层 with 块里唯一的语句, 这里把它们合并回去
+fn parse_with(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    context_expr: ExpressionEnum,
+) -> Result<(With, usize)> {
+    let instruction = &opcode_instructions[idx];
+    let is_async = instruction.opcode() == Opcode::BeforeAsyncWith;
+    // `async with` awaits the result of `__aenter__()` first
+    let target_start = if is_async {
+        send_loop_end(opcode_instructions, idx + 1).ok_or(format!(
+            "[BeforeAsyncWith] No await, deviation is {}",
+            instruction.offset
+        ))? + 1
+    } else {
+        idx + 1
+    };
+    let target_instruction = opcode_instructions.get(target_start).ok_or(format!(
+        "[BeforeWith] No target, deviation is {}",
+        instruction.offset
+    ))?;
+    // the with block is protected by the handler which calls `__exit__`
+    let handler = target_instruction.handler.get().ok_or(format!(
+        "[BeforeWith] No exit handler, deviation is {}",
+        instruction.offset
+    ))?;
+    let (target, body_start) = if target_instruction.opcode() == Opcode::PopTop {
+        (None, target_start + 1)
+    } else {
+        let (target, end) = parse_target(opcode_instructions, target_start)?;
+        (Some(Box::new(target)), end)
+    };
+    // 3.12: the `CLEANUP_THROW` of an await in the with block is moved to the end of the code object
+    let protected_end = opcode_instructions[target_start..]
+        .iter()
+        .rposition(|x| x.handler.get() == Some(handler) && x.opcode() != Opcode::CleanupThrow)
+        .map_or(body_start, |i| target_start + i + 1)
+        .max(body_start);
+
+    // `__exit__(None, None, None)` is called before every exit of the with block
+    let mut i = body_start;
+    while i < protected_end {
+        if opcode_instructions[i].handler.get() != Some(handler)
+            && let Some(exit_end) = with_exit_end(opcode_instructions, i)
+        {
+            hide_with_exit(opcode_instructions, i, exit_end);
+            i = exit_end + 1;
+        } else {
+            i += 1;
+        }
+    }
+    let mut exit_start = protected_end;
+    while opcode_instructions
+        .get(exit_start)
+        .is_some_and(|x| x.opcode() == Opcode::Nop)
+    {
+        exit_start += 1;
+    }
+    let (body_end, end) = match with_exit_end(opcode_instructions, exit_start).or_else(|| {
+        // `return x` in the with block: LOAD x, SWAP 2, <exit>, RETURN_VALUE
+        (opcode_instructions
+            .get(exit_start)
+            .is_some_and(|x| x.opcode() == Opcode::Swap && x.arg == Some(2)))
+        .then(|| with_exit_end(opcode_instructions, exit_start + 1))
+        .flatten()
+    }) {
+        Some(exit_end) => {
+            hide_with_exit(opcode_instructions, exit_start, exit_end);
+            // a return statement in the with block doesn't start a new line after the exit,
+            // but the implicit `return None` at the end of the code object doesn't either
+            let exit_line = opcode_instructions[exit_start].starts_line;
+            let is_return_const = |x: &OpcodeInstruction| {
+                x.starts_line == exit_line && x.argval.as_deref() != Some("None")
+            };
+            let return_end = match &opcode_instructions[exit_end + 1..] {
+                [x, ..] if x.opcode() == Opcode::ReturnValue => Some(exit_end + 2),
+                [x, ..] if x.opcode() == Opcode::ReturnConst && is_return_const(x) => {
+                    Some(exit_end + 2)
+                }
+                [x, y, ..]
+                    if x.opcode() == Opcode::LoadConst
+                        && y.opcode() == Opcode::ReturnValue
+                        && is_return_const(x) =>
+                {
+                    Some(exit_end + 3)
+                }
+                _ => None,
+            };
+            match return_end {
+                Some(return_end) => (return_end, return_end),
+                None => (exit_start, exit_end + 1),
+            }
+        }
+        None => (protected_end, protected_end),
+    };
+
+    let mut body = Expr::parse(&opcode_instructions[body_start..body_end])?.bodys;
+    let mut items = vec![WithItem {
+        context_expr: Box::new(context_expr),
+        target,
+        start_line: instruction.starts_line.unwrap_or_default(),
+        start_offset: instruction.offset,
+        end_offset: opcode_instructions[body_start - 1].offset,
+    }];
+    if let [ExpressionEnum::With(inner)] = body.as_slice()
+        && inner.is_async == is_async
+        && inner.start_line == instruction.starts_line.unwrap_or_default()
+    {
+        let inner = inner.clone();
+        items.extend(inner.items);
+        body = inner.body;
+    }
+    Ok((
+        With {
+            items,
+            body,
+            is_async,
+            start_line: instruction.starts_line.unwrap_or_default(),
+            start_offset: instruction.offset,
+            end_offset: opcode_instructions[end - 1].offset,
+        },
+        end,
+    ))
+}
+
+/// with 语句调用 `__exit__(None, None, None)` 的指令:
+/// LOAD_CONST None * 3, [PRECALL 2], CALL 2, [await], POP_TOP
+/// 返回最后一条 POP_TOP 的下标
+fn with_exit_end(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<usize> {
+    let mut i = idx;
+    for _ in 0..3 {
+        let instruction = opcode_instructions.get(i)?;
+        if instruction.opcode() != Opcode::LoadConst
+            || instruction.argval.as_deref() != Some("None")
+        {
+            return None;
+        }
+        i += 1;
+    }
+    if opcode_instructions.get(i)?.opname == "PRECALL" {
+        i += 1;
+    }
+    let call = opcode_instructions.get(i)?;
+    if call.opcode() != Opcode::Call || call.arg != Some(2) {
+        return None;
+    }
+    i += 1;
+    if opcode_instructions.get(i)?.opcode() == Opcode::GetAwaitable {
+        i = send_loop_end(opcode_instructions, i)? + 1;
+    }
+    (opcode_instructions.get(i)?.opcode() == Opcode::PopTop).then_some(i)
+}
+
+/// 隐藏一次 `__exit__` 调用, 如果是 return 语句之前的调用, 连同把返回值换到栈顶的 SWAP 2 一起隐藏
+fn hide_with_exit(opcode_instructions: &[OpcodeInstruction], start: usize, exit_end: usize) {
+    opcode_instructions[start..=exit_end]
+        .iter()
+        .for_each(|x| _ = x.opcode.replace(Opcode::Nop));
+    if start > 0
+        && opcode_instructions[start - 1].opcode() == Opcode::Swap
+        && opcode_instructions[start - 1].arg == Some(2)
+        && opcode_instructions
+            .get(exit_end + 1)
+            .is_some_and(|x| x.opcode() == Opcode::ReturnValue)
+    {
+        opcode_instructions[start - 1].opcode.replace(Opcode::Nop);
+    }
+}
+
+/// await 和 yield from 的循环, 返回循环最后一条指令的下标
+/// - 3.11+: GET_AWAITABLE/GET_YIELD_FROM_ITER, LOAD_CONST None, SEND, YIELD_VALUE, RESUME, JUMP_BACKWARD_NO_INTERRUPT, [END_SEND]
+/// - 3.10-: GET_AWAITABLE/GET_YIELD_FROM_ITER, LOAD_CONST None, YIELD_FROM
+fn send_loop_end(opcode_instructions: &[OpcodeInstruction], idx: usize) -> Option<usize> {
+    if !matches!(
+        opcode_instructions.get(idx)?.opcode(),
+        Opcode::GetAwaitable | Opcode::GetYieldFromIter
+    ) {
+        return None;
+    }
+    let send = opcode_instructions[idx..]
+        .iter()
+        .take(3)
+        .position(|x| matches!(x.opcode(), Opcode::Send | Opcode::YieldFrom))?
+        + idx;
+    if opcode_instructions[send].opcode() == Opcode::YieldFrom {
+        return Some(send);
+    }
+    let send_to = opcode_instructions[send].jump_target().ok()?;
+    let send_to_idx = opcode_instructions
+        .iter()
+        .position(|x| x.offset == send_to)?;
+    if opcode_instructions[send_to_idx].opcode() == Opcode::EndSend {
+        Some(send_to_idx)
+    } else {
+        Some(send_to_idx - 1)
+    }
+}
+
+/// `except ... as e:` 的结尾, 编译器生成的 `e = None; del e`
+/// 3.11+ 在重建 try 的时候已经隐藏了, 这里处理之前的版本
+fn is_exception_cleanup(opcode_instructions: &[OpcodeInstruction], idx: usize) -> bool {
+    let Some([load, store, delete]) = idx
+        .checked_sub(2)
+        .and_then(|start| opcode_instructions.get(start..=idx))
+    else {
+        return false;
+    };
+    load.opcode() == Opcode::LoadConst
+        && load.argval.as_deref() == Some("None")
+        && store.opname.starts_with("STORE_")
+        && store.argval == delete.argval
+        && opcode_instructions.get(idx + 1).is_some_and(|x| {
+            matches!(
+                x.opname.as_str(),
+                "JUMP_FORWARD" | "RERAISE" | "END_FINALLY"
+            )
+        })
+}
+
+/// 把导入的模块或者 IMPORT_FROM 取出的名字保存到 `name`
+fn store_import(mut import: Import, name: String) -> Import {
+    let fragment = import.fragment.take();
+    match (&mut import.names, fragment) {
+        // from m import a as b
+        (Some(names), Some(fragment)) => {
+            let target = ExpressionEnum::BaseValue(BaseValue {
+                value: fragment.clone(),
+                ..Default::default()
+            });
+            if fragment == name {
+                names.push(target);
+            } else {
+                names.push(ExpressionEnum::Alias(Alias {
+                    target: Box::new(target),
+                    alias: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                        value: name,
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }));
+            }
+        }
+        // import a.b.c as d
+        (None, Some(_)) => import.alias = Some(name),
+        // `import a.b` stores the top-level package `a`
+        (None, None) if import.module.split('.').next() != Some(name.as_str()) => {
+            import.alias = Some(name)
+        }
+        _ => {}
+    }
+    import
+}
+
+/// 解析一个赋值目标, 返回目标和它后面第一条指令的下标
+/// 支持名字, 属性, 下标以及(嵌套的, 带星号的)序列解包
+fn parse_target(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+) -> Result<(ExpressionEnum, usize)> {
+    let instruction = opcode_instructions
+        .get(idx)
+        .ok_or("[Target] No instruction")?;
+    match instruction.opcode() {
+        Opcode::StoreName | Opcode::StoreFast | Opcode::StoreGlobal | Opcode::StoreDeref => {
+            let name = instruction.argval.as_ref().ok_or(format!(
+                "[Target] No argval, deviation is {}",
+                instruction.offset
+            ))?;
+            Ok((
+                ExpressionEnum::BaseValue(BaseValue {
+                    value: name.clone(),
+                    start_line: instruction.starts_line.unwrap_or_default(),
+                    start_offset: instruction.offset,
+                    end_offset: instruction.offset,
+                }),
+                idx + 1,
+            ))
+        }
+        Opcode::UnpackSequence | Opcode::UnpackEx => {
+            let arg = instruction.arg.ok_or(format!(
+                "[Target] No arg, deviation is {}",
+                instruction.offset
+            ))?;
+            // UNPACK_EX: the low byte is the count of the targets before the starred one,
+            // and the high byte is the count of the targets after it
+            let (count, starred) = if instruction.opcode() == Opcode::UnpackEx {
+                ((arg & 0xff) + 1 + (arg >> 8), Some(arg & 0xff))
+            } else {
+                (arg, None)
+            };
+            let mut values = Vec::with_capacity(count);
+            let mut end = idx + 1;
+            for i in 0..count {
+                let (value, next) = parse_target(opcode_instructions, end)?;
+                if starred == Some(i) {
+                    values.push(ExpressionEnum::Starred(Starred {
+                        value: Box::new(value),
+                        double_star: false,
+                        start_line: instruction.starts_line.unwrap_or_default(),
+                        start_offset: opcode_instructions[end].offset,
+                        end_offset: opcode_instructions[next - 1].offset,
+                    }));
+                } else {
+                    values.push(value);
+                }
+                end = next;
+            }
+            Ok((
+                ExpressionEnum::Container(Container {
+                    values,
+                    container_type: ContainerType::Tuple,
+                    start_line: instruction.starts_line.unwrap_or_default(),
+                    start_offset: instruction.offset,
+                    end_offset: opcode_instructions[end - 1].offset,
+                }),
+                end,
+            ))
+        }
+        _ => {
+            // the object (and the index) of an attribute or subscript target is loaded before the store
+            let store = opcode_instructions[idx..]
+                .iter()
+                .position(|x| matches!(x.opcode(), Opcode::StoreAttr | Opcode::StoreSubscr))
+                .map(|i| idx + i)
+                .ok_or(format!(
+                    "[Target] Expect a store instruction, deviation is {}",
+                    instruction.offset
+                ))?;
+            let store_instruction = &opcode_instructions[store];
+            let mut values = Expr::parse(&opcode_instructions[idx..store])?.bodys;
+            let err = format!(
+                "[Target] Stack is empty, deviation is {}",
+                store_instruction.offset
+            );
+            let target = if store_instruction.opcode() == Opcode::StoreAttr {
+                let attr = store_instruction.argval.as_ref().ok_or(format!(
+                    "[Target] No argval, deviation is {}",
+                    store_instruction.offset
+                ))?;
+                ExpressionEnum::Attribute(Attribute {
+                    parent: Box::new(values.pop().ok_or(err)?),
+                    attr: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                        value: attr.clone(),
+                        ..Default::default()
+                    })),
+                    start_offset: store_instruction.offset,
+                    end_offset: store_instruction.offset,
+                    ..Default::default()
+                })
+            } else {
+                let index = values.pop().ok_or(err.clone())?;
+                ExpressionEnum::Subscr(Subscr {
+                    target: Box::new(values.pop().ok_or(err)?),
+                    index: Box::new(index),
+                    start_offset: store_instruction.offset,
+                    end_offset: store_instruction.offset,
+                    ..Default::default()
+                })
+            };
+            Ok((target, store + 1))
+        }
+    }
+}
+
+/// 被 `COPY 1` 复制的值存到的目标, 返回目标和它后面的位置
+/// 属性和下标目标前面只能加载它们的对象和下标, 否则就不是一个目标
+fn copy_target(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+) -> Option<(ExpressionEnum, usize)> {
+    let instruction = opcode_instructions.get(idx)?;
+    match instruction.opcode() {
+        Opcode::StoreName
+        | Opcode::StoreFast
+        | Opcode::StoreGlobal
+        | Opcode::StoreDeref
+        | Opcode::UnpackSequence
+        | Opcode::UnpackEx => parse_target(opcode_instructions, idx).ok(),
+        _ => {
+            let store = opcode_instructions[idx..]
+                .iter()
+                .position(|x| {
+                    !matches!(
+                        x.opcode(),
+                        Opcode::LoadName
+                            | Opcode::LoadFast
+                            | Opcode::LoadGlobal
+                            | Opcode::LoadDeref
+                            | Opcode::LoadConst
+                            | Opcode::LoadAttr
+                            | Opcode::BinarySubscr
+                    )
+                })
+                .map(|i| idx + i)?;
+            let count = match opcode_instructions[store].opcode() {
+                Opcode::StoreAttr => 1,
+                Opcode::StoreSubscr => 2,
+                _ => return None,
+            };
+            let values = Expr::parse(&opcode_instructions[idx..store]).ok()?.bodys;
+            if values.len() != count {
+                return None;
+            }
+            parse_target(opcode_instructions, idx).ok()
+        }
+    }
+}
+
+/// 判断两段指令是否相同(忽略偏移)
+fn same_instructions(a: &[OpcodeInstruction], b: &[OpcodeInstruction]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.opname == y.opname && x.arg == y.arg && x.argval == y.argval)
+}
+
+fn is_compare(instruction: &OpcodeInstruction) -> bool {
+    matches!(
+        instruction.opcode(),
+        Opcode::CompareOp | Opcode::IsOp | Opcode::ContainsOp
+    )
+}
+
+fn compare_operator(instruction: &OpcodeInstruction) -> Result<String> {
+    match (instruction.opcode(), instruction.arg) {
+        (Opcode::IsOp, Some(0)) => Ok("is".to_string()),
+        (Opcode::IsOp, Some(1)) => Ok("is not".to_string()),
+        (Opcode::ContainsOp, Some(0)) => Ok("in".to_string()),
+        (Opcode::ContainsOp, Some(1)) => Ok("not in".to_string()),
+        (Opcode::CompareOp, _) => Ok(instruction
+            .argval
+            .as_ref()
+            .ok_or(format!(
+                "[Compare] No argval, deviation is {}",
+                instruction.offset
+            ))?
+            .clone()),
+        _ => Err(format!(
+            "[Compare] Invalid compare instruction, deviation is {}",
+            instruction.offset
+        )
+        .into()),
+    }
+}
+
+/// 链式比较以 `SWAP 2` + `COPY 2` + 比较指令 开始
+fn is_compare_chain_start(opcode_instructions: &[OpcodeInstruction], idx: usize) -> bool {
+    opcode_instructions
+        .get(idx)
+        .is_some_and(|x| x.opcode() == Opcode::Swap && x.arg == Some(2))
+        && opcode_instructions
+            .get(idx + 1)
+            .is_some_and(|x| x.opcode() == Opcode::Copy && x.arg == Some(2))
+        && opcode_instructions.get(idx + 2).is_some_and(is_compare)
+}
+
+/// 解析链式比较, 例如: `0 < x < 10`
+///
+/// ```text
+/// LOAD 0; LOAD x; SWAP 2; COPY 2; COMPARE_OP <; JUMP_IF_FALSE_OR_POP cleanup
+/// LOAD 10; COMPARE_OP <; JUMP_FORWARD end
+/// cleanup: SWAP 2; POP_TOP
+/// end: ...
+/// ```
+/// 返回表达式和表达式结束的位置
+fn parse_compare_chain(
+    opcode_instructions: &[OpcodeInstruction],
+    idx: usize,
+    left: ExpressionEnum,
+    first: ExpressionEnum,
+) -> Result<(ExpressionEnum, usize)> {
+    let mut operators = Vec::new();
+    let mut comparators = Vec::new();
+    let mut operand = first;
+    let mut this_idx = idx;
+    loop {
+        operators.push(compare_operator(&opcode_instructions[this_idx + 2])?);
+        comparators.push(operand);
+        // the value is popped by the cleanup when the chain is used as a condition
+        let jump = cond_jump_at(opcode_instructions, this_idx + 3)
+            .filter(|x| !x.if_true && x.start == this_idx + 3)
+            .filter(|x| {
+                x.keep
+                    || opcode_instructions
+                        .get(x.target)
+                        .is_some_and(|x| x.opcode() == Opcode::PopTop)
+            })
+            .ok_or(format!(
+                "[Compare] Expect a conditional jump, deviation is {}",
+                opcode_instructions[this_idx].offset
+            ))?;
+        let mut next = jump.end;
+        loop {
+            if next + 1 >= opcode_instructions.len() {
+                return Err(format!(
+                    "[Compare] No chain end, deviation is {}",
+                    opcode_instructions[idx].offset
+                )
+                .into());
+            }
+            if is_compare_chain_start(opcode_instructions, next) {
+                operand = parse_value(&opcode_instructions[jump.end..next])?;
+                this_idx = next;
+                break;
+            }
+            if !jump.keep {
+                if !is_compare(&opcode_instructions[next])
+                    || cond_jump_at(opcode_instructions, next + 1)
+                        .is_none_or(|x| x.keep || x.start != next + 1)
+                {
+                    next += 1;
+                    continue;
+                }
+                operators.push(compare_operator(&opcode_instructions[next])?);
+                comparators.push(parse_value(&opcode_instructions[jump.end..next])?);
+                // leave the last conditional jump to the statement, and hide the cleanup
+                let mut cleanup_end = jump.target + 1;
+                if opcode_instructions
+                    .get(cleanup_end)
+                    .is_some_and(|x| x.opcode() == Opcode::JumpForward)
+                {
+                    cleanup_end += 1;
+                }
+                let skip_cleanup = opcode_instructions.get(next + 2).filter(|x| {
+                    x.opcode() == Opcode::JumpForward
+                        && x.jump_target()
+                            .ok()
+                            .and_then(|target| jump_target_idx(opcode_instructions, target))
+                            == Some(cleanup_end)
+                });
+                skip_cleanup
+                    .into_iter()
+                    .chain(&opcode_instructions[jump.target..cleanup_end])
+                    .for_each(|x| _ = x.opcode.replace(Opcode::Nop));
+                return Ok((
+                    ExpressionEnum::Compare(Compare {
+                        left: Box::new(left),
+                        operators,
+                        comparators,
+                        start_line: opcode_instructions[idx].starts_line.unwrap_or_default(),
+                        start_offset: opcode_instructions[idx].offset,
+                        end_offset: opcode_instructions[next].offset,
+                    }),
+                    next + 1,
+                ));
+            }
+            // 3.12 copies the tail which ends with return after the cleanup instead of jumping
+            let tail = &opcode_instructions[(next + 1).min(jump.target)..jump.target];
+            let copied_tail =
+                opcode_instructions.get(jump.target + 2..jump.target + 2 + tail.len());
+            let is_copied = tail.last().is_some_and(is_return)
+                && copied_tail.is_some_and(|x| same_instructions(tail, x));
+            if is_compare(&opcode_instructions[next])
+                && (opcode_instructions[next + 1].opcode() == Opcode::JumpForward
+                    && next + 2 == jump.target
+                    || is_copied)
+            {
+                operators.push(compare_operator(&opcode_instructions[next])?);
+                comparators.push(parse_value(&opcode_instructions[jump.end..next])?);
+                let end = if is_copied {
+                    // the cleanup and the copied tail are useless now
+                    opcode_instructions[jump.target..jump.target + 2 + tail.len()]
+                        .iter()
+                        .for_each(|x| _ = x.opcode.replace(Opcode::Nop));
+                    next + 1
+                } else {
+                    jump_target_idx(
+                        opcode_instructions,
+                        opcode_instructions[next + 1].jump_target()?,
+                    )
+                    .ok_or(format!(
+                        "[Compare] No jump target, deviation is {}",
+                        opcode_instructions[next + 1].offset
+                    ))?
+                };
+                return Ok((
+                    ExpressionEnum::Compare(Compare {
+                        left: Box::new(left),
+                        operators,
+                        comparators,
+                        start_line: opcode_instructions[idx].starts_line.unwrap_or_default(),
+                        start_offset: opcode_instructions[idx].offset,
+                        end_offset: opcode_instructions[next].offset,
+                    }),
+                    end,
+                ));
+            }
+            next += 1;
+        }
+    }
+}
+
+/// `__build_class__(func, name, *bases, **keywords)` 创建一个类
+/// 其他的调用保持不变
+fn build_class(call: Call) -> Result<ExpressionEnum> {
+    if !matches!(call.func.as_ref(), ExpressionEnum::BaseValue(BaseValue { value, .. }) if value == "__build_class__")
+    {
+        return Ok(ExpressionEnum::Call(call));
+    }
+    let mut args = call.args.into_iter();
+    let Some(ExpressionEnum::Function(function)) = args.next() else {
+        return Err(format!(
+            "[BuildClass] Expect the class body, deviation is {}",
+            call.start_offset
+        )
+        .into());
+    };
+    // skip the name of the class
+    args.next();
+    Ok(ExpressionEnum::Class(Class {
+        bases: args.collect(),
+        keywords: call.keywords,
+        start_offset: call.start_offset,
+        end_offset: call.end_offset,
+        ..Class::new(&function.mark)?
+    }))
+}
+
+/// 可以被装饰的定义, 也就是 def 和 class
+fn is_definition(expr: &ExpressionEnum) -> bool {
+    match expr {
+        ExpressionEnum::Function(function) => {
+            !function.name.starts_with('<') || function.name.starts_with("<generic parameters of ")
+        }
+        ExpressionEnum::Class(_) => true,
+        _ => false,
+    }
+}
+
+/// 装饰器是从下往上调用的, 所以后调用的装饰器在前面
+fn decorate(definition: ExpressionEnum, decorator: ExpressionEnum) -> ExpressionEnum {
+    match definition {
+        ExpressionEnum::Function(mut function) => {
+            function.decorators.insert(0, decorator);
+            ExpressionEnum::Function(function)
+        }
+        ExpressionEnum::Class(mut class) => {
+            class.decorators.insert(0, decorator);
+            ExpressionEnum::Class(class)
+        }
+        x => x,
+    }
+}
+
+/// 模块和类里的 x.y: int = 1 会先赋值, 再计算注解然后丢掉
+fn is_annotation_of_target(
+    exprs_stack: &[ExpressionEnum],
+    instruction: &OpcodeInstruction,
+) -> bool {
+    let [.., ExpressionEnum::Assign(assign), annotation] = exprs_stack else {
+        return false;
+    };
+    assign.operator == "="
+        && assign.start_line == instruction.starts_line.unwrap_or_default()
+        && matches!(
+            assign.target.as_ref(),
+            ExpressionEnum::Attribute(_) | ExpressionEnum::Subscr(_)
+        )
+        && matches!(
+            annotation,
+            ExpressionEnum::BaseValue(_)
+                | ExpressionEnum::Attribute(_)
+                | ExpressionEnum::Subscr(_)
+                | ExpressionEnum::BinaryOperation(_)
+        )
+}
+
+/// 类型参数会被 `COPY 1` 然后存到同名的局部变量里, 泛型类的基类会被存到 `.generic_base` 里,
+/// 这些存储不是代码里写的, 直接跳过
+fn skip_hidden_store(opcode_instructions: &[OpcodeInstruction], idx: usize) -> usize {
+    let is_store = |i: usize| {
+        opcode_instructions
+            .get(i)
+            .is_some_and(|x| matches!(x.opcode(), Opcode::StoreFast | Opcode::StoreDeref))
+    };
+    let is_copy = opcode_instructions
+        .get(idx)
+        .is_some_and(|x| x.opcode() == Opcode::Copy && x.arg == Some(1));
+    if is_copy && is_store(idx + 1) {
+        return idx + 2;
+    }
+    let is_generic_base = opcode_instructions
+        .get(idx)
+        .is_some_and(|x| x.argval.as_deref() == Some(".generic_base"));
+    if is_generic_base && is_store(idx) {
+        return idx + 1;
+    }
+    idx
+}
+
+/// `<generic parameters of f>` 是 3.12 泛型定义的包装函数, 调用它就得到定义本身
+///
+/// 调用之后 `end_offset` 是调用的位置, 之后就可以被装饰了
+fn is_generic_wrapper(function: &Function) -> bool {
+    function.name.starts_with("<generic parameters of ")
+        && function.start_offset == function.end_offset
+}
+
+/// 用包装函数的返回值还原泛型定义, 例如: def f[T](x: T), class C[T], type A[T] = ...
+///
+/// 包装函数的参数是泛型函数的默认值, 泛型类的基类里多了一个 `.generic_base`
+pub fn resolve_generic(wrapper: &Function, generic: &Expr) -> Result<ExpressionEnum> {
+    let definition = generic
+        .bodys
+        .iter()
+        .rev()
+        .find_map(|expr| match expr {
+            ExpressionEnum::Return(ret) => Some(ret.value.as_ref().clone()),
+            _ => None,
+        })
+        .ok_or(format!("[Generic] No definition in {}", wrapper.name))?;
+    match definition {
+        ExpressionEnum::Function(mut function) => {
+            let mut args = wrapper.defaults.iter().cloned();
+            if matches!(function.defaults.as_slice(), [ExpressionEnum::BaseValue(x)] if x.value == ".defaults")
+            {
+                function.defaults = args.next().map(tuple_values).unwrap_or_default();
+            }
+            if let Some(kw_defaults) = args.next() {
+                set_function_attribute(&mut function, FUNCTION_KWDEFAULTS, kw_defaults)?;
+            }
+            function.decorators = wrapper.decorators.clone();
+            Ok(ExpressionEnum::Function(function))
+        }
+        ExpressionEnum::Class(mut class) => {
+            class.type_params = generic
+                .bodys
+                .iter()
+                .find_map(|expr| match expr {
+                    ExpressionEnum::Assign(assign)
+                        if matches!(assign.target.as_ref(), ExpressionEnum::BaseValue(x) if x.value == ".type_params") =>
+                    {
+                        Some(tuple_values(assign.values.as_ref().clone()))
+                    }
+                    _ => None,
+                })
+                .unwrap_or_default();
+            class.bases.retain(
+                |base| !matches!(base, ExpressionEnum::BaseValue(x) if x.value == ".generic_base"),
+            );
+            class.decorators = wrapper.decorators.clone();
+            Ok(ExpressionEnum::Class(class))
+        }
+        definition => Ok(definition),
+    }
+}
+
+/// 判断是否是对`name`的增量赋值, 例如: a += 1
+fn is_inplace_of(value: &ExpressionEnum, name: &str) -> bool {
+    const INPLACE_OPERATORS: [&str; 13] = [
+        "+=", "-=", "*=", "/=", "//=", "%=", "**=", "@=", "&=", "|=", "^=", "<<=", ">>=",
+    ];
+    matches!(
+        value,
+        ExpressionEnum::BinaryOperation(BinaryOperation { left, operator, .. })
+            if INPLACE_OPERATORS.contains(&operator.as_str())
+                && matches!(left.as_ref(), ExpressionEnum::BaseValue(x) if x.value == name)
+    )
+}
+
+/// 保存到下标或者切片, 例如: a[0] = 1, a[i:j] += 1
+fn assign_subscr(
+    target: Subscr,
+    value: ExpressionEnum,
+    instruction: &OpcodeInstruction,
+) -> Result<ExpressionEnum> {
+    // e.g. a[0] += 1, the slices are built twice in 3.12 so compare the code
+    if let ExpressionEnum::BinaryOperation(BinaryOperation { left, operator, .. }) = &value
+        && let ExpressionEnum::Subscr(Subscr {
+            target: left_target,
+            index: left_index,
+            ..
+        }) = left.as_ref()
+        && *left_target == target.target
+        && left_index.build()? == target.index.build()?
+        && operator.ends_with('=')
+    {
+        return Ok(value);
+    }
+    Ok(ExpressionEnum::Assign(Assign {
+        target: Box::new(ExpressionEnum::Subscr(target)),
+        values: Box::new(value),
+        operator: "=".to_string(),
+        chain: Vec::new(),
+        start_line: instruction.starts_line.unwrap_or_default(),
+        start_offset: instruction.offset,
+        end_offset: instruction.offset,
+    }))
+}
+
+/// 解析关键字参数的名字, 例如: ('end', 'sep')
+fn parse_kw_names(names: &str) -> Vec<String> {
+    names
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|x| x.trim().trim_matches('\'').to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// 关键字参数的值在位置参数的最后面
+fn split_keywords(
+    args: &mut Vec<ExpressionEnum>,
+    names: Vec<String>,
+    instruction: &OpcodeInstruction,
+) -> Result<Vec<Keyword>> {
+    let start = args.len().checked_sub(names.len()).ok_or(format!(
+        "[Call] Too many keyword names, deviation is {}",
+        instruction.offset
+    ))?;
+    Ok(names
+        .into_iter()
+        .zip(args.split_off(start))
+        .map(|(arg, value)| Keyword {
+            arg: Some(arg),
+            value: Box::new(value),
+            start_line: instruction.starts_line.unwrap_or_default(),
+            start_offset: instruction.offset,
+            end_offset: instruction.offset,
+        })
+        .collect())
+}
+
+fn list_to_tuple(list: ExpressionEnum) -> ExpressionEnum {
+    match list {
+        ExpressionEnum::Container(Container {
+            values,
+            container_type: ContainerType::List,
+            start_line,
+            start_offset,
+            end_offset,
+        }) => ExpressionEnum::Container(Container {
+            values,
+            container_type: ContainerType::Tuple,
+            start_line,
+            start_offset,
+            end_offset,
+        }),
+        x => x,
+    }
+}
+
+/// `MAKE_FUNCTION` 和 `SET_FUNCTION_ATTRIBUTE` 的 flag
+const FUNCTION_DEFAULTS: usize = 0x01;
+const FUNCTION_KWDEFAULTS: usize = 0x02;
+const FUNCTION_ANNOTATIONS: usize = 0x04;
+const FUNCTION_CLOSURE: usize = 0x08;
+const FUNCTION_ANNOTATE: usize = 0x10;
+
+fn set_function_attribute(
+    function: &mut Function,
+    flag: usize,
+    value: ExpressionEnum,
+) -> Result<()> {
+    match flag {
+        FUNCTION_DEFAULTS => function.defaults = tuple_values(value),
+        FUNCTION_KWDEFAULTS => {
+            // {'name': value, ...}
+            if let ExpressionEnum::Container(container) = value {
+                for pair in container.values.chunks(2) {
+                    if let [ExpressionEnum::BaseValue(key), value] = pair {
+                        function.kw_defaults.push(Keyword {
+                            arg: Some(key.value.trim_matches('\'').to_string()),
+                            value: Box::new(value.clone()),
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
+        FUNCTION_ANNOTATIONS => {
+            // ('name', annotation, ...) since 3.10, and {'name': annotation, ...} before
+            let values = match value {
+                ExpressionEnum::Container(container) => container.values,
+                value => tuple_values(value),
+            };
+            for (idx, pair) in values.chunks(2).enumerate() {
+                if let [ExpressionEnum::BaseValue(name), annotation] = pair {
+                    function.args.push(FastVariable {
+                        index: idx,
+                        name: name.value.trim_matches('\'').to_string(),
+                        annotation: Some(annotation.build()?.join("")),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        // the cells are found by the trace of the function
+        FUNCTION_CLOSURE => {}
+        // 3.14 `__annotate__` is a function which can not be decompiled here
+        FUNCTION_ANNOTATE => {}
+        _ => {
+            return Err(format!("[SetFunctionAttribute] Invalid flag {}", flag).into());
+        }
+    }
+    Ok(())
+}
+
+/// 被 `LIST_EXTEND` 或者 `SET_UPDATE` 展开的值
+/// 常量tuple和frozenset是编译器合并的元素, 其他的是 `*a`
+fn unpack_display(value: ExpressionEnum, instruction: &OpcodeInstruction) -> Vec<ExpressionEnum> {
+    match value {
+        ExpressionEnum::BaseValue(constant) if constant.value.starts_with('(') => {
+            tuple_values(ExpressionEnum::BaseValue(constant))
+        }
+        ExpressionEnum::BaseValue(constant)
+            if constant.value.starts_with("frozenset({") && constant.value.ends_with("})") =>
+        {
+            let items = &constant.value["frozenset({".len()..constant.value.len() - "})".len()];
+            tuple_values(ExpressionEnum::BaseValue(BaseValue {
+                value: format!("({})", items),
+                ..constant
+            }))
+        }
+        x => vec![ExpressionEnum::Starred(Starred {
+            value: Box::new(x),
+            start_offset: instruction.offset,
+            end_offset: instruction.offset,
+            ..Default::default()
+        })],
+    }
+}
+
+/// 一个tuple里的所有值, 常量tuple会按照顶层的逗号拆开
+/// 例如: (1, 'a, b', (2, 3)) -> 1 | 'a, b' | (2, 3)
+fn tuple_values(tuple: ExpressionEnum) -> Vec<ExpressionEnum> {
+    match tuple {
+        ExpressionEnum::Container(Container {
+            values,
+            container_type: ContainerType::Tuple | ContainerType::List,
+            ..
+        }) => values,
+        ExpressionEnum::BaseValue(value) if value.value.starts_with('(') => {
+            let inner = &value.value[1..value.value.len().saturating_sub(1).max(1)];
+            let mut items = Vec::new();
+            let mut depth = 0usize;
+            let mut quote = None;
+            let mut escaped = false;
+            let mut begin = 0;
+            for (i, c) in inner.char_indices() {
+                match quote {
+                    Some(_) if escaped => escaped = false,
+                    Some(_) if c == '\\' => escaped = true,
+                    Some(q) if c == q => quote = None,
+                    Some(_) => {}
+                    None => match c {
+                        '\'' | '"' => quote = Some(c),
+                        '(' | '[' | '{' => depth += 1,
+                        ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                        ',' if depth == 0 => {
+                            items.push(&inner[begin..i]);
+                            begin = i + 1;
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            items.push(&inner[begin..]);
+            items
+                .into_iter()
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(|x| {
+                    ExpressionEnum::BaseValue(BaseValue {
+                        value: x.to_string(),
+                        ..value.clone()
+                    })
+                })
+                .collect()
+        }
+        x => vec![x],
+    }
+}
+
+/// 常量切片, 以及包含切片的常量tuple
+/// 例如: slice(None, None, 2) -> ::2, (slice(None, None, None), 1) -> :, 1
+fn const_slice(value: ExpressionEnum) -> ExpressionEnum {
+    let ExpressionEnum::BaseValue(base) = &value else {
+        return value;
+    };
+    if base.value.starts_with("slice(") {
+        let bounds = ExpressionEnum::BaseValue(BaseValue {
+            value: base.value["slice".len()..].to_string(),
+            ..base.clone()
+        });
+        return ExpressionEnum::Slice(Slice {
+            slice: tuple_values(bounds),
+            start_offset: base.start_offset,
+            end_offset: base.end_offset,
+            ..Default::default()
+        });
+    }
+    if base.value.starts_with('(') && base.value.contains("slice(") {
+        return ExpressionEnum::Container(Container {
+            values: tuple_values(value.clone())
+                .into_iter()
+                .map(const_slice)
+                .collect(),
+            container_type: ContainerType::Tuple,
+            start_offset: base.start_offset,
+            end_offset: base.end_offset,
+            ..Default::default()
+        });
+    }
+    value
+}
+
+/// `CALL_FUNCTION_EX` 的位置参数是一个tuple
+fn unpack_call_args(args: ExpressionEnum) -> Vec<ExpressionEnum> {
+    match args {
+        ExpressionEnum::Container(Container {
+            values,
+            container_type: ContainerType::Tuple | ContainerType::List,
+            ..
+        }) => values,
+        // a constant tuple, e.g. f(**a) -> ()
+        ExpressionEnum::BaseValue(value) if value.value.starts_with('(') => {
+            let values = value
+                .value
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .trim_end_matches(',');
+            if values.is_empty() {
+                vec![]
+            } else {
+                vec![ExpressionEnum::BaseValue(BaseValue {
+                    value: values.to_string(),
+                    ..value
+                })]
+            }
+        }
+        // e.g. f(*a)
+        x => vec![ExpressionEnum::Starred(Starred {
+            value: Box::new(x),
+            ..Default::default()
+        })],
+    }
+}
+
+/// `CALL_FUNCTION_EX` 的关键字参数是一个dict
+fn unpack_call_kwargs(
+    kwargs: ExpressionEnum,
+    instruction: &OpcodeInstruction,
+) -> Result<Vec<Keyword>> {
+    let ExpressionEnum::Container(Container {
+        values,
+        container_type: ContainerType::Dict,
+        ..
+    }) = kwargs
+    else {
+        // e.g. f(**a)
+        return Ok(vec![Keyword {
+            value: Box::new(kwargs),
+            ..Default::default()
+        }]);
+    };
+    let mut keywords = Vec::new();
+    let mut values = values.into_iter();
+    while let Some(key) = values.next() {
+        match key {
+            ExpressionEnum::Starred(Starred {
+                value,
+                double_star: true,
+                start_line,
+                start_offset,
+                end_offset,
+            }) => {
+                if value.is_container() {
+                    // the keywords after `**a` are merged as a dict
+                    keywords.extend(unpack_call_kwargs(*value, instruction)?);
+                } else {
+                    keywords.push(Keyword {
+                        arg: None,
+                        value,
+                        start_line,
+                        start_offset,
+                        end_offset,
+                    });
+                }
+            }
+            ExpressionEnum::BaseValue(key) => {
+                let value = values.next().ok_or(format!(
+                    "[CallFunctionEx] No keyword value, deviation is {}",
+                    instruction.offset
+                ))?;
+                keywords.push(Keyword {
+                    arg: Some(key.value.trim_matches('\'').to_string()),
+                    value: Box::new(value),
+                    start_line: key.start_line,
+                    start_offset: key.start_offset,
+                    end_offset: key.end_offset,
+                });
+            }
+            _ => {
+                return Err(format!(
+                    "[CallFunctionEx] Invalid keyword, deviation is {}",
+                    instruction.offset
+                )
+                .into())
+            }
+        }
+    }
+    Ok(keywords)
+}
+
+pub fn get_trace(opcode_instructions: &[OpcodeInstruction]) -> Result<TraceBack> {
+    let mut traceback = TraceBack::default();
+
+    // 3.11+ makes the cells by `MAKE_CELL`, and their indexes are shared with the local variables
+    let cells = opcode_instructions
+        .iter()
+        .filter(|x| x.opcode() == Opcode::MakeCell)
+        .filter_map(|x| x.arg)
+        .collect::<Vec<_>>();
+    let has_make_cell = !cells.is_empty()
+        || opcode_instructions
+            .iter()
+            .any(|x| x.opcode() == Opcode::CopyFreeVars);
+    // before 3.11, the cells are only known by loading them to make the closure of inner functions
+    let closures = opcode_instructions
+        .iter()
+        .filter(|x| x.opcode() == Opcode::LoadClosure)
+        .filter_map(|x| x.argval.as_deref())
+        .collect::<Vec<_>>();
+    let is_cell = |instruction: &OpcodeInstruction| {
+        if has_make_cell {
+            instruction.arg.is_some_and(|arg| cells.contains(&arg))
+        } else {
+            instruction
+                .argval
+                .as_deref()
+                .is_some_and(|name| closures.contains(&name))
+        }
+    };
+
+    for instruction in opcode_instructions {
+        let opcode = match instruction.opcode() {
+            // the cells of this function are the same as the local variables
+            Opcode::LoadDeref | Opcode::LoadClosure if has_make_cell && is_cell(instruction) => {
+                Opcode::LoadFast
+            }
+            Opcode::StoreDeref if has_make_cell && is_cell(instruction) => Opcode::StoreFast,
+            opcode => opcode,
+        };
+        match opcode {
+            Opcode::StoreGlobal | Opcode::DeleteGlobal => {
+                let name = instruction.argval.as_ref().ok_or(format!(
+                    "[Trace] No argval, deviation is {}",
+                    instruction.offset
+                ))?;
+                traceback.insert_global(name);
+            }
+            Opcode::StoreDeref | Opcode::DeleteDeref if !is_cell(instruction) => {
+                let name = instruction.argval.as_ref().ok_or(format!(
+                    "[Trace] No argval, deviation is {}",
+                    instruction.offset
+                ))?;
+                traceback.insert_nonlocal(name);
+            }
+            Opcode::StoreFast => {
+                let arg = instruction.arg.as_ref().ok_or(format!(
+                    "[Trace] No arg, deviation is {}",
+                    instruction.offset
+                ))?;
+                let name = instruction.argval.as_ref().ok_or(format!(
+                    "[Trace] No argval, deviation is {}",
+                    instruction.offset
+                ))?;
+
+                if let Some(local) = traceback.get_mut_local(arg) {
+                    if !local.is_store {
+                        // do not store fast before, it's an arguement for function
+                        local.is_arg = true;
+                    } else {
+                        // store fast after load fast, not arguement for function
+                        local.is_arg = false;
+                    }
+                } else {
+                    // store fast before load fast, not arguement for function
+                    traceback.insert_local(
+                        *arg,
+                        Local {
+                            name: name.clone(),
+                            is_store: true,
+                            is_arg: false,
+                        },
+                    );
+                }
+            }
+            // `del x` before storing it also reads an argument
+            Opcode::LoadFast | Opcode::DeleteFast => {
+                let arg = instruction.arg.as_ref().ok_or(format!(
+                    "[Trace] No arg, deviation is {}",
+                    instruction.offset
+                ))?;
+                let name = instruction.argval.as_ref().ok_or(format!(
+                    "[Trace] No argval, deviation is {}",
+                    instruction.offset
+                ))?;
+                if let Some(local) = traceback.get_mut_local(arg) {
+                    // load fast after store fast, not arguement for function
+                    if local.is_store {
+                        local.is_arg = false;
+                    } else {
+                        // is arg because its not store before
+                        local.is_arg = true;
+                    }
+                } else {
+                    // if load fast before store fast, it's an arguement for function
+                    traceback.insert_local(
+                        *arg,
+                        Local {
+                            name: name.clone(),
+                            is_store: false,
+                            is_arg: true,
+                        },
+                    );
+                }
+            }
+            // 3.11+ 生成器和协程都以 RETURN_GENERATOR 开头, 没有 yield 的才是协程
+            Opcode::ReturnGenerator
+                if !opcode_instructions
+                    .iter()
+                    .any(|x| x.opcode() == Opcode::YieldValue) =>
+            {
+                traceback.mark_async();
+            }
+            Opcode::GetAwaitable
+            | Opcode::GetAiter
+            | Opcode::GetAnext
+            | Opcode::BeforeAsyncWith
+            | Opcode::EndAsyncFor => {
+                traceback.mark_async();
+            }
+            // 异步生成器里的 yield
+            _ if instruction.opname == "ASYNC_GEN_WRAP"
+                || instruction.argval.as_deref() == Some("INTRINSIC_ASYNC_GEN_WRAP") =>
+            {
+                traceback.mark_async();
+            }
+            Opcode::PopJumpIfFalse | Opcode::PopJumpIfTrue => {
+                let jump_target = instruction.jump_target()?;
+                traceback.insert_jump(instruction.offset, jump_target);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(traceback)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    /// 解析 dis 的输出, 生成 `<main>` 的代码, 不合并函数和类
+    fn decompile_text(text: &'static str) -> Vec<String> {
+        let code_object_map = text.parse_opcode().unwrap();
+        let mut expr = Expr::parse(code_object_map.get("<main>").unwrap()).unwrap();
+        crate::decompile::strip_implicit_returns(&mut expr.bodys);
+        expr.bodys.iter().flat_map(|x| x.build().unwrap()).collect()
+    }
+
+    /// 反编译整个字节码对象映射表, 返回每一行
+    fn decompile_map<G: CodeGenerator>(
+        code_object_map: &CodeObjectMap,
+        generator: &G,
+    ) -> Vec<String> {
+        let mut decompiled_code = code_object_map.decompile_with(generator).unwrap();
+        decompiled_code
+            .iter()
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        let instructions = [
+            OpcodeInstruction {
+                opcode: Opcode::LoadConst.into(),
+                opname: "LOAD_CONST".into(),
+                arg: Some(0),
+                argval: Some("'a'".into()),
+                offset: 2,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::LoadName.into(),
+                opname: "LOAD_NAME".into(),
+                arg: Some(0),
+                argval: Some("int".into()),
+                offset: 4,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::LoadConst.into(),
+                opname: "LOAD_CONST".into(),
+                arg: Some(1),
+                argval: Some("'return'".into()),
+                offset: 6,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::LoadName.into(),
+                opname: "LOAD_NAME".into(),
+                arg: Some(0),
+                argval: Some("int".into()),
+                offset: 8,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::BuildTuple.into(),
+                opname: "BUILD_TUPLE".into(),
+                arg: Some(4),
+                argval: None,
+                offset: 10,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::LoadConst.into(),
+                opname: "LOAD_CONST".into(),
+                arg: Some(2),
+                argval: Some(
+                    "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>".into(),
+                ),
+                offset: 12,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::MakeFunction.into(),
+                opname: "MAKE_FUNCTION".into(),
+                arg: Some(4),
+                argval: Some("annotations".into()),
+                offset: 14,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+            OpcodeInstruction {
+                opcode: Opcode::StoreName.into(),
+                opname: "STORE_NAME".into(),
+                arg: Some(1),
+                argval: Some("test".into()),
+                offset: 16,
+                starts_line: Some(1),
+                is_jump_target: false,
+                positions: vec![],
+                handler: None.into(),
+            },
+        ];
+
+        assert_eq!(
+            Expr::parse(&instructions).unwrap(),
+            Box::new(Expr {
+                bodys: vec![ExpressionEnum::Function(Function {
+                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
+                        .into(),
+                    name: "test".into(),
+                    args: [
+                        FastVariable {
+                            index: 0,
+                            name: "a".into(),
+                            annotation: Some("int".into()),
+                            ..Default::default()
+                        },
+                        FastVariable {
+                            index: 1,
+                            name: "return".into(),
+                            annotation: Some("int".into()),
+                            ..Default::default()
+                        },
+                    ]
+                    .into(),
+                    defaults: vec![],
+                    start_line: 1,
+                    end_line: 1,
+                    bodys: vec![],
+                    start_offset: 14,
+                    end_offset: 14,
+                    ..Default::default()
+                },),]
+            })
+        )
+    }
+
+    #[test]
+    fn test_query() {
+        let expr = Box::new(Expr {
+            bodys: vec![ExpressionEnum::Assign(Assign {
+                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                    value: "test".into(),
+                    ..Default::default()
+                })),
+                values: Box::new(ExpressionEnum::Function(Function {
+                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
+                        .into(),
+                    name: "test".into(),
+                    args: vec![],
+                    defaults: vec![],
+                    start_line: 1,
+                    end_line: 1,
+                    bodys: vec![],
+                    ..Default::default()
+                })),
+                operator: "=".into(),
+                ..Default::default()
+            })],
+        });
+        let assign_query = expr.query::<Assign>();
+        let function_query = expr.query::<Function>();
+        //dbg!(query);
+        //assert!(false);
+        assert_eq!(
+            assign_query,
+            vec![&Assign {
+                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                    value: "test".into(),
+                    ..Default::default()
+                })),
+                values: Box::new(ExpressionEnum::Function(Function {
+                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
+                        .into(),
+                    name: "test".into(),
+                    args: vec![],
+                    defaults: vec![],
+                    start_line: 1,
+                    end_line: 1,
+                    bodys: vec![],
+                    ..Default::default()
+                },)),
+                operator: "=".into(),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(
+            function_query,
+            vec![&Function {
+                mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
+                    .into(),
+                name: "test".into(),
+                args: vec![],
+                defaults: vec![],
+                start_line: 1,
+                end_line: 1,
+                bodys: vec![],
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_any() {
+        let expr = Assign {
+            target: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                value: "a".to_string(),
+                ..Default::default()
+            })),
+            values: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                value: "1".to_string(),
+                ..Default::default()
+            })),
+            operator: "=".to_string(),
+            ..Default::default()
+        };
+        let any = expr.try_query::<Assign>();
+
+        //dbg!(any);
+        //assert!(false);
+        assert_eq!(
+            any,
+            Some(&Assign {
+                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                    value: "a".to_string(),
+                    ..Default::default()
+                })),
+                values: Box::new(ExpressionEnum::BaseValue(BaseValue {
+                    value: "1".to_string(),
+                    ..Default::default()
+                })),
+                operator: "=".to_string(),
+                ..Default::default()
+            })
+        )
+    }
+
+    #[test]
+    fn test_parse_bool_op() {
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE     2 (to 10)
+              6 LOAD_NAME                1 (b)
+              8 JUMP_IF_TRUE_OR_POP      1 (to 12)
+        >>   10 LOAD_NAME                2 (c)
+        >>   12 STORE_NAME               3 (x)
+
+  2          14 LOAD_CONST               0 (0)
+             16 LOAD_NAME                0 (a)
+             18 SWAP                     2
+             20 COPY                     2
+             22 COMPARE_OP               0 (<)
+             28 POP_JUMP_FORWARD_IF_FALSE     6 (to 42)
+             30 LOAD_CONST               1 (10)
+             32 COMPARE_OP               0 (<)
+             38 POP_JUMP_FORWARD_IF_FALSE     5 (to 50)
+             40 JUMP_FORWARD             2 (to 46)
+        >>   42 POP_TOP
+             44 JUMP_FORWARD             2 (to 50)
+        >>   46 LOAD_NAME                4 (p)
+             48 JUMP_FORWARD             1 (to 52)
+        >>   50 LOAD_NAME                5 (q)
+        >>   52 STORE_NAME               6 (y)
+"#,
+        );
+
+        assert_eq!(code, vec!["x = a and b or c", "y = p if 0 < a < 10 else q"]);
+
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE     4 (to 14)
+              6 LOAD_NAME                1 (b)
+              8 POP_JUMP_FORWARD_IF_FALSE     4 (to 18)
+             10 LOAD_NAME                2 (c)
+             12 POP_JUMP_FORWARD_IF_FALSE     2 (to 18)
+        >>   14 LOAD_NAME                3 (g)
+             16 JUMP_FORWARD             1 (to 20)
+        >>   18 LOAD_NAME                4 (h)
+        >>   20 STORE_NAME               5 (x)
+"#,
+        );
+
+        assert_eq!(code, vec!["x = g if not a or b and c else h"]);
+
+        // the test of an if statement, `a` jumps to `c` instead of the else block
+        let code = decompile_text(
+            r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE     2 (to 10)
+              6 LOAD_NAME                1 (b)
+              8 POP_JUMP_FORWARD_IF_TRUE     2 (to 14)
+        >>   10 LOAD_NAME                2 (c)
+             12 POP_JUMP_FORWARD_IF_FALSE    11 (to 36)
+
+  2     >>   14 PUSH_NULL
+             16 LOAD_NAME                3 (print)
+             18 LOAD_CONST               0 (1)
+             20 PRECALL                  1
+             24 CALL                     1
+             34 POP_TOP
+
+  3     >>   36 PUSH_NULL
+             38 LOAD_NAME                3 (print)
+             40 LOAD_CONST               1 (3)
+             42 PRECALL                  1
+             46 CALL                     1
+             56 POP_TOP
+             58 LOAD_CONST               2 (None)
+             60 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(code, vec!["if a and b or c:", "    print(1)", "print(3)"]);
+    }
+
+    #[test]
+    fn test_parse_if_return_tail() {
+        // both branches end with their own `return None`, which must not be taken as
+        // the shared tail of a conditional expression
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f8890450f30, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 LOAD_CONST               1 (None)
+             10 RETURN_VALUE
+
+Disassembly of <code object f at 0x7f8890450f30, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_FAST                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE    16 (to 38)
+
+  3           6 LOAD_GLOBAL              1 (NULL + g)
+             18 PRECALL                  0
+             22 CALL                     0
+             32 POP_TOP
+             34 LOAD_CONST               0 (None)
+             36 RETURN_VALUE
+
+  5     >>   38 LOAD_GLOBAL              3 (NULL + h)
+             50 PRECALL                  0
+             54 CALL                     0
+             64 POP_TOP
+             66 LOAD_CONST               0 (None)
+             68 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f(a):",
+                "    if a:",
+                "        g()",
+                "    else:",
+                "        h()"
+            ]
+        );
+
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f674823b9f0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 LOAD_CONST               1 (None)
+             10 RETURN_VALUE
+
+Disassembly of <code object f at 0x7f674823b9f0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_FAST                0 (args)
+              4 POP_JUMP_FORWARD_IF_FALSE    35 (to 76)
+              6 LOAD_GLOBAL              1 (NULL + len)
+             18 LOAD_FAST                0 (args)
+             20 PRECALL                  1
+             24 CALL                     1
+             34 LOAD_CONST               1 (1)
+             36 COMPARE_OP               2 (==)
+             42 POP_JUMP_FORWARD_IF_FALSE    32 (to 108)
+             44 LOAD_FAST                0 (args)
+             46 LOAD_CONST               2 (0)
+             48 BINARY_SUBSCR
+             58 LOAD_CONST               2 (0)
+             60 BINARY_SUBSCR
+             70 LOAD_FAST                1 (chars)
+             72 CONTAINS_OP              1
+             74 POP_JUMP_FORWARD_IF_FALSE    16 (to 108)
+
+  3     >>   76 LOAD_GLOBAL              3 (NULL + g)
+             88 PRECALL                  0
+             92 CALL                     0
+            102 POP_TOP
+            104 LOAD_CONST               0 (None)
+            106 RETURN_VALUE
+
+  5     >>  108 LOAD_GLOBAL              5 (NULL + h)
+            120 PRECALL                  0
+            124 CALL                     0
+            134 POP_TOP
+            136 LOAD_CONST               0 (None)
+            138 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f(args, chars):",
+                "    if not args or len(args) == 1 and args[0][0] not in chars:",
+                "        g()",
+                "    else:",
+                "        h()"
+            ]
+        );
+
+        // 3.12: the call which takes the conditional expression is copied to both branches
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object h at 0x7f690da12830, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (h)
+
+  3           8 LOAD_CONST               1 (<code object h2 at 0x7f690da51ce0, file "<dis>", line 3>)
+             10 MAKE_FUNCTION            0
+             12 STORE_NAME               1 (h2)
+
+  5          14 PUSH_NULL
+             16 LOAD_NAME                2 (g)
+             18 LOAD_NAME                3 (d)
+             20 POP_JUMP_IF_FALSE        2 (to 26)
+             22 LOAD_NAME                4 (c)
+             24 JUMP_FORWARD             1 (to 28)
+        >>   26 LOAD_NAME                5 (e)
+        >>   28 CALL                     1
+             36 STORE_NAME               6 (x)
+
+  6          38 PUSH_NULL
+             40 LOAD_NAME                7 (f)
+             42 LOAD_NAME                3 (d)
+             44 POP_JUMP_IF_FALSE        7 (to 60)
+             46 LOAD_NAME                4 (c)
+             48 CALL                     1
+             56 POP_TOP
+             58 RETURN_CONST             2 (None)
+        >>   60 LOAD_NAME                5 (e)
+             62 CALL                     1
+             70 POP_TOP
+             72 RETURN_CONST             2 (None)
+
+Disassembly of <code object h at 0x7f690da12830, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_GLOBAL              1 (NULL + g)
+             12 LOAD_GLOBAL              2 (d)
+             22 POP_JUMP_IF_FALSE       10 (to 44)
+             24 LOAD_GLOBAL              4 (c)
+             34 CALL                     1
+             42 RETURN_VALUE
+        >>   44 LOAD_GLOBAL              6 (e)
+             54 CALL                     1
+             62 RETURN_VALUE
+
+Disassembly of <code object h2 at 0x7f690da51ce0, file "<dis>", line 3>:
+  3           0 RESUME                   0
+
+  4           2 LOAD_GLOBAL              1 (NULL + f)
+             12 LOAD_GLOBAL              2 (d)
+             22 POP_JUMP_IF_FALSE       11 (to 46)
+             24 LOAD_GLOBAL              4 (c)
+             34 CALL                     1
+             42 POP_TOP
+             44 RETURN_CONST             0 (None)
+        >>   46 LOAD_GLOBAL              6 (e)
+             56 CALL                     1
+             64 POP_TOP
+             66 RETURN_CONST             0 (None)
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def h():",
+                "    return g(c if d else e)",
+                "",
+                "",
+                "def h2():",
+                "    f(c if d else e)",
+                "",
+                "",
+                "x = g(c if d else e)",
+                "f(c if d else e)"
+            ]
+        );
+
+        // both branches end with the implicit `return None`, which a module can't have
+        let code = decompile_text(
+            r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (x)
+              4 LOAD_CONST               0 (3)
+              6 COMPARE_OP               4 (>)
+             12 POP_JUMP_FORWARD_IF_FALSE     2 (to 18)
+
+  2          14 LOAD_CONST               1 (None)
+             16 RETURN_VALUE
+
+  1     >>   18 LOAD_CONST               1 (None)
+             20 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(code, vec!["if x > 3:", "    pass"]);
+
+        let code = decompile_text(
+            r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (x)
+              4 POP_JUMP_IF_FALSE        1 (to 8)
+
+  2           6 RETURN_CONST             0 (None)
+
+  4     >>    8 PUSH_NULL
+             10 LOAD_NAME                1 (y)
+             12 CALL                     0
+             20 POP_TOP
+             22 RETURN_CONST             0 (None)
+"#,
+        );
+
+        assert_eq!(code, vec!["if x:", "    pass", "else:", "    y()"]);
+    }
+
+    #[test]
+    fn test_parse_call_keywords() {
+        // 3.11, the names of `KW_NAMES` are only in the `dis.code_info` constants
+        let code = decompile_text(
+            r#"
+Name:              <module>
+Filename:          <dis>
+Argument count:    0
+Positional-only arguments: 0
+Kw-only arguments: 0
+Number of locals:  0
+Stack size:        5
+Flags:             0x0
+Constants:
+   0: ''
+   1: ('end',)
+   2: 'c'
+   3: 1
+   4: None
+Names:
+   0: print
+   1: x
+   2: f
+   3: a
+   4: b
+   5: d
+  0           0 RESUME                   0
+
+  1           2 PUSH_NULL
+              4 LOAD_NAME                0 (print)
+              6 LOAD_NAME                1 (x)
+              8 LOAD_CONST               0 ('')
+             10 KW_NAMES                 1
+             12 PRECALL                  2
+             16 CALL                     2
+             26 POP_TOP
+
+  2          28 PUSH_NULL
+             30 LOAD_NAME                2 (f)
+             32 LOAD_NAME                3 (a)
+             34 BUILD_LIST               1
+             36 LOAD_NAME                4 (b)
+             38 LIST_EXTEND              1
+             40 LIST_TO_TUPLE
+             42 LOAD_CONST               2 ('c')
+             44 LOAD_CONST               3 (1)
+             46 BUILD_MAP                1
+             48 LOAD_NAME                5 (d)
+             50 DICT_MERGE               1
+             52 CALL_FUNCTION_EX         1
+             54 POP_TOP
+             56 LOAD_CONST               4 (None)
+             58 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(code, vec!["print(x, end='')", "f(a, *b, c=1, **d)"]);
+
+        // 3.13
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (f)
+              4 PUSH_NULL
+              6 LOAD_NAME                1 (a)
+              8 LOAD_CONST               0 (1)
+             10 LOAD_CONST               1 (('b',))
+             12 CALL_KW                  2
+             20 POP_TOP
+"#,
+        );
+
+        assert_eq!(code, vec!["f(a, b=1)"]);
+
+        // plain 3.11 `dis` without `show_code` doesn't show the keyword names,
+        // the other statements are still decompiled
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 PUSH_NULL
+              4 LOAD_NAME                0 (print)
+              6 LOAD_NAME                1 (x)
+              8 LOAD_CONST               0 ('')
+             10 KW_NAMES                 1
+             12 PRECALL                  2
+             16 CALL                     2
+             26 POP_TOP
+
+  2          28 PUSH_NULL
+             30 LOAD_BUILD_CLASS
+             32 LOAD_CONST               2 (<code object Foo at 0x7f1b46941f10, file "<dis>", line 2>)
+             34 MAKE_FUNCTION            0
+             36 LOAD_CONST               3 ('Foo')
+             38 LOAD_NAME                2 (Meta)
+             40 KW_NAMES                 4
+             42 PRECALL                  3
+             46 CALL                     3
+             56 STORE_NAME               3 (Foo)
+
+  4          58 PUSH_NULL
+             60 LOAD_NAME                4 (f)
+             62 LOAD_CONST               5 (1)
+             64 LOAD_CONST               6 (2)
+             66 LOAD_CONST               7 (3)
+             68 KW_NAMES                 8
+             70 PRECALL                  3
+             74 CALL                     3
+             84 POP_TOP
+             86 LOAD_CONST               9 (None)
+             88 RETURN_VALUE
+
+Disassembly of <code object Foo at 0x7f1b46941f10, file "<dis>", line 2>:
+  2           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('Foo')
+              8 STORE_NAME               2 (__qualname__)
+
+  3          10 LOAD_CONST               1 (None)
+             12 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "# pyrev: unknown keyword names at offset 10, \
+                 the keywords are shown as positional arguments",
+                "print(x, '')",
+                "",
+                "",
+                "# pyrev: unknown keyword names at offset 40, \
+                 the keywords are shown as positional arguments",
+                "class Foo(Meta):",
+                "    pass",
+                "",
+                "",
+                "# pyrev: unknown keyword names at offset 68, \
+                 the keywords are shown as positional arguments",
+                "f(1, 2, 3)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_class_keywords() {
+        let code_object_map = r#"
+Name:              <module>
+Filename:          <dis>
+Argument count:    0
+Positional-only arguments: 0
+Kw-only arguments: 0
+Number of locals:  0
+Stack size:        8
+Flags:             0x0
+Constants:
+   0: <code object A at 0x7f9d43420100, file "<dis>", line 1>
+   1: 'A'
+   2: True
+   3: ('metaclass', 'flag')
+   4: None
+Names:
+   0: B
+   1: C
+   2: M
+   3: A
+  0           0 RESUME                   0
+
+  1           2 PUSH_NULL
+              4 LOAD_BUILD_CLASS
+              6 LOAD_CONST               0 (<code object A at 0x7f9d43420100, file "<dis>", line 1>)
+              8 MAKE_FUNCTION            0
+             10 LOAD_CONST               1 ('A')
+             12 LOAD_NAME                0 (B)
+             14 LOAD_NAME                1 (C)
+             16 LOAD_NAME                2 (M)
+             18 LOAD_CONST               2 (True)
+             20 KW_NAMES                 3
+             22 PRECALL                  6
+             26 CALL                     6
+             36 STORE_NAME               3 (A)
+             38 LOAD_CONST               4 (None)
+             40 RETURN_VALUE
+
+Disassembly of <code object A at 0x7f9d43420100, file "<dis>", line 1>:
+  1           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('A')
+              8 STORE_NAME               2 (__qualname__)
+
+  2          10 LOAD_CONST               1 (None)
+             12 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec!["class A(B, C, metaclass=M, flag=True):", "    pass"]
+        );
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        // closure, nonlocal, global and the 3.11 `LOAD_CLASSDEREF` in a class body
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7fae665ea5b0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+
+  6           8 LOAD_CONST               1 (<code object g at 0x7fae668948f0, file "<dis>", line 6>)
+             10 MAKE_FUNCTION            0
+             12 STORE_NAME               1 (g)
+
+ 15          14 LOAD_CONST               2 (<code object k at 0x7fae665fa320, file "<dis>", line 15>)
+             16 MAKE_FUNCTION            0
+             18 STORE_NAME               2 (k)
+             20 LOAD_CONST               3 (None)
+             22 RETURN_VALUE
+
+Disassembly of <code object f at 0x7fae665ea5b0, file "<dis>", line 1>:
+              0 MAKE_CELL                1 (x)
+
+  1           2 RESUME                   0
+
+  2           4 LOAD_CONST               1 (1)
+              6 STORE_DEREF              1 (x)
+
+  3           8 PUSH_NULL
+             10 LOAD_BUILD_CLASS
+             12 LOAD_CLOSURE             1 (x)
+             14 BUILD_TUPLE              1
+             16 LOAD_CONST               2 (<code object C at 0x7fae665f9f10, file "<dis>", line 3>)
+             18 MAKE_FUNCTION            8 (closure)
+             20 LOAD_CONST               3 ('C')
+             22 PRECALL                  2
+             26 CALL                     2
+             36 STORE_FAST               0 (C)
+
+  5          38 LOAD_FAST                0 (C)
+             40 RETURN_VALUE
+
+Disassembly of <code object C at 0x7fae665f9f10, file "<dis>", line 3>:
+              0 COPY_FREE_VARS           1
+
+  3           2 RESUME                   0
+              4 LOAD_NAME                0 (__name__)
+              6 STORE_NAME               1 (__module__)
+              8 LOAD_CONST               0 ('f.<locals>.C')
+             10 STORE_NAME               2 (__qualname__)
+
+  4          12 LOAD_CLASSDEREF          0 (x)
+             14 STORE_NAME               3 (y)
+             16 LOAD_CONST               1 (None)
+             18 RETURN_VALUE
+
+Disassembly of <code object g at 0x7fae668948f0, file "<dis>", line 6>:
+              0 MAKE_CELL                2 (a)
+
+  6           2 RESUME                   0
+
+  7           4 LOAD_CONST               1 (1)
+              6 STORE_DEREF              2 (a)
+
+  8           8 LOAD_CLOSURE             2 (a)
+             10 BUILD_TUPLE              1
+             12 LOAD_CONST               2 (<code object h at 0x7fae665fa250, file "<dis>", line 8>)
+             14 MAKE_FUNCTION            8 (closure)
+             16 STORE_FAST               0 (h)
+
+ 12          18 LOAD_CLOSURE             2 (a)
+             20 BUILD_TUPLE              1
+             22 LOAD_CONST               3 (<code object i at 0x7fae6658a4f0, file "<dis>", line 12>)
+             24 MAKE_FUNCTION            8 (closure)
+             26 STORE_FAST               1 (i)
+
+ 14          28 LOAD_FAST                0 (h)
+             30 LOAD_FAST                1 (i)
+             32 BUILD_TUPLE              2
+             34 RETURN_VALUE
+
+Disassembly of <code object h at 0x7fae665fa250, file "<dis>", line 8>:
+              0 COPY_FREE_VARS           1
+
+  8           2 RESUME                   0
+
+ 10           4 LOAD_DEREF               0 (a)
+              6 LOAD_CONST               1 (1)
+              8 BINARY_OP               13 (+=)
+             12 STORE_DEREF              0 (a)
+
+ 11          14 LOAD_DEREF               0 (a)
+             16 RETURN_VALUE
+
+Disassembly of <code object i at 0x7fae6658a4f0, file "<dis>", line 12>:
+              0 COPY_FREE_VARS           1
+
+ 12           2 RESUME                   0
+
+ 13           4 LOAD_DEREF               0 (a)
+              6 RETURN_VALUE
+
+Disassembly of <code object k at 0x7fae665fa320, file "<dis>", line 15>:
+ 15           0 RESUME                   0
+
+ 17           2 LOAD_CONST               1 (2)
+              4 STORE_GLOBAL             0 (b)
+              6 LOAD_CONST               0 (None)
+              8 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    x = 1",
+                "",
+                "    class C:",
+                "        y = x",
+                "",
+                "    return C",
+                "",
+                "",
+                "def g():",
+                "    a = 1",
+                "",
+                "    def h():",
+                "        nonlocal a",
+                "        a += 1",
+                "        return a",
+                "",
+                "    def i():",
+                "        return a",
+                "",
+                "    return (h, i)",
+                "",
+                "",
+                "def k():",
+                "    global b",
+                "    b = 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_decorators() {
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (app)
+              4 LOAD_METHOD              1 (route)
+             26 LOAD_CONST               0 ('/x')
+             28 PRECALL                  1
+             32 CALL                     1
+
+  2          42 LOAD_NAME                2 (cache)
+
+  3          44 LOAD_CONST               1 (<code object f at 0x7fa5fca0e4f0, file "<dis>", line 1>)
+             46 MAKE_FUNCTION            0
+
+  2          48 PRECALL                  0
+             52 CALL                     0
+
+  1          62 PRECALL                  0
+             66 CALL                     0
+
+  3          76 STORE_NAME               3 (f)
+
+  6          78 PUSH_NULL
+             80 LOAD_BUILD_CLASS
+             82 LOAD_CONST               2 (<code object P at 0x7fa5fca80f30, file "<dis>", line 6>)
+             84 MAKE_FUNCTION            0
+             86 LOAD_CONST               3 ('P')
+             88 PRECALL                  2
+             92 CALL                     2
+            102 STORE_NAME               4 (P)
+
+ 15         104 LOAD_NAME                5 (dataclass)
+
+ 16         106 PUSH_NULL
+            108 LOAD_BUILD_CLASS
+            110 LOAD_CONST               4 (<code object D at 0x7fa5fca7e4c0, file "<dis>", line 15>)
+            112 MAKE_FUNCTION            0
+            114 LOAD_CONST               5 ('D')
+            116 PRECALL                  2
+            120 CALL                     2
+
+ 15         130 PRECALL                  0
+            134 CALL                     0
+
+ 16         144 STORE_NAME               6 (D)
+            146 LOAD_CONST               6 (None)
+            148 RETURN_VALUE
+
+Disassembly of <code object f at 0x7fa5fca0e4f0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  4           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object P at 0x7fa5fca80f30, file "<dis>", line 6>:
+  6           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('P')
+              8 STORE_NAME               2 (__qualname__)
+
+  7          10 LOAD_NAME                3 (property)
+
+  8          12 LOAD_CONST               1 (<code object x at 0x7fa5fca7e250, file "<dis>", line 7>)
+             14 MAKE_FUNCTION            0
+
+  7          16 PRECALL                  0
+             20 CALL                     0
+
+  8          30 STORE_NAME               4 (x)
+
+ 11          32 LOAD_NAME                4 (x)
+             34 LOAD_ATTR                5 (setter)
+
+ 12          44 LOAD_CONST               2 (<code object x at 0x7fa5fca7e3f0, file "<dis>", line 11>)
+             46 MAKE_FUNCTION            0
+
+ 11          48 PRECALL                  0
+             52 CALL                     0
+
+ 12          62 STORE_NAME               4 (x)
+             64 LOAD_CONST               3 (None)
+             66 RETURN_VALUE
+
+Disassembly of <code object x at 0x7fa5fca7e250, file "<dis>", line 7>:
+  7           0 RESUME                   0
+
+  9           2 LOAD_FAST                0 (self)
+              4 LOAD_ATTR                0 (_x)
+             14 RETURN_VALUE
+
+Disassembly of <code object x at 0x7fa5fca7e3f0, file "<dis>", line 11>:
+ 11           0 RESUME                   0
+
+ 13           2 LOAD_FAST                1 (v)
+              4 LOAD_FAST                0 (self)
+              6 STORE_ATTR               0 (_x)
+             16 LOAD_CONST               0 (None)
+             18 RETURN_VALUE
+
+Disassembly of <code object D at 0x7fa5fca7e4c0, file "<dis>", line 15>:
+ 15           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('D')
+              8 STORE_NAME               2 (__qualname__)
+
+ 17          10 LOAD_CONST               1 (None)
+             12 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "@app.route('/x')",
+                "@cache",
+                "def f():",
+                "    pass",
+                "",
+                "",
+                "class P:",
+                "    @property",
+                "    def x(self):",
+                "        return self._x",
+                "",
+                "    @x.setter",
+                "    def x(self, v):",
+                "        self._x = v",
+                "",
+                "",
+                "@dataclass",
+                "class D:",
+                "    pass",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        // the arguments are inferred from the local variables, the unused ones get a placeholder
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               8 ((1, 2))
+              4 LOAD_CONST               2 (3)
+              6 LOAD_CONST               3 (('e',))
+              8 BUILD_CONST_KEY_MAP      1
+             10 LOAD_CONST               4 (<code object g at 0x7ff366e4e4f0, file "<dis>", line 1>)
+             12 MAKE_FUNCTION            3 (defaults, kwdefaults)
+             14 STORE_NAME               0 (g)
+
+  4          16 LOAD_CONST               9 ((1,))
+             18 LOAD_CONST               1 (2)
+             20 LOAD_CONST               5 (('c',))
+             22 BUILD_CONST_KEY_MAP      1
+             24 LOAD_CONST               6 (<code object h at 0x7ff366ebe250, file "<dis>", line 4>)
+             26 MAKE_FUNCTION            3 (defaults, kwdefaults)
+             28 STORE_NAME               1 (h)
+             30 LOAD_CONST               7 (None)
+             32 RETURN_VALUE
+
+Disassembly of <code object g at 0x7ff366e4e4f0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object h at 0x7ff366ebe250, file "<dis>", line 4>:
+  4           0 RESUME                   0
+
+  5           2 LOAD_FAST                0 (a)
+              4 LOAD_FAST                1 (b)
+              6 BINARY_OP                0 (+)
+             10 LOAD_FAST                2 (c)
+             12 BINARY_OP                0 (+)
+             16 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def g(_0=1, _1=2, *, e=3):",
+                "    pass",
+                "",
+                "",
+                "def h(a, b, _2=1, *, c=2):",
+                "    return a + b + c",
+            ]
+        );
+
+        // the defaults stay on the trailing arguments, the ones read in the body don't get them
+        // unless a local variable after the arguments tells how many arguments there are
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               6 ((2,))
+              4 LOAD_CONST               1 (4)
+              6 LOAD_CONST               2 (('d',))
+              8 BUILD_CONST_KEY_MAP      1
+             10 LOAD_CONST               3 (<code object s at 0x7fd7f8f35620, file "<dis>", line 1>)
+             12 MAKE_FUNCTION            3 (defaults, kwdefaults)
+             14 STORE_NAME               0 (s)
+
+  3          16 LOAD_CONST               6 ((2,))
+             18 LOAD_CONST               4 (<code object u at 0x7fd7f8f35550, file "<dis>", line 3>)
+             20 MAKE_FUNCTION            1 (defaults)
+             22 STORE_NAME               1 (u)
+             24 RETURN_CONST             5 (None)
+
+Disassembly of <code object s at 0x7fd7f8f35620, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_FAST                0 (a)
+              4 RETURN_VALUE
+
+Disassembly of <code object u at 0x7fd7f8f35550, file "<dis>", line 3>:
+  3           0 RESUME                   0
+
+  4           2 LOAD_FAST                0 (a)
+              4 STORE_FAST               2 (x)
+
+  5           6 LOAD_FAST                2 (x)
+              8 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def s(a, _1=2, *, d=4):",
+                "    return a",
+                "",
+                "",
+                "def u(a, _1=2):",
+                "    x = a",
+                "    return x"
+            ]
+        );
+
+        // with `dis.code_info`, the exact signature
+        let code_object_map = r#"
+Name:              <module>
+Filename:          <dis>
+Argument count:    0
+Positional-only arguments: 0
+Kw-only arguments: 0
+Number of locals:  0
+Stack size:        3
+Flags:             0x0
+Constants:
+   0: 1
+   1: 2
+   2: 3
+   3: ('e',)
+   4: <code object g at 0x7f766ec4def0, file "<dis>", line 1>
+   5: ('c',)
+   6: <code object h at 0x7f766f05f5d0, file "<dis>", line 4>
+   7: None
+   8: (1, 2)
+   9: (1,)
+Names:
+   0: g
+   1: h
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               8 ((1, 2))
+              4 LOAD_CONST               2 (3)
+              6 LOAD_CONST               3 (('e',))
+              8 BUILD_CONST_KEY_MAP      1
+             10 LOAD_CONST               4 (<code object g at 0x7f766ec4def0, file "<dis>", line 1>)
+             12 MAKE_FUNCTION            3 (defaults, kwdefaults)
+             14 STORE_NAME               0 (g)
+
+  4          16 LOAD_CONST               9 ((1,))
+             18 LOAD_CONST               1 (2)
+             20 LOAD_CONST               5 (('c',))
+             22 BUILD_CONST_KEY_MAP      1
+             24 LOAD_CONST               6 (<code object h at 0x7f766f05f5d0, file "<dis>", line 4>)
+             26 MAKE_FUNCTION            3 (defaults, kwdefaults)
+             28 STORE_NAME               1 (h)
+             30 LOAD_CONST               7 (None)
+             32 RETURN_VALUE
+
+Name:              g
+Filename:          <dis>
+Argument count:    3
+Positional-only arguments: 2
+Kw-only arguments: 2
+Number of locals:  7
+Stack size:        1
+Flags:             OPTIMIZED, NEWLOCALS, VARARGS, VARKEYWORDS
+Constants:
+   0: None
+Variable names:
+   0: a
+   1: b
+   2: c
+   3: d
+   4: e
+   5: args
+   6: kw
+Disassembly of <code object g at 0x7f766ec4def0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Name:              h
+Filename:          <dis>
+Argument count:    2
+Positional-only arguments: 0
+Kw-only arguments: 1
+Number of locals:  3
+Stack size:        2
+Flags:             OPTIMIZED, NEWLOCALS
+Constants:
+   0: None
+Variable names:
+   0: a
+   1: b
+   2: c
+Disassembly of <code object h at 0x7f766f05f5d0, file "<dis>", line 4>:
+  4           0 RESUME                   0
+
+  5           2 LOAD_FAST                0 (a)
+              4 LOAD_FAST                1 (b)
+              6 BINARY_OP                0 (+)
+             10 LOAD_FAST                2 (c)
+             12 BINARY_OP                0 (+)
+             16 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def g(a, b=1, /, c=2, *args, d, e=3, **kw):",
+                "    pass",
+                "",
+                "",
+                "def h(a, b=1, *, c=2):",
+                "    return a + b + c",
+            ]
+        );
+
+        // the unused receiver of a method depends on its decorators and name
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 PUSH_NULL
+              4 LOAD_BUILD_CLASS
+              6 LOAD_CONST               0 (<code object A at 0x7f4ead294f30, file "<dis>", line 1>)
+              8 MAKE_FUNCTION            0
+             10 LOAD_CONST               1 ('A')
+             12 PRECALL                  2
+             16 CALL                     2
+             26 STORE_NAME               0 (A)
+             28 LOAD_CONST               2 (None)
+             30 RETURN_VALUE
+
+Disassembly of <code object A at 0x7f4ead294f30, file "<dis>", line 1>:
+  1           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('A')
+              8 STORE_NAME               2 (__qualname__)
+
+  2          10 LOAD_CONST               1 (<code object __new__ at 0x7f4ead2224f0, file "<dis>", line 2>)
+             12 MAKE_FUNCTION            0
+             14 STORE_NAME               3 (__new__)
+
+  5          16 LOAD_NAME                4 (staticmethod)
+
+  6          18 LOAD_CONST               2 (<code object s at 0x7f4ead221ef0, file "<dis>", line 5>)
+             20 MAKE_FUNCTION            0
+
+  5          22 PRECALL                  0
+             26 CALL                     0
+
+  6          36 STORE_NAME               5 (s)
+
+  9          38 LOAD_NAME                6 (classmethod)
+
+ 10          40 LOAD_CONST               3 (<code object c at 0x7f4ead222670, file "<dis>", line 9>)
+             42 MAKE_FUNCTION            0
+
+  9          44 PRECALL                  0
+             48 CALL                     0
+
+ 10          58 STORE_NAME               7 (c)
+
+ 13          60 LOAD_CONST               4 (<code object u at 0x7f4ead222730, file "<dis>", line 13>)
+             62 MAKE_FUNCTION            0
+             64 STORE_NAME               8 (u)
+             66 LOAD_CONST               5 (None)
+             68 RETURN_VALUE
+
+Disassembly of <code object __new__ at 0x7f4ead2224f0, file "<dis>", line 2>:
+  2           0 RESUME                   0
+
+  3           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object s at 0x7f4ead221ef0, file "<dis>", line 5>:
+  5           0 RESUME                   0
+
+  7           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object c at 0x7f4ead222670, file "<dis>", line 9>:
+  9           0 RESUME                   0
+
+ 11           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object u at 0x7f4ead222730, file "<dis>", line 13>:
+ 13           0 RESUME                   0
+
+ 14           2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "class A:",
+                "    def __new__(cls):",
+                "        pass",
+                "",
+                "    @staticmethod",
+                "    def s():",
+                "        pass",
+                "",
+                "    @classmethod",
+                "    def c(cls):",
+                "        pass",
+                "",
+                "    def u(self):",
+                "        pass",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_try() {
+        // 3.12 moves the except blocks to the end
+        let code = decompile_text(
+            r#"
+  1           2 NOP
+
+  2           4 PUSH_NULL
+              6 LOAD_NAME                0 (a)
+              8 CALL                     0
+             16 POP_TOP
+
+  6          18 PUSH_NULL
+             20 LOAD_NAME                1 (c)
+             22 CALL                     0
+             30 POP_TOP
+             32 RETURN_CONST             0 (None)
+        >>   34 PUSH_EXC_INFO
+
+  3          36 LOAD_NAME                2 (E)
+             38 CHECK_EXC_MATCH
+             40 POP_JUMP_IF_FALSE       18 (to 78)
+             42 STORE_NAME               3 (e)
+
+  4          44 PUSH_NULL
+             46 LOAD_NAME                4 (b)
+             48 LOAD_NAME                3 (e)
+             50 CALL                     1
+             58 POP_TOP
+             60 POP_EXCEPT
+             62 LOAD_CONST               0 (None)
+             64 STORE_NAME               3 (e)
+             66 DELETE_NAME              3 (e)
+             68 RETURN_CONST             0 (None)
+        >>   70 LOAD_CONST               0 (None)
+             72 STORE_NAME               3 (e)
+             74 DELETE_NAME              3 (e)
+             76 RERAISE                  1
+
+  3     >>   78 RERAISE                  0
+        >>   80 COPY                     3
+             82 POP_EXCEPT
+             84 RERAISE                  1
+ExceptionTable:
+  4 to 16 -> 34 [0]
+  34 to 42 -> 80 [1] lasti
+  44 to 58 -> 70 [1] lasti
+  70 to 78 -> 80 [1] lasti
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "try:",
+                "    a()",
+                "except E as e:",
+                "    b(e)",
+                "else:",
+                "    c()",
+            ]
+        );
+
+        // 3.11, the else block of the last statement returns before the except blocks
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7fdc6ee60670, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 LOAD_CONST               1 (None)
+             10 RETURN_VALUE
+
+Disassembly of <code object f at 0x7fdc6ee60670, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 NOP
+
+  3           4 LOAD_GLOBAL              1 (NULL + a)
+             16 PRECALL                  0
+             20 CALL                     0
+             30 POP_TOP
+
+  7          32 LOAD_GLOBAL              3 (NULL + c)
+             44 PRECALL                  0
+             48 CALL                     0
+             58 POP_TOP
+             60 LOAD_CONST               0 (None)
+             62 RETURN_VALUE
+        >>   64 PUSH_EXC_INFO
+
+  4          66 LOAD_GLOBAL              4 (E)
+             78 CHECK_EXC_MATCH
+             80 POP_JUMP_FORWARD_IF_FALSE    18 (to 118)
+             82 POP_TOP
+
+  5          84 LOAD_GLOBAL              7 (NULL + b)
+             96 PRECALL                  0
+            100 CALL                     0
+            110 POP_TOP
+            112 POP_EXCEPT
+            114 LOAD_CONST               0 (None)
+            116 RETURN_VALUE
+
+  4     >>  118 RERAISE                  0
+        >>  120 COPY                     3
+            122 POP_EXCEPT
+            124 RERAISE                  1
+ExceptionTable:
+  4 to 30 -> 64 [0]
+  64 to 110 -> 120 [1] lasti
+  118 to 118 -> 120 [1] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    try:",
+                "        a()",
+                "    except E:",
+                "        b()",
+                "    else:",
+                "        c()",
+            ]
+        );
+
+        // the empty try body is not protected, but the finally block is still copied after it
+        let code = decompile_text(
+            r#"
+  0           0 RESUME                   0
+
+  1           2 NOP
+
+  2           4 NOP
+
+  4           6 PUSH_NULL
+              8 LOAD_NAME                0 (g)
+             10 PRECALL                  0
+             14 CALL                     0
+             24 POP_TOP
+             26 JUMP_FORWARD            15 (to 58)
+             28 PUSH_EXC_INFO
+             30 PUSH_NULL
+             32 LOAD_NAME                0 (g)
+             34 PRECALL                  0
+             38 CALL                     0
+             48 POP_TOP
+             50 RERAISE                  0
+        >>   52 COPY                     3
+             54 POP_EXCEPT
+             56 RERAISE                  1
+
+  5     >>   58 LOAD_CONST               0 (1)
+             60 STORE_NAME               1 (x)
+             62 LOAD_CONST               1 (None)
+             64 RETURN_VALUE
+ExceptionTable:
+  28 to 50 -> 52 [1] lasti
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec!["try:", "    pass", "finally:", "    g()", "x = 1"]
+        );
+
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f5a1d878cf0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 LOAD_CONST               1 (None)
+             10 RETURN_VALUE
+
+Disassembly of <code object f at 0x7f5a1d878cf0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 NOP
+
+  3           4 NOP
+
+  5           6 LOAD_GLOBAL              1 (NULL + g)
+             18 PRECALL                  0
+             22 CALL                     0
+             32 POP_TOP
+             34 LOAD_CONST               0 (None)
+             36 RETURN_VALUE
+             38 PUSH_EXC_INFO
+             40 LOAD_GLOBAL              1 (NULL + g)
+             52 PRECALL                  0
+             56 CALL                     0
+             66 POP_TOP
+             68 RERAISE                  0
+        >>   70 COPY                     3
+             72 POP_EXCEPT
+             74 RERAISE                  1
+ExceptionTable:
+  38 to 68 -> 70 [1] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    try:",
+                "        pass",
+                "    finally:",
+                "        g()"
+            ]
+        );
+
+        // 3.12 leaves the finally block of an empty try body at the end, after the other handlers
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f3d860810b0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 RETURN_CONST             1 (None)
+
+Disassembly of <code object f at 0x7f3d860810b0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 NOP
+
+  3           4 LOAD_GLOBAL              1 (NULL + a)
+             14 CALL                     0
+             22 POP_TOP
+
+  6     >>   24 NOP
+
+  7          26 NOP
+
+  9          28 LOAD_GLOBAL              7 (NULL + z)
+             38 CALL                     0
+             46 POP_TOP
+
+ 10          48 LOAD_GLOBAL              9 (NULL + y)
+             58 CALL                     0
+             66 POP_TOP
+
+ 11          68 RETURN_CONST             1 (1)
+        >>   70 PUSH_EXC_INFO
+
+  4          72 LOAD_GLOBAL              2 (E)
+             82 CHECK_EXC_MATCH
+             84 POP_JUMP_IF_FALSE       13 (to 112)
+             86 POP_TOP
+
+  5          88 LOAD_GLOBAL              5 (NULL + b)
+             98 CALL                     0
+            106 POP_TOP
+            108 POP_EXCEPT
+            110 JUMP_BACKWARD           44 (to 24)
+
+  4     >>  112 RERAISE                  0
+        >>  114 COPY                     3
+            116 POP_EXCEPT
+            118 RERAISE                  1
+            120 PUSH_EXC_INFO
+
+  9         122 LOAD_GLOBAL              7 (NULL + z)
+            132 CALL                     0
+            140 POP_TOP
+            142 RERAISE                  0
+        >>  144 COPY                     3
+            146 POP_EXCEPT
+            148 RERAISE                  1
+ExceptionTable:
+  4 to 22 -> 70 [0]
+  70 to 106 -> 114 [1] lasti
+  112 to 112 -> 114 [1] lasti
+  120 to 142 -> 144 [1] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    try:",
+                "        a()",
+                "    except E:",
+                "        b()",
+                "    try:",
+                "        pass",
+                "    finally:",
+                "        z()",
+                "    y()",
+                "    return 1",
+            ]
+        );
+    }
+
     #[test]
-    fn test_parse_expr() {
-        let instructions = [
-            OpcodeInstruction {
-                opcode: Opcode::LoadConst.into(),
-                opname: "LOAD_CONST".into(),
-                arg: Some(0),
-                argval: Some("'a'".into()),
-                offset: 2,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::LoadName.into(),
-                opname: "LOAD_NAME".into(),
-                arg: Some(0),
-                argval: Some("int".into()),
-                offset: 4,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::LoadConst.into(),
-                opname: "LOAD_CONST".into(),
-                arg: Some(1),
-                argval: Some("'return'".into()),
-                offset: 6,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::LoadName.into(),
-                opname: "LOAD_NAME".into(),
-                arg: Some(0),
-                argval: Some("int".into()),
-                offset: 8,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::BuildTuple.into(),
-                opname: "BUILD_TUPLE".into(),
-                arg: Some(4),
-                argval: None,
-                offset: 10,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::LoadConst.into(),
-                opname: "LOAD_CONST".into(),
-                arg: Some(2),
-                argval: Some(
-                    "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>".into(),
-                ),
-                offset: 12,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::MakeFunction.into(),
-                opname: "MAKE_FUNCTION".into(),
-                arg: Some(4),
-                argval: Some("annotations".into()),
-                offset: 14,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-            OpcodeInstruction {
-                opcode: Opcode::StoreName.into(),
-                opname: "STORE_NAME".into(),
-                arg: Some(1),
-                argval: Some("test".into()),
-                offset: 16,
-                starts_line: Some(1),
-                is_jump_target: false,
-                positions: vec![],
-            },
-        ];
+    fn test_merge_for_loop() {
+        // the body of a loop in a function, with the handlers of a try statement moved to the end
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f8f3752b3f0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 RETURN_CONST             1 (None)
+
+Disassembly of <code object f at 0x7f8f3752b3f0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_FAST                0 (x)
+              4 GET_ITER
+        >>    6 FOR_ITER                21 (to 52)
+             10 STORE_FAST               1 (i)
+
+  3          12 NOP
+
+  4          14 NOP
+
+  6          16 LOAD_GLOBAL              1 (NULL + z)
+             26 CALL                     0
+             34 POP_TOP
+
+  7          36 PUSH_NULL
+             38 LOAD_FAST                1 (i)
+             40 CALL                     0
+             48 POP_TOP
+             50 JUMP_BACKWARD           23 (to 6)
+
+  2     >>   52 END_FOR
+
+  8          54 RETURN_CONST             1 (1)
+             56 PUSH_EXC_INFO
+
+  6          58 LOAD_GLOBAL              1 (NULL + z)
+             68 CALL                     0
+             76 POP_TOP
+             78 RERAISE                  0
+        >>   80 COPY                     3
+             82 POP_EXCEPT
+             84 RERAISE                  1
+ExceptionTable:
+  56 to 78 -> 80 [2] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f(x):",
+                "    for i in x:",
+                "        try:",
+                "            pass",
+                "        finally:",
+                "            z()",
+                "        i()",
+                "    return 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        // 3.13 (`python -m dis -O`) jumps to labels, and the exception table uses them too
+        let code_object_map = r#"
+   0           0       RESUME                   0
+
+   1           2       LOAD_NAME                0 (x)
+               4       GET_ITER
+        L1:    6       FOR_ITER                11 (to L2)
+              10       STORE_NAME               1 (i)
+
+   2          12       LOAD_NAME                2 (print)
+              14       PUSH_NULL
+              16       LOAD_NAME                1 (i)
+              18       CALL                     1
+              26       POP_TOP
+              28       JUMP_BACKWARD           13 (to L1)
+
+   1    L2:   32       END_FOR
+              34       POP_TOP
+
+   3          36       LOAD_NAME                3 (a)
+              38       TO_BOOL
+              46       POP_JUMP_IF_FALSE        8 (to L3)
+
+   4          50       LOAD_NAME                4 (b)
+              52       PUSH_NULL
+              54       CALL                     0
+              62       POP_TOP
+              64       JUMP_FORWARD             7 (to L4)
+
+   6    L3:   66       LOAD_NAME                5 (c)
+              68       PUSH_NULL
+              70       CALL                     0
+              78       POP_TOP
+
+   7    L4:   80       NOP
+
+   8    L5:   82       LOAD_CONST               0 (1)
+              84       STORE_NAME               3 (a)
+        L6:   86       RETURN_CONST             2 (None)
+
+  --    L7:   88       PUSH_EXC_INFO
+
+   9          90       LOAD_NAME                6 (E)
+              92       CHECK_EXC_MATCH
+              94       POP_JUMP_IF_FALSE        5 (to L9)
+              98       POP_TOP
+
+  10         100       LOAD_CONST               1 (2)
+             102       STORE_NAME               4 (b)
+        L8:  104       POP_EXCEPT
+             106       RETURN_CONST             2 (None)
+
+   9    L9:  108       RERAISE                  0
+
+  --   L10:  110       COPY                     3
+             112       POP_EXCEPT
+             114       RERAISE                  1
+ExceptionTable:
+  L5 to L6 -> L7 [0]
+  L7 to L8 -> L10 [1] lasti
+  L9 to L10 -> L10 [1] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+        assert_eq!(
+            code,
+            vec![
+                "for i in x:",
+                "    print(i)",
+                "if a:",
+                "    b()",
+                "else:",
+                "    c()",
+                "try:",
+                "    a = 1",
+                "except E:",
+                "    b = 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_with() {
+        // the items of a multi-item with are compiled as nested withs
+        let code = decompile_text(
+            r#"
+  1           2 PUSH_NULL
+              4 LOAD_NAME                0 (a)
+              6 CALL                     0
+             14 BEFORE_WITH
+             16 STORE_NAME               1 (x)
+             18 PUSH_NULL
+             20 LOAD_NAME                2 (b)
+             22 CALL                     0
+             30 BEFORE_WITH
+             32 UNPACK_SEQUENCE          2
+             36 STORE_NAME               3 (y)
+             38 STORE_NAME               4 (z)
+
+  2          40 PUSH_NULL
+             42 LOAD_NAME                5 (f)
+             44 LOAD_NAME                1 (x)
+             46 LOAD_NAME                3 (y)
+             48 CALL                     2
+             56 POP_TOP
+
+  1          58 LOAD_CONST               0 (None)
+             60 LOAD_CONST               0 (None)
+             62 LOAD_CONST               0 (None)
+             64 CALL                     2
+             72 POP_TOP
+        >>   74 LOAD_CONST               0 (None)
+             76 LOAD_CONST               0 (None)
+             78 LOAD_CONST               0 (None)
+             80 CALL                     2
+             88 POP_TOP
+             90 RETURN_CONST             0 (None)
+        >>   92 PUSH_EXC_INFO
+             94 WITH_EXCEPT_START
+             96 POP_JUMP_IF_TRUE         1 (to 100)
+             98 RERAISE                  2
+        >>  100 POP_TOP
+            102 POP_EXCEPT
+            104 POP_TOP
+            106 POP_TOP
+            108 JUMP_BACKWARD           18 (to 74)
+        >>  110 COPY                     3
+            112 POP_EXCEPT
+            114 RERAISE                  1
+        >>  116 PUSH_EXC_INFO
+            118 WITH_EXCEPT_START
+            120 POP_JUMP_IF_TRUE         1 (to 124)
+            122 RERAISE                  2
+        >>  124 POP_TOP
+            126 POP_EXCEPT
+            128 POP_TOP
+            130 POP_TOP
+            132 RETURN_CONST             0 (None)
+        >>  134 COPY                     3
+            136 POP_EXCEPT
+            138 RERAISE                  1
+ExceptionTable:
+  16 to 30 -> 116 [1] lasti
+  32 to 56 -> 92 [2] lasti
+  58 to 72 -> 116 [1] lasti
+  92 to 100 -> 110 [4] lasti
+  102 to 114 -> 116 [1] lasti
+  116 to 124 -> 134 [3] lasti
+"#,
+        );
+
+        assert_eq!(code, vec!["with a() as x, b() as (y, z):", "    f(x, y)"]);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        // 3.13 splits `FORMAT_VALUE` into `CONVERT_VALUE` and `FORMAT_SIMPLE` / `FORMAT_WITH_SPEC`
+        let code = decompile_text(
+            r#"
+  1          2       LOAD_NAME                0 (x)
+             4       CONVERT_VALUE            2 (repr)
+             6       LOAD_CONST               0 ('>')
+             8       LOAD_NAME                1 (width)
+            10       FORMAT_SIMPLE
+            12       BUILD_STRING             2
+            14       FORMAT_WITH_SPEC
+            16       LOAD_CONST               1 (' ')
+            18       LOAD_NAME                2 (y)
+            20       LOAD_CONST               2 ('.2f')
+            22       FORMAT_WITH_SPEC
+            24       BUILD_STRING             3
+            26       STORE_NAME               3 (a)
+            28       RETURN_CONST             3 (None)
+"#,
+        );
+
+        assert_eq!(code, vec![r#"a = f"{x!r:>{width}} {y:.2f}""#]);
+    }
+
+    #[test]
+    fn test_parse_unpack() {
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (xs)
+              4 UNPACK_EX                1
+              6 STORE_NAME               1 (a)
+              8 STORE_NAME               2 (rest)
+
+  2          10 LOAD_NAME                3 (d)
+             12 LOAD_NAME                4 (k)
+             14 COPY                     2
+             16 COPY                     2
+             18 BINARY_SUBSCR
+             22 LOAD_CONST               0 (1)
+             24 BINARY_OP               13 (+=)
+             28 SWAP                     3
+             30 SWAP                     2
+             32 STORE_SUBSCR
+
+  3          36 LOAD_NAME                5 (v)
+             38 UNPACK_SEQUENCE          2
+             42 LOAD_NAME                6 (self)
+             44 STORE_ATTR               1 (a)
+             54 UNPACK_SEQUENCE          2
+             58 STORE_NAME               7 (b)
+             60 STORE_NAME               8 (c)
+             62 RETURN_CONST             1 (None)
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec!["(a, *rest) = xs", "d[k] += 1", "(self.a, (b, c)) = v"]
+        );
+    }
+
+    #[test]
+    fn test_parse_yield_from() {
+        let code = decompile_text(
+            r#"
+  2           2 LOAD_GLOBAL              0 (h)
+              4 CALL_FUNCTION            0
+              6 GET_YIELD_FROM_ITER
+              8 LOAD_CONST               0 (None)
+             10 YIELD_FROM
+             12 STORE_FAST               0 (y)
+
+  3          14 LOAD_GLOBAL              1 (print)
+             16 LOAD_CONST               1 (2)
+             18 YIELD_VALUE
+             20 CALL_FUNCTION            1
+             22 POP_TOP
+             24 LOAD_CONST               0 (None)
+             26 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(code, vec!["y = yield from h()", "print((yield 2))"]);
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let code = decompile_text(
+            r#"
+  3          18 LOAD_CONST               0 (0)
+             20 LOAD_CONST               1 (None)
+             22 IMPORT_NAME              2 (a.b.c)
+             24 IMPORT_FROM              3 (b)
+             26 SWAP                     2
+             28 POP_TOP
+             30 IMPORT_FROM              4 (c)
+             32 STORE_NAME               5 (d)
+             34 POP_TOP
+
+  5          48 LOAD_CONST               2 (2)
+             50 LOAD_CONST               3 (('x',))
+             52 IMPORT_NAME              8 (pkg)
+             54 IMPORT_FROM              9 (x)
+             56 STORE_NAME               9 (x)
+             58 POP_TOP
+
+  6          60 LOAD_CONST               4 (1)
+             62 LOAD_CONST               5 (('y', 'z'))
+             64 IMPORT_NAME             10
+             66 IMPORT_FROM             11 (y)
+             68 STORE_NAME              11 (y)
+             70 IMPORT_FROM             12 (z)
+             72 STORE_NAME              13 (w)
+             74 POP_TOP
+
+  8          88 LOAD_CONST               0 (0)
+             90 LOAD_CONST               7 (('*',))
+             92 IMPORT_NAME             16 (m)
+             94 IMPORT_STAR
+             96 LOAD_CONST               1 (None)
+             98 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "import a.b.c as d",
+                "from ..pkg import x",
+                "from . import y, z as w",
+                "from m import *"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_delete() {
+        let code = decompile_text(
+            r#"
+  1           2 DELETE_NAME              0 (a)
+              4 LOAD_NAME                1 (b)
+              6 LOAD_CONST               0 (0)
+              8 DELETE_SUBSCR
+             10 LOAD_NAME                2 (c)
+             12 DELETE_ATTR              3 (x)
+
+  2          14 DELETE_NAME              4 (q)
+             16 LOAD_CONST               2 (None)
+             18 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(code, vec!["del a, b[0], c.x", "del q"]);
+    }
+
+    #[test]
+    fn test_parse_slice() {
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (a)
+              4 LOAD_CONST               0 (1)
+              6 LOAD_NAME                1 (x)
+              8 BINARY_SLICE
+             10 STORE_NAME               2 (y)
+
+  2          12 LOAD_NAME                1 (x)
+             14 LOAD_NAME                0 (a)
+             16 LOAD_CONST               0 (1)
+             18 LOAD_CONST               1 (3)
+             20 STORE_SLICE
+
+  3          22 LOAD_NAME                0 (a)
+             24 LOAD_CONST               2 (None)
+             26 LOAD_CONST               2 (None)
+             28 LOAD_CONST               3 (2)
+             30 BUILD_SLICE              3
+             32 DELETE_SUBSCR
+
+  4          34 LOAD_NAME                3 (m)
+             36 LOAD_CONST               4 ((slice(None, None, None), 1))
+             38 BINARY_OP               26 ([])
+             40 STORE_NAME               2 (y)
+
+  5          42 LOAD_NAME                0 (a)
+             44 LOAD_CONST               5 (slice(None, 2, None))
+             46 BINARY_OP               26 ([])
+             48 STORE_NAME               2 (y)
+             50 LOAD_CONST               2 (None)
+             52 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "y = a[1:x]",
+                "a[1:3] = x",
+                "del a[::2]",
+                "y = m[:, 1]",
+                "y = a[:2]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_assign() {
+        let code = decompile_text(
+            r#"
+  1           2 PUSH_NULL
+              4 LOAD_NAME                0 (f)
+              6 PRECALL                  0
+             10 CALL                     0
+             20 COPY                     1
+             22 STORE_NAME               1 (a)
+             24 STORE_NAME               2 (b)
+
+  2          26 LOAD_CONST               0 (1)
+             28 COPY                     1
+             30 LOAD_NAME                3 (x)
+             32 STORE_ATTR               4 (y)
+             42 LOAD_NAME                5 (z)
+             44 LOAD_CONST               1 (0)
+             46 STORE_SUBSCR
+
+  3          50 PUSH_NULL
+             52 LOAD_NAME                6 (print)
+             54 LOAD_CONST               2 (5)
+             56 COPY                     1
+             58 STORE_NAME               7 (m)
+             60 PRECALL                  1
+             64 CALL                     1
+             74 POP_TOP
+
+  4          76 LOAD_NAME                3 (x)
+             78 COPY                     1
+             80 STORE_NAME               8 (n)
+             82 LOAD_CONST               3 (3)
+             84 COMPARE_OP               4 (>)
+             90 STORE_NAME               9 (w)
+             92 LOAD_CONST               4 (None)
+             94 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "a = b = f()",
+                "x.y = z[0] = 1",
+                "print((m := 5))",
+                "w = (n := x) > 3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_display() {
+        let code = decompile_text(
+            r#"
+  1           2 BUILD_SET                0
+              4 LOAD_NAME                0 (a)
+              6 SET_UPDATE               1
+              8 LOAD_CONST               0 (1)
+             10 SET_ADD                  1
+             12 STORE_NAME               1 (s)
+
+  2          14 BUILD_MAP                0
+             16 LOAD_NAME                2 (x)
+             18 DICT_UPDATE              1
+             20 LOAD_CONST               1 ('k')
+             22 LOAD_NAME                3 (v)
+             24 BUILD_MAP                1
+             26 DICT_UPDATE              1
+             28 STORE_NAME               4 (d)
+
+  3          30 BUILD_SET                0
+             32 LOAD_CONST               2 (frozenset({1, 2, 3}))
+             34 SET_UPDATE               1
+             36 STORE_NAME               5 (t)
+
+  4          38 LOAD_CONST               0 (1)
+             40 LOAD_CONST               3 (2)
+             42 LOAD_CONST               4 (3)
+             44 BUILD_LIST               3
+             46 LOAD_NAME                6 (b)
+             48 LIST_EXTEND              1
+             50 STORE_NAME               7 (l)
+             52 LOAD_CONST               5 (None)
+             54 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "s = { *a, 1 }",
+                "d = { **x, 'k': v }",
+                "t = { 1, 2, 3 }",
+                "l = [1, 2, 3, *b]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_none() {
+        // None is a value, only the implicit `return None` at the end is dropped
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (None)
+              4 STORE_NAME               0 (x)
+
+  2           6 PUSH_NULL
+              8 LOAD_NAME                1 (f)
+             10 LOAD_CONST               0 (None)
+             12 LOAD_CONST               1 (1)
+             14 PRECALL                  2
+             18 CALL                     2
+             28 POP_TOP
+
+  3          30 LOAD_CONST               1 (1)
+             32 LOAD_ATTR                2 (real)
+             42 STORE_NAME               3 (y)
+
+  4          44 LOAD_CONST               2 (<code object <lambda> at 0x7f425596def0, file "<dis>", line 4>)
+             46 MAKE_FUNCTION            0
+             48 STORE_NAME               4 (h)
+
+  5          50 LOAD_CONST               3 (<code object p at 0x7f42559ce5b0, file "<dis>", line 5>)
+             52 MAKE_FUNCTION            0
+             54 STORE_NAME               5 (p)
+
+ 10          56 LOAD_CONST               4 (<code object q at 0x7f42559cec40, file "<dis>", line 10>)
+             58 MAKE_FUNCTION            0
+             60 STORE_NAME               6 (q)
+             62 LOAD_CONST               0 (None)
+             64 RETURN_VALUE
+
+Disassembly of <code object <lambda> at 0x7f425596def0, file "<dis>", line 4>:
+  4           0 RESUME                   0
+              2 LOAD_CONST               0 (None)
+              4 RETURN_VALUE
+
+Disassembly of <code object p at 0x7f42559ce5b0, file "<dis>", line 5>:
+  5           0 RESUME                   0
+
+  6           2 LOAD_FAST                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE     2 (to 10)
+
+  7           6 LOAD_CONST               0 (None)
+              8 RETURN_VALUE
+
+  8     >>   10 LOAD_GLOBAL              1 (NULL + print)
+             22 LOAD_FAST                0 (a)
+             24 PRECALL                  1
+             28 CALL                     1
+             38 POP_TOP
+
+  9          40 LOAD_CONST               1 (1)
+             42 STORE_FAST               1 (x)
+             44 LOAD_CONST               0 (None)
+             46 RETURN_VALUE
+
+Disassembly of <code object q at 0x7f42559cec40, file "<dis>", line 10>:
+ 10           0 RESUME                   0
+
+ 11           2 LOAD_FAST                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE    16 (to 38)
+
+ 12           6 LOAD_GLOBAL              1 (NULL + f)
+             18 PRECALL                  0
+             22 CALL                     0
+             32 POP_TOP
+             34 LOAD_CONST               0 (None)
+             36 RETURN_VALUE
+
+ 11     >>   38 LOAD_CONST               0 (None)
+             40 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
 
         assert_eq!(
-            Expr::parse(&instructions).unwrap(),
-            Box::new(Expr {
-                bodys: vec![ExpressionEnum::Function(Function {
-                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
-                        .into(),
-                    name: "test".into(),
-                    args: [
-                        FastVariable {
-                            index: 0,
-                            name: "a".into(),
-                            annotation: Some("int".into()),
-                            ..Default::default()
-                        },
-                        FastVariable {
-                            index: 1,
-                            name: "return".into(),
-                            annotation: Some("int".into()),
-                            ..Default::default()
-                        },
-                    ]
-                    .into(),
-                    defaults: vec![],
-                    start_line: 1,
-                    end_line: 1,
-                    bodys: vec![],
-                    start_offset: 14,
-                    end_offset: 14,
-                    ..Default::default()
-                },),]
-                .into()
+            code,
+            vec![
+                "x = None",
+                "f(None, 1)",
+                "y = (1).real",
+                "h = lambda : None",
+                "",
+                "",
+                "def p(a):",
+                "    if a:",
+                "        return",
+                "    else:",
+                "        print(a)",
+                "        x = 1",
+                "",
+                "",
+                "def q(a):",
+                "    if a:",
+                "        f()"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_assert_raise() {
+        let code = decompile_text(
+            r#"
+  2           2 LOAD_NAME                0 (x)
+              4 POP_JUMP_FORWARD_IF_TRUE    13 (to 32)
+              6 LOAD_ASSERTION_ERROR
+              8 LOAD_CONST               1 ('msg ')
+             10 LOAD_NAME                0 (x)
+             12 FORMAT_VALUE             0
+             14 BUILD_STRING             2
+             16 PRECALL                  0
+             20 CALL                     0
+             30 RAISE_VARARGS            1
+
+  3     >>   32 LOAD_NAME                0 (x)
+             34 POP_JUMP_FORWARD_IF_FALSE     2 (to 40)
+             36 LOAD_ASSERTION_ERROR
+             38 RAISE_VARARGS            1
+
+  4     >>   40 LOAD_NAME                1 (V)
+             42 LOAD_CONST               2 (1)
+             44 PRECALL                  1
+             48 CALL                     1
+             58 LOAD_CONST               0 (None)
+             60 RAISE_VARARGS            2
+
+  5          62 RAISE_VARARGS            0
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "assert x, f\"msg {x}\"",
+                "assert not x",
+                "raise V(1) from None",
+                "raise"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_annotation() {
+        let code = decompile_text(
+            r#"
+  1           2 SETUP_ANNOTATIONS
+              4 LOAD_CONST               0 (1)
+              6 STORE_NAME               0 (x)
+              8 LOAD_NAME                1 (int)
+             10 LOAD_NAME                2 (__annotations__)
+             12 LOAD_CONST               1 ('x')
+             14 STORE_SUBSCR
+
+  2          18 LOAD_NAME                3 (list)
+             20 LOAD_NAME                1 (int)
+             22 BINARY_SUBSCR
+             26 LOAD_NAME                2 (__annotations__)
+             28 LOAD_CONST               2 ('y')
+             30 STORE_SUBSCR
+
+  3          34 LOAD_CONST               3 ('q')
+             36 LOAD_NAME                4 (a)
+             38 STORE_ATTR               5 (b)
+             48 LOAD_NAME                6 (str)
+             50 POP_TOP
+             52 RETURN_CONST             4 (None)
+"#,
+        );
+
+        assert_eq!(code, vec!["x: int = 1", "y: list[int]", "a.b: str = 'q'"]);
+    }
+
+    #[test]
+    fn test_parse_type_params() {
+        // the body of `<generic parameters of Pair>` for `type Pair[K: str, *Ts] = ...`
+        let code_object_map = r#"
+  1           6 LOAD_CONST               0 ('Pair')
+              8 LOAD_CONST               1 ('K')
+             10 LOAD_CONST               2 (<code object K at 0x7f612a1796f0, file "a.py", line 1>)
+             12 MAKE_FUNCTION            0
+             14 CALL_INTRINSIC_2         2 (INTRINSIC_TYPEVAR_WITH_BOUND)
+             16 COPY                     1
+             18 STORE_DEREF              0 (K)
+             20 LOAD_CONST               3 ('Ts')
+             22 CALL_INTRINSIC_1         9 (INTRINSIC_TYPEVARTUPLE)
+             24 COPY                     1
+             26 STORE_DEREF              1 (Ts)
+             28 BUILD_TUPLE              2
+             30 LOAD_CLOSURE             0 (K)
+             32 LOAD_CLOSURE             1 (Ts)
+             34 BUILD_TUPLE              2
+             36 LOAD_CONST               4 (<code object Pair at 0x7f612a136090, file "a.py", line 1>)
+             38 MAKE_FUNCTION            8 (closure)
+             40 BUILD_TUPLE              3
+             42 CALL_INTRINSIC_1        11 (INTRINSIC_TYPEALIAS)
+             44 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let expr = Expr::parse(code_object_map.get("<main>").unwrap()).unwrap();
+        let alias = expr.query_singleton::<TypeAlias>().unwrap();
+        assert_eq!(alias.name, "Pair");
+
+        let params = alias
+            .type_params
+            .iter()
+            .map(|param| match param {
+                ExpressionEnum::TypeParam(param) => (
+                    param.name.as_str(),
+                    param.kind.clone(),
+                    param.bound.is_some(),
+                ),
+                _ => panic!("Expect a type parameter"),
             })
-        )
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                ("K", TypeParamKind::TypeVar, true),
+                ("Ts", TypeParamKind::TypeVarTuple, false)
+            ]
+        );
     }
 
     #[test]
-    fn test_query() {
-        let expr = Box::new(Expr {
-            bodys: vec![ExpressionEnum::Assign(Assign {
-                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                    value: "test".into(),
-                    ..Default::default()
-                })),
-                values: Box::new(ExpressionEnum::Function(Function {
-                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
-                        .into(),
-                    name: "test".into(),
-                    args: vec![],
-                    defaults: vec![],
-                    start_line: 1,
-                    end_line: 1,
-                    bodys: vec![],
-                    ..Default::default()
-                })),
-                operator: "=".into(),
-                ..Default::default()
-            })]
-            .into(),
+    fn test_parse_precedence() {
+        let code = decompile_text(
+            r#"
+  1           2 LOAD_NAME                0 (a)
+              4 LOAD_NAME                1 (b)
+              6 BINARY_OP                0 (+)
+             10 LOAD_NAME                2 (c)
+             12 BINARY_OP                5 (*)
+             16 STORE_NAME               3 (x)
+
+  2          18 LOAD_NAME                0 (a)
+             20 LOAD_NAME                1 (b)
+             22 LOAD_NAME                2 (c)
+             24 BINARY_OP               10 (-)
+             28 BINARY_OP               10 (-)
+             32 STORE_NAME               4 (y)
+
+  3          34 LOAD_NAME                0 (a)
+             36 UNARY_NEGATIVE
+             38 LOAD_CONST               0 (2)
+             40 BINARY_OP                8 (**)
+             44 STORE_NAME               5 (z)
+
+  4          46 LOAD_NAME                0 (a)
+             48 POP_JUMP_FORWARD_IF_TRUE     2 (to 54)
+             50 LOAD_NAME                1 (b)
+             52 JUMP_IF_FALSE_OR_POP     1 (to 56)
+        >>   54 LOAD_NAME                2 (c)
+        >>   56 STORE_NAME               6 (u)
+
+  5          58 LOAD_NAME                1 (b)
+             60 POP_JUMP_FORWARD_IF_FALSE     2 (to 66)
+             62 LOAD_NAME                0 (a)
+             64 JUMP_FORWARD             1 (to 68)
+        >>   66 LOAD_NAME                2 (c)
+        >>   68 LOAD_ATTR                7 (d)
+             78 STORE_NAME               8 (s)
+             80 LOAD_CONST               1 (None)
+             82 RETURN_VALUE
+"#,
+        );
+
+        assert_eq!(
+            code,
+            vec![
+                "x = (a + b) * c",
+                "y = a - (b - c)",
+                "z = (-a) ** 2",
+                "u = (a or b) and c",
+                "s = (a if b else c).d"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_style() {
+        let code_object_map = r#"
+  1           2 LOAD_NAME                0 (a)
+              4 GET_ITER
+        >>    6 FOR_ITER                18 (to 44)
+              8 STORE_NAME               1 (i)
+
+  2          10 PUSH_NULL
+             12 LOAD_NAME                2 (print)
+             14 LOAD_CONST               0 ('a')
+             16 LOAD_CONST               1 ("it's")
+             18 BUILD_LIST               0
+             20 LOAD_CONST               2 ((1, 2, 3))
+             22 LIST_EXTEND              1
+             24 LOAD_NAME                3 (bbbbbbbbbb)
+             26 PRECALL                  4
+             30 CALL                     4
+             40 POP_TOP
+             42 JUMP_BACKWARD           19 (to 6)
+
+  1     >>   44 LOAD_CONST               3 (None)
+             46 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = PythonGenerator::new(CodeStyle {
+            indent_width: 2,
+            quote_style: QuoteStyle::Double,
+            max_line_length: Some(30),
+            ..Default::default()
         });
-        let assign_query = expr.query::<Assign>();
-        let function_query = expr.query::<Function>();
-        //dbg!(query);
-        //assert!(false);
+
+        let code = decompile_map(&code_object_map, &generator);
+
         assert_eq!(
-            assign_query,
-            vec![&Assign {
-                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                    value: "test".into(),
-                    ..Default::default()
-                })),
-                values: Box::new(ExpressionEnum::Function(Function {
-                    mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
-                        .into(),
-                    name: "test".into(),
-                    args: vec![],
-                    defaults: vec![],
-                    start_line: 1,
-                    end_line: 1,
-                    bodys: vec![],
-                    ..Default::default()
-                },)),
-                operator: "=".into(),
-                ..Default::default()
-            }]
+            code,
+            vec![
+                "for i in a:",
+                "  print(",
+                "    \"a\",",
+                "    \"it's\",",
+                "    [1, 2, 3],",
+                "    bbbbbbbbbb,",
+                "  )",
+            ]
+        );
+
+        // the indent and the assignment count too
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7fd52bc72830, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+              8 RETURN_CONST             1 (None)
+
+Disassembly of <code object f at 0x7fd52bc72830, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2           2 LOAD_GLOBAL              1 (NULL + some_really_long_function_name)
+             12 LOAD_GLOBAL              2 (argument_one)
+             22 LOAD_GLOBAL              4 (argument_two)
+             32 LOAD_GLOBAL              6 (argument_three)
+             42 CALL                     3
+             50 STORE_FAST               0 (result)
+
+  3          52 LOAD_FAST                0 (result)
+             54 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = PythonGenerator::new(CodeStyle {
+            max_line_length: Some(79),
+            ..Default::default()
+        });
+
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    result = some_really_long_function_name(",
+                "        argument_one,",
+                "        argument_two,",
+                "        argument_three,",
+                "    )",
+                "    return result",
+            ]
         );
+    }
+
+    #[test]
+    fn test_line_aligned() {
+        let code_object_map = r#"
+  1           2 LOAD_CONST               0 (1)
+              4 STORE_NAME               0 (a)
+
+  4           6 NOP
+
+  5           8 LOAD_CONST               1 (2)
+             10 STORE_NAME               1 (b)
+             12 JUMP_FORWARD            11 (to 36)
+        >>   14 PUSH_EXC_INFO
+
+  6          16 LOAD_NAME                2 (E)
+             18 CHECK_EXC_MATCH
+             20 POP_JUMP_FORWARD_IF_FALSE     3 (to 28)
+             22 POP_TOP
+
+  7          24 POP_EXCEPT
+             26 JUMP_FORWARD             4 (to 36)
+
+  6     >>   28 RERAISE                  0
+        >>   30 COPY                     3
+             32 POP_EXCEPT
+             34 RERAISE                  1
+
+  8     >>   36 PUSH_NULL
+             38 LOAD_NAME                3 (print)
+             40 LOAD_NAME                1 (b)
+             42 PRECALL                  1
+             46 CALL                     1
+             56 POP_TOP
+             58 LOAD_CONST               2 (None)
+             60 RETURN_VALUE
+ExceptionTable:
+  8 to 10 -> 14 [0]
+  14 to 22 -> 30 [1] lasti
+  28 to 28 -> 30 [1] lasti
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = PythonGenerator::new(CodeStyle {
+            line_aligned: true,
+            ..Default::default()
+        });
+        let code = decompile_map(&code_object_map, &generator);
+
         assert_eq!(
-            function_query,
-            vec![&Function {
-                mark: "<code object test at 0x00000279922BDB80, file \"test/def.py\", line 1>"
-                    .into(),
-                name: "test".into(),
-                args: vec![],
-                defaults: vec![],
-                start_line: 1,
-                end_line: 1,
-                bodys: vec![],
-                ..Default::default()
-            }]
+            code,
+            vec![
+                "a = 1",
+                "",
+                "",
+                "try:",
+                "    b = 2",
+                "except E:",
+                "    pass",
+                "print(b)"
+            ]
+        );
+
+        // the simple statements on the same line are joined, the body of `if` can't be
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (1)
+              4 STORE_NAME               0 (a)
+              6 LOAD_CONST               1 (2)
+              8 STORE_NAME               1 (b)
+
+  2          10 LOAD_NAME                0 (a)
+             12 POP_JUMP_FORWARD_IF_FALSE    11 (to 36)
+             14 PUSH_NULL
+             16 LOAD_NAME                2 (print)
+             18 LOAD_NAME                1 (b)
+             20 PRECALL                  1
+             24 CALL                     1
+             34 POP_TOP
+
+  3     >>   36 LOAD_CONST               2 (3)
+             38 STORE_NAME               3 (c)
+             40 LOAD_CONST               3 (4)
+             42 STORE_NAME               4 (d)
+             44 LOAD_CONST               4 (5)
+             46 STORE_NAME               5 (e)
+             48 LOAD_CONST               5 (None)
+             50 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code,
+            vec![
+                "a = 1; b = 2",
+                "if a:",
+                "    print(b)  # pyrev: line 2",
+                "c = 3; d = 4; e = 5  # pyrev: line 3"
+            ]
         );
     }
 
     #[test]
-    fn test_any() {
-        let expr = Assign {
-            target: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                value: "a".to_string(),
-                ..Default::default()
-            })),
-            values: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                value: "1".to_string(),
-                ..Default::default()
-            })),
-            operator: "=".to_string(),
+    fn test_annotate() {
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (1)
+              4 STORE_NAME               0 (a)
+
+  2           6 PUSH_NULL
+              8 LOAD_NAME                1 (print)
+             10 LOAD_NAME                0 (a)
+             12 PRECALL                  1
+             16 CALL                     1
+             26 POP_TOP
+             28 LOAD_CONST               1 (None)
+             30 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = AnnotatedGenerator::new(
+            &code_object_map,
+            CodeStyle::default(),
+            Annotation::Instructions,
+        );
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code,
+            vec![
+                "# line 1, offset 2-4",
+                "#      2 LOAD_CONST 0 (1)",
+                "#      4 STORE_NAME 0 (a)",
+                "a = 1",
+                "# line 2, offset 6-26",
+                "#      6 PUSH_NULL",
+                "#      8 LOAD_NAME 1 (print)",
+                "#     10 LOAD_NAME 0 (a)",
+                "#     12 PRECALL 1",
+                "#     16 CALL 1",
+                "#     26 POP_TOP",
+                "print(a)"
+            ]
+        );
+
+        // the test belongs to the `if`, not to the first statement of its body
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (x)
+              4 LOAD_CONST               0 (10)
+              6 COMPARE_OP               0 (<)
+             12 POP_JUMP_FORWARD_IF_FALSE    10 (to 34)
+
+  2          14 PUSH_NULL
+             16 LOAD_NAME                1 (f)
+             18 PRECALL                  0
+             22 CALL                     0
+             32 POP_TOP
+
+  3     >>   34 LOAD_CONST               1 (1)
+             36 STORE_NAME               2 (y)
+             38 LOAD_CONST               2 (None)
+             40 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator =
+            AnnotatedGenerator::new(&code_object_map, CodeStyle::default(), Annotation::Offsets);
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code,
+            vec![
+                "# line 1, offset 2-32",
+                "if x < 10:",
+                "    # offset 14-22",
+                "    f()",
+                "# line 3, offset 34-36",
+                "y = 1"
+            ]
+        );
+
+        // the `POP_TOP` of the call is in the range of the `if`, and the implicit returns
+        // belong to no statement
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_NAME                0 (x)
+              4 POP_JUMP_FORWARD_IF_FALSE    13 (to 32)
+
+  2           6 PUSH_NULL
+              8 LOAD_NAME                1 (print)
+             10 LOAD_NAME                0 (x)
+             12 PRECALL                  1
+             16 CALL                     1
+             26 POP_TOP
+             28 LOAD_CONST               0 (None)
+             30 RETURN_VALUE
+
+  1     >>   32 LOAD_CONST               0 (None)
+             34 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = AnnotatedGenerator::new(
+            &code_object_map,
+            CodeStyle::default(),
+            Annotation::Instructions,
+        );
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code,
+            vec![
+                "# line 1, offset 2-30",
+                "#      2 LOAD_NAME 0 (x)",
+                "#      4 POP_JUMP_FORWARD_IF_FALSE 13 (to 32)",
+                "if x:",
+                "    # line 2, offset 6-26",
+                "    #      6 PUSH_NULL",
+                "    #      8 LOAD_NAME 1 (print)",
+                "    #     10 LOAD_NAME 0 (x)",
+                "    #     12 PRECALL 1",
+                "    #     16 CALL 1",
+                "    #     26 POP_TOP",
+                "    print(x)",
+            ]
+        );
+
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 PUSH_NULL
+              4 LOAD_BUILD_CLASS
+              6 LOAD_CONST               0 (<code object C at 0x7f0aef37df10, file "<dis>", line 1>)
+              8 MAKE_FUNCTION            0
+             10 LOAD_CONST               1 ('C')
+             12 PRECALL                  2
+             16 CALL                     2
+             26 STORE_NAME               0 (C)
+             28 LOAD_CONST               2 (None)
+             30 RETURN_VALUE
+
+Disassembly of <code object C at 0x7f0aef37df10, file "<dis>", line 1>:
+  1           0 RESUME                   0
+              2 LOAD_NAME                0 (__name__)
+              4 STORE_NAME               1 (__module__)
+              6 LOAD_CONST               0 ('C')
+              8 STORE_NAME               2 (__qualname__)
+
+  2          10 LOAD_CONST               1 (3)
+             12 STORE_NAME               3 (z)
+             14 LOAD_CONST               2 (None)
+             16 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let generator = AnnotatedGenerator::new(
+            &code_object_map,
+            CodeStyle::default(),
+            Annotation::Instructions,
+        );
+        let code = decompile_map(&code_object_map, &generator);
+
+        assert_eq!(
+            code[code.len() - 4..],
+            [
+                "    # line 2, offset 10-12",
+                "    #     10 LOAD_CONST 1 (3)",
+                "    #     12 STORE_NAME 3 (z)",
+                "    z = 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_code_object() {
+        // `g` and `a` are not loaded, so `f` can't be decompiled
+        let code_object_map = r#"
+  0           0 RESUME                   0
+
+  1           2 LOAD_CONST               0 (<code object f at 0x7f083402c8f0, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+
+  4           8 PUSH_NULL
+             10 LOAD_NAME                0 (f)
+             12 LOAD_CONST               1 (1)
+             14 PRECALL                  1
+             18 CALL                     1
+             28 STORE_NAME               1 (x)
+             30 LOAD_CONST               2 (None)
+             32 RETURN_VALUE
+
+Disassembly of <code object f at 0x7f083402c8f0, file "<dis>", line 1>:
+  1           0 RESUME                   0
+
+  2          16 PRECALL                  1
+             20 CALL                     1
+             30 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "def f():",
+                "    # pyrev: failed to decompile f: [Call] Stack is empty, deviation is 20",
+                "    #    0 RESUME 0",
+                "    #   16 PRECALL 1",
+                "    #   20 CALL 1",
+                "    #   30 RETURN_VALUE",
+                "    pass",
+                "",
+                "",
+                "x = f(1)"
+            ]
+        );
+
+        // the lambda is a part of the statement, so the whole statement can't be generated
+        let code_object_map = r#"
+  1           2 LOAD_CONST               0 (<code object <lambda> at 0x7f0834028a30, file "<dis>", line 1>)
+              4 MAKE_FUNCTION            0
+              6 STORE_NAME               0 (f)
+
+  2           8 LOAD_CONST               1 (1)
+             10 STORE_NAME               1 (x)
+             12 LOAD_CONST               2 (None)
+             14 RETURN_VALUE
+
+Disassembly of <code object <lambda> at 0x7f0834028a30, file "<dis>", line 1>:
+  1           0 RESUME                   0
+              2 PRECALL                  1
+              6 CALL                     1
+             16 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+        let code = decompile_map(&code_object_map, &PythonGenerator::default());
+
+        assert_eq!(
+            code,
+            vec![
+                "# pyrev: failed to generate: <lambda> failed to decompile",
+                "x = 1"
+            ]
+        );
+
+        // the jump target has no instruction before it
+        let code_object_map = r#"
+  1     >>    2 LOAD_NAME                0 (a)
+              4 POP_JUMP_FORWARD_IF_FALSE     0 (to 2)
+              6 LOAD_CONST               2 (None)
+              8 RETURN_VALUE
+"#
+        .parse_opcode()
+        .unwrap();
+
+        assert!(Expr::parse(code_object_map.get("<main>").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_build_docstring() {
+        let docstring = ExpressionEnum::Docstring(Docstring {
+            value: r#"'Say "hi"\n    to C:\\temp\\\n\n    caf\xe9 \u4f60\n    '"#.to_string(),
             ..Default::default()
-        };
-        let any = expr.try_query::<Assign>();
+        });
 
-        //dbg!(any);
-        //assert!(false);
         assert_eq!(
-            any,
-            Some(&Assign {
-                target: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                    value: "a".to_string(),
-                    ..Default::default()
-                })),
-                values: Box::new(ExpressionEnum::BaseValue(BaseValue {
-                    value: "1".to_string(),
-                    ..Default::default()
-                })),
-                operator: "=".to_string(),
-                ..Default::default()
-            })
-        )
+            docstring.build().unwrap(),
+            vec![
+                r#""""Say "hi""#,
+                r#"to C:\\temp\\"#,
+                "",
+                "café 你",
+                r#"""""#
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuple_values() {
+        let values = tuple_values(ExpressionEnum::BaseValue(BaseValue {
+            value: "(1, 'x, y', (2, 3), \"a'\\\"\", [4, 5])".to_string(),
+            ..Default::default()
+        }))
+        .into_iter()
+        .flat_map(|x| x.build().unwrap())
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            vec!["1", "'x, y'", "(2, 3)", "\"a'\\\"\"", "[4, 5]"]
+        );
     }
 }
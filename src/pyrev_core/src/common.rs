@@ -156,6 +156,45 @@ pub struct Local {
     pub is_arg: bool,
 }
 
+/// `dis.code_info` 打印出的代码对象信息, 用来还原函数签名
+/// 例如:
+/// Argument count:    2
+/// Positional-only arguments: 1
+/// Kw-only arguments: 2
+/// Flags:             OPTIMIZED, NEWLOCALS, VARARGS, VARKEYWORDS
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeInfo {
+    pub arg_count: usize,
+    pub pos_only_arg_count: usize,
+    pub kw_only_arg_count: usize,
+    pub flags: Vec<String>,
+    pub constants: Vec<String>,
+    pub var_names: Vec<String>,
+}
+
+impl CodeInfo {
+    pub fn has_varargs(&self) -> bool {
+        self.flags.iter().any(|x| x == "VARARGS")
+    }
+
+    pub fn has_varkeywords(&self) -> bool {
+        self.flags.iter().any(|x| x == "VARKEYWORDS")
+    }
+
+    /// 函数的文档字符串是第一个常量, 3.14 之前没有文档字符串的时候第一个常量是 None
+    pub fn docstring(&self) -> Option<&String> {
+        self.constants
+            .first()
+            .filter(|x| x.starts_with('\'') || x.starts_with('"'))
+    }
+
+    pub fn is_async(&self) -> bool {
+        self.flags
+            .iter()
+            .any(|x| x == "COROUTINE" || x == "ASYNC_GENERATOR")
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct TraceBack {
     /// is_store is true if the arg is stored in the function argument
@@ -168,6 +207,12 @@ pub struct TraceBack {
     jumps: OrderMap<usize, usize>,
     /// is_async is true if the function is async
     is_async: bool,
+    /// the names which are declared as `global` in the function
+    globals: Vec<String>,
+    /// the names which are declared as `nonlocal` in the function
+    nonlocals: Vec<String>,
+    /// the code info printed before the disassembly, if any
+    code_info: Option<CodeInfo>,
 }
 
 #[allow(unused)]
@@ -212,13 +257,47 @@ impl TraceBack {
         &mut self.jumps
     }
 
+    pub fn insert_global(&mut self, name: &str) {
+        if !self.globals.iter().any(|x| x == name) {
+            self.globals.push(name.to_string());
+        }
+    }
+
+    pub fn get_globals(&self) -> &Vec<String> {
+        &self.globals
+    }
+
+    pub fn insert_nonlocal(&mut self, name: &str) {
+        if !self.nonlocals.iter().any(|x| x == name) {
+            self.nonlocals.push(name.to_string());
+        }
+    }
+
+    pub fn get_nonlocals(&self) -> &Vec<String> {
+        &self.nonlocals
+    }
+
+    pub fn set_code_info(&mut self, code_info: Option<CodeInfo>) {
+        self.code_info = code_info;
+    }
+
+    pub fn get_code_info(&self) -> Option<&CodeInfo> {
+        self.code_info.as_ref()
+    }
+
     pub fn extend(&mut self, tb: Self) {
         self.locals.extend(tb.locals);
         self.jumps.extend(tb.jumps);
+        tb.globals.iter().for_each(|x| self.insert_global(x));
+        tb.nonlocals.iter().for_each(|x| self.insert_nonlocal(x));
     }
 
     pub fn asyncable(&self) -> bool {
-        self.is_async
+        // code info 的 flags 比从指令推断的更准确
+        match &self.code_info {
+            Some(code_info) => code_info.is_async(),
+            None => self.is_async,
+        }
     }
 
     pub fn mark_async(&mut self) {
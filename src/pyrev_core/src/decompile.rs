@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Debug;
 
@@ -7,42 +8,63 @@ use super::prelude::*;
 use pyrev_ast::*;
 
 pub trait Decompiler {
-    fn decompile(&self) -> Result<DecompiledCode>;
+    fn decompile(&self) -> Result<DecompiledCode> {
+        self.decompile_with(&PythonGenerator::default())
+    }
+    fn decompile_with<G: CodeGenerator>(&self, generator: &G) -> Result<DecompiledCode>;
     #[allow(unused)]
     fn optimize(&self, expr: &Expr) -> Result<Expr>;
 }
 
 impl Decompiler for CodeObjectMap {
-    /// 从字节码对象映射表中解析为AST, 然后再从AST解析为代码
-    fn decompile(&self) -> Result<DecompiledCode> {
+    /// 从字节码对象映射表中解析为AST, 然后再用`generator`从AST生成代码
+    fn decompile_with<G: CodeGenerator>(&self, generator: &G) -> Result<DecompiledCode> {
         let mut decompiled_code = DecompiledCode::default();
         let mut exprs_map = HashMap::new();
         for (mark, code_object) in self.iter() {
-            let expr = Expr::parse(code_object)?;
-            let trace = get_trace(code_object)?;
+            // one unsupported code object shouldn't fail the whole file
+            let expr = match Expr::parse(code_object).and_then(|expr| {
+                merge_for_loops(&expr)?;
+                Ok(expr)
+            }) {
+                Ok(expr) => *expr,
+                Err(err) => {
+                    warn!("Failed to decompile {}: {}", mark, err);
+                    failed_expr(mark, code_object, &err.to_string())
+                }
+            };
+            let mut trace = get_trace(code_object).unwrap_or_else(|err| {
+                warn!("Failed to trace {}: {}", mark, err);
+                TraceBack::default()
+            });
+            trace.set_code_info(code_object.info.clone());
 
-            exprs_map.insert(mark.clone(), (*expr, trace));
+            exprs_map.insert(mark.clone(), (expr, trace));
         }
         #[cfg(debug_assertions)]
         {
             // dbg!(&exprs_map);
         }
 
-        let mut main_expr = merge("<main>", &exprs_map)?;
+        let mut main_expr = merge("<main>", &exprs_map).unwrap_or_else(|err| {
+            warn!("Failed to merge <main>: {}", err);
+            let instructions = self.get("<main>").map_or(&[][..], |x| x.as_slice());
+            failed_expr("<main>", instructions, &err.to_string())
+        });
         #[cfg(debug_assertions)]
         {
             // dbg!(&main_expr);
         }
         fixed_async_object(&mut main_expr, &exprs_map)?;
+        patch_docstring(&mut main_expr.bodys);
+        strip_implicit_returns(&mut main_expr.bodys);
 
-        for (i, instruction) in main_expr.iter().enumerate() {
-            let code = instruction
-                .build()?
-                .iter()
-                .enumerate()
-                .map(|(l, s)| (l + i, s.to_string()))
-                .collect::<Vec<_>>();
-            decompiled_code.code.extend(code);
+        for (i, line) in generator
+            .generate_module(&main_expr.bodys)?
+            .into_iter()
+            .enumerate()
+        {
+            decompiled_code.insert(i + 1, line);
         }
         Ok(decompiled_code)
     }
@@ -77,7 +99,9 @@ fn fixed_async_object(
 ///
 /// 比如`<main>`有一个函数foo, 就需要把foo的定义合并到`<main>`里面的foo Function的 bodys
 fn merge(mark: &str, maps: &HashMap<String, (Expr, TraceBack)>) -> Result<Expr> {
-    let (this_expr, traceback) = maps.get(mark).ok_or(format!("No {} expr", &mark))?;
+    let (this_expr, _) = maps.get(mark).ok_or(format!("No {} expr", &mark))?;
+    // the functions which are defined in the class body
+    let mut methods = Vec::new();
 
     loop {
         let mut is_merged = true;
@@ -85,74 +109,54 @@ fn merge(mark: &str, maps: &HashMap<String, (Expr, TraceBack)>) -> Result<Expr>
         // merge the function
         let function_query = this_expr.query::<Function>();
         for function in function_query {
-            if function.bodys.is_empty() {
-                let new_bodys = maps
-                    .get(&function.mark)
-                    .ok_or(format!("No {} expr", &function.mark))?
-                    .0
-                    .bodys
-                    .clone();
-
+            if function.bodys.is_empty() && function.name.starts_with("<generic parameters of ") {
+                let generic = lookup(maps, &function.mark);
+                let definitions = match resolve_generic(function, &generic.0) {
+                    Ok(definition) => vec![definition],
+                    // the generic wrapper itself failed to decompile
+                    Err(_) if is_failed(&generic.0) => generic.0.bodys.clone(),
+                    Err(err) => {
+                        warn!("Failed to resolve {}: {}", function.mark, err);
+                        failed_expr(&function.mark, &[], &err.to_string()).bodys
+                    }
+                };
+                if let [ExpressionEnum::Function(method)] = definitions.as_slice()
+                    && methods.contains(&function.mark)
+                {
+                    methods.push(method.mark.clone());
+                }
                 function.with_mut_unchecked().patch_by(|mut f| {
-                    f.bodys = new_bodys;
-                    traceback
-                        .get_locals()
-                        .iter()
-                        .for_each(|(k, Local { name, is_arg, .. })| {
-                            if *is_arg {
-                                f.args.push(FastVariable {
-                                    index: *k,
-                                    name: name.to_owned(),
-                                    annotation: None,
-                                    ..Default::default()
-                                })
-                            }
-                        })
+                    f.bodys = definitions;
                 })?;
-
                 is_merged = false;
+                continue;
             }
-
-            // update the function arguments
-            let function_locals = maps
-                .get(&function.mark)
-                .ok_or(format!("No {} expr", &function.mark))?
-                .1
-                .get_locals()
-                .clone();
-            let args = function.args.clone();
-            let mut function_args = HashMap::new();
-            for (k, Local { name, is_arg, .. }) in function_locals.iter() {
-                if *is_arg && !function_args.contains_key(name) {
-                    function_args.insert(name, (k, None));
-                }
-            }
-            for fv in args.iter() {
-                if function_args.contains_key(&fv.name) {
-                    function_args
-                        .get_mut(&fv.name)
-                        .unwrap()
-                        .1
-                        .clone_from(&fv.annotation)
+            if function.bodys.is_empty() {
+                let lookup = lookup(maps, &function.mark);
+                let (function_expr, function_trace) = lookup.as_ref();
+                // the walrus in a comprehension binds the name in the enclosing scope
+                let mut new_bodys = if is_comprehension(&function.name) {
+                    Vec::new()
                 } else {
-                    function_args.insert(&fv.name, (&fv.index, fv.annotation.clone()));
+                    declarations(function_trace)
+                };
+                if let Some(docstring) = function_docstring(function, function_expr, function_trace)
+                {
+                    new_bodys.insert(0, docstring);
                 }
-            }
+                new_bodys.extend(function_expr.bodys.iter().cloned());
+                let is_method = methods.contains(&function.mark);
+
+                function.with_mut_unchecked().patch_by(|mut f| {
+                    f.bodys = new_bodys;
+                    patch_signature(&mut f, function_trace, is_method);
+                })?;
 
-            function.with_mut_unchecked().patch_by(|mut f| {
-                f.args.clear();
-                for (arg, (idx, anno)) in function_args.iter() {
-                    f.args.push(FastVariable {
-                        index: **idx,
-                        name: arg.to_string(),
-                        annotation: anno.clone(),
-                        ..Default::default()
-                    })
+                // an empty body will never be merged
+                if !function_expr.bodys.is_empty() {
+                    is_merged = false;
                 }
-            })?;
-            function
-                .with_mut_unchecked()
-                .patch_by(|mut f| f.args.sort_by(|a, b| a.index.cmp(&b.index)))?;
+            }
 
             #[cfg(debug_assertions)]
             {
@@ -164,35 +168,28 @@ fn merge(mark: &str, maps: &HashMap<String, (Expr, TraceBack)>) -> Result<Expr>
         let class_query = this_expr.query::<Class>();
         for class in class_query {
             if class.members.is_empty() {
-                let new_members = maps
-                    .get(&class.mark)
-                    .ok_or(format!("No {} expr", &class.mark))?
+                let mut new_members = lookup(maps, &class.mark)
                     .0
                     .bodys
-                    .clone();
-
-                class.with_mut_unchecked().patch_by(|mut c| {
-                    c.members = new_members;
-                })?;
+                    .iter()
+                    .filter(|x| !is_class_cell(x))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                patch_docstring(&mut new_members);
 
-                is_merged = false;
-            }
-        }
+                methods.extend(new_members.iter().filter_map(|member| match member {
+                    ExpressionEnum::Function(function) => Some(function.mark.clone()),
+                    _ => None,
+                }));
+                if !new_members.is_empty() {
+                    class.with_mut_unchecked().patch_by(|mut c| {
+                        c.members = new_members;
+                    })?;
 
-        // merge the for loop
-        let for_query = this_expr.query::<For>();
-        let mut want_to_removes = Vec::new();
-        for for_loop in for_query {
-            if for_loop.body.is_empty() {
-                let (new_body, want_to_remove) =
-                    find_expr_among(this_expr, for_loop.from, for_loop.to)?;
-                for_loop.with_mut_unchecked().patch_by(|mut f| {
-                    f.body = new_body;
-                })?;
-                want_to_removes.extend(want_to_remove);
+                    is_merged = false;
+                }
             }
         }
-        commit_expr(this_expr, &want_to_removes)?;
 
         //dbg!(&this_expr);
         if is_merged {
@@ -202,6 +199,410 @@ fn merge(mark: &str, maps: &HashMap<String, (Expr, TraceBack)>) -> Result<Expr>
     Ok(this_expr.to_owned())
 }
 
+/// 把 for 循环后面的语句放进循环体, 只在同一个字节码对象里找, 偏移在别的对象里没有意义
+fn merge_for_loops(expr: &Expr) -> Result<()> {
+    let for_query = expr.query::<For>();
+    let mut want_to_removes = Vec::new();
+    for for_loop in for_query {
+        if for_loop.body.is_empty() {
+            let (new_body, want_to_remove) = find_expr_among(expr, for_loop.from, for_loop.to)?;
+            for_loop.with_mut_unchecked().patch_by(|mut f| {
+                f.body = new_body;
+            })?;
+            want_to_removes.extend(want_to_remove);
+        }
+    }
+    commit_expr(expr, &want_to_removes)
+}
+
+/// 反编译失败的字节码对象只输出一个注释, 包括失败的原因和它的反汇编
+fn failed_expr(mark: &str, instructions: &[OpcodeInstruction], reason: &str) -> Expr {
+    let mut lines = vec![format!(
+        "pyrev: failed to decompile {}: {}",
+        code_object_name(mark),
+        reason
+    )];
+    lines.extend(instructions.iter().map(OpcodeInstruction::disassemble));
+    Expr {
+        bodys: vec![ExpressionEnum::Comment(Comment {
+            lines,
+            ..Default::default()
+        })],
+    }
+}
+
+/// 反编译失败的字节码对象只剩下一个注释
+fn is_failed(expr: &Expr) -> bool {
+    matches!(expr.bodys.as_slice(), [ExpressionEnum::Comment(_)])
+}
+
+/// 找到字节码对象反编译的结果, 没有它的反汇编的时候当作反编译失败
+fn lookup<'a>(
+    maps: &'a HashMap<String, (Expr, TraceBack)>,
+    mark: &str,
+) -> Cow<'a, (Expr, TraceBack)> {
+    match maps.get(mark) {
+        Some(parsed) => Cow::Borrowed(parsed),
+        None => {
+            warn!("No disassembly of {}", mark);
+            Cow::Owned((
+                failed_expr(mark, &[], &format!("no disassembly of {}", mark)),
+                TraceBack::default(),
+            ))
+        }
+    }
+}
+
+/// 字节码对象的名字, 例如: `<code object f at 0x..., file "a.py", line 1>` -> `f`
+fn code_object_name(mark: &str) -> &str {
+    mark.strip_prefix("<code object ")
+        .and_then(|rest| rest.split(" at 0x").next())
+        .unwrap_or(mark)
+}
+
+/// 还原函数签名
+///
+/// 如果有 `dis.code_info` 的信息, 参数就是 varnames 的前几个,
+/// 否则只能从局部变量推断: 先读后写的局部变量是参数, 参数的下标总是在最前面
+fn patch_signature(function: &mut Function, trace: &TraceBack, is_method: bool) {
+    // the annotations are known when making the function
+    let annotations = function
+        .args
+        .iter()
+        .filter(|arg| arg.annotation.is_some())
+        .map(|arg| (arg.name.clone(), arg.annotation.clone()))
+        .collect::<Vec<_>>();
+    let annotation_of = |name: &str| {
+        annotations
+            .iter()
+            .find(|(arg, _)| arg == name)
+            .and_then(|(_, anno)| anno.clone())
+    };
+
+    let mut names = Vec::new();
+    if let Some(info) = trace.get_code_info() {
+        function.pos_only_count = info.pos_only_arg_count;
+        function.kw_only_count = info.kw_only_arg_count;
+        function.has_varargs = info.has_varargs();
+        function.has_varkeywords = info.has_varkeywords();
+        let count = info.arg_count
+            + info.kw_only_arg_count
+            + function.has_varargs as usize
+            + function.has_varkeywords as usize;
+        for index in 0..count {
+            let name = match info.var_names.get(index) {
+                Some(name) => name.clone(),
+                None => trace
+                    .get_local(&index)
+                    .map_or(format!("_{}", index), |local| local.name.clone()),
+            };
+            names.push(name);
+        }
+    } else {
+        let mut locals = trace
+            .get_locals()
+            .iter()
+            .filter(|(_, local)| local.is_arg)
+            .map(|(index, local)| (*index, local.name.clone()))
+            .collect::<Vec<_>>();
+        locals.sort_by_key(|(index, _)| *index);
+        let has_receiver = is_method && !function.decorators.iter().any(is_staticmethod);
+        // the first local variable which is not an argument follows the arguments
+        let first_local = trace
+            .get_locals()
+            .iter()
+            .filter(|(_, local)| !local.is_arg)
+            .map(|(index, _)| *index)
+            .min();
+        let count = first_local.unwrap_or_else(|| locals.last().map_or(0, |(index, _)| index + 1));
+        for index in 0..count {
+            match locals.iter().find(|(i, _)| *i == index) {
+                Some((_, name)) => names.push(name.clone()),
+                // the unused arguments
+                None if index == 0 && has_receiver => names.push(method_receiver(function)),
+                None => names.push(format!("_{}", index)),
+            }
+        }
+        if has_receiver && names.is_empty() {
+            names.push(method_receiver(function));
+        }
+        // the annotated or keyword-only arguments which are never used
+        for (name, _) in annotations.iter().filter(|(name, _)| name != "return") {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        for keyword in function.kw_defaults.iter() {
+            if let Some(name) = keyword.arg.as_ref()
+                && !names.contains(name)
+            {
+                names.push(name.clone());
+            }
+        }
+        // the unused arguments which have defaults
+        let positional_count = names
+            .iter()
+            .filter(|name| {
+                !function
+                    .kw_defaults
+                    .iter()
+                    .any(|keyword| keyword.arg.as_ref() == Some(name))
+            })
+            .count();
+        // without the local variables the unused arguments after the last one read in the
+        // body are unknown, the defaults belong to them rather than to the ones read
+        let unread = names[..positional_count]
+            .iter()
+            .rev()
+            .take_while(|name| !locals.iter().any(|(_, local)| local == *name))
+            .count();
+        let missing = match first_local {
+            Some(_) => function.defaults.len().saturating_sub(positional_count),
+            None => function.defaults.len().saturating_sub(unread),
+        };
+        for index in positional_count..positional_count + missing {
+            names.insert(index, format!("_{}", index));
+        }
+        // only the keyword-only arguments with defaults can be found
+        let (mut positional, kw_only): (Vec<_>, Vec<_>) = names.into_iter().partition(|name| {
+            !function
+                .kw_defaults
+                .iter()
+                .any(|keyword| keyword.arg.as_ref() == Some(name))
+        });
+        function.kw_only_count = kw_only.len();
+        positional.extend(kw_only);
+        names = positional;
+    }
+
+    let return_annotation = annotation_of("return");
+    function.args = names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| FastVariable {
+            index,
+            annotation: annotation_of(&name),
+            name,
+            ..Default::default()
+        })
+        .collect();
+    if return_annotation.is_some() {
+        function.args.push(FastVariable {
+            index: function.args.len(),
+            name: "return".to_string(),
+            annotation: return_annotation,
+            ..Default::default()
+        });
+    }
+}
+
+fn is_staticmethod(decorator: &ExpressionEnum) -> bool {
+    matches!(decorator, ExpressionEnum::BaseValue(BaseValue { value, .. }) if value == "staticmethod")
+}
+
+/// 没有用到的第一个参数, 一般是 self 或者 cls
+fn method_receiver(function: &Function) -> String {
+    let is_classmethod = function.decorators.iter().any(|decorator| {
+        matches!(decorator, ExpressionEnum::BaseValue(BaseValue { value, .. }) if value == "classmethod")
+    }) || ["__new__", "__init_subclass__", "__class_getitem__"].contains(&function.name.as_str());
+    if is_classmethod {
+        "cls".to_string()
+    } else {
+        "self".to_string()
+    }
+}
+
+/// 类体末尾由编译器生成的 `__classcell__ = __class__`, 供 `super()` 使用,
+/// 以及 3.12 的 `__classdictcell__` 和 3.13 的 `__static_attributes__`
+fn is_class_cell(expr: &ExpressionEnum) -> bool {
+    match expr {
+        ExpressionEnum::BaseValue(value) => value.value == "__class__",
+        ExpressionEnum::Assign(assign) => matches!(
+            assign.target.as_ref(),
+            ExpressionEnum::BaseValue(BaseValue { value, .. })
+                if ["__classcell__", "__classdictcell__", "__static_attributes__"]
+                    .contains(&value.as_str())
+        ),
+        ExpressionEnum::Return(ret) => is_class_cell(&ret.value),
+        // 3.11 的类体以 `return (__classcell__ := __class__)` 结束
+        ExpressionEnum::NamedExpr(named) => matches!(
+            named.target.as_ref(),
+            ExpressionEnum::BaseValue(BaseValue { value, .. }) if value == "__classcell__"
+        ),
+        _ => false,
+    }
+}
+
+/// 模块和类的文档字符串会存到 `__doc__` 里, 在 `__module__` 和 `__qualname__` 这些赋值之后
+fn patch_docstring(bodys: &mut [ExpressionEnum]) {
+    let is_name = |expr: &ExpressionEnum, names: &[&str]| match expr {
+        ExpressionEnum::BaseValue(name) => names.contains(&name.value.as_str()),
+        _ => false,
+    };
+    let first = bodys.iter_mut().find(|expr| {
+        !matches!(expr, ExpressionEnum::Assign(assign)
+            if is_name(&assign.target, &["__module__", "__qualname__", "__firstlineno__"]))
+    });
+    if let Some(expr) = first
+        && let ExpressionEnum::Assign(assign) = expr
+        && assign.chain.is_empty()
+        && is_name(&assign.target, &["__doc__"])
+        && let ExpressionEnum::BaseValue(value) = assign.values.as_ref()
+        && (value.value.starts_with('\'') || value.value.starts_with('"'))
+    {
+        *expr = ExpressionEnum::Docstring(Docstring {
+            value: value.value.clone(),
+            start_line: assign.start_line,
+            start_offset: assign.start_offset,
+            end_offset: assign.end_offset,
+        });
+    }
+}
+
+/// 去掉模块, 类和函数最后隐式的 `return None`, 包括最后的 if, try 和 with
+/// 每个分支末尾复制的那一份; 嵌套的定义也一样处理
+pub(crate) fn strip_implicit_returns(bodys: &mut Vec<ExpressionEnum>) {
+    strip_returns(bodys, false);
+}
+
+/// `in_function` 的时候才能写 `return`, 模块和类的代码块里复制的 `return None` 都要去掉
+fn strip_returns(bodys: &mut Vec<ExpressionEnum>, in_function: bool) {
+    strip_tail_return(bodys, in_function);
+    for expr in bodys.iter_mut() {
+        strip_nested_returns(expr);
+    }
+}
+
+/// 语句里面嵌套的函数和类
+fn strip_nested_returns(expr: &mut ExpressionEnum) {
+    let bodys = |bodys: &mut Vec<ExpressionEnum>| bodys.iter_mut().for_each(strip_nested_returns);
+    match expr {
+        // lambda, comprehensions and lazy values are expressions, never statements
+        ExpressionEnum::Function(function) if function.name.starts_with('<') => {
+            bodys(&mut function.bodys)
+        }
+        ExpressionEnum::Function(function) => strip_returns(&mut function.bodys, true),
+        ExpressionEnum::Class(class) => strip_returns(&mut class.members, false),
+        ExpressionEnum::If(if_else) => {
+            bodys(&mut if_else.body);
+            if let Some(or_else) = if_else.or_else.as_mut() {
+                strip_nested_returns(or_else);
+            }
+        }
+        ExpressionEnum::For(for_loop) => bodys(&mut for_loop.body),
+        ExpressionEnum::With(with) => bodys(&mut with.body),
+        ExpressionEnum::Try(try_expr) => {
+            bodys(&mut try_expr.body);
+            bodys(&mut try_expr.except);
+            bodys(&mut try_expr.or_else);
+            if let Some(finally) = try_expr.finally.as_mut() {
+                strip_nested_returns(finally);
+            }
+        }
+        ExpressionEnum::Except(except) => bodys(&mut except.body),
+        ExpressionEnum::Finally(finally) => bodys(&mut finally.body),
+        _ => {}
+    }
+}
+
+/// 代码块最后的 `return None`, 走到代码块的末尾也一样返回 None
+fn strip_tail_return(bodys: &mut Vec<ExpressionEnum>, in_function: bool) {
+    // 3.11 also returns at the end of the exception path of a trailing `with`
+    while bodys.last().is_some_and(is_none_return) {
+        bodys.pop();
+    }
+    match bodys.last_mut() {
+        Some(ExpressionEnum::If(if_else)) => strip_if_return(if_else, in_function),
+        Some(ExpressionEnum::With(with)) => strip_tail_return(&mut with.body, in_function),
+        Some(ExpressionEnum::Try(try_expr)) => {
+            // the end of the try block is not the end when there is an else block,
+            // and a return in the finally block swallows the exception
+            if try_expr.or_else.is_empty() {
+                strip_tail_return(&mut try_expr.body, in_function);
+            } else {
+                strip_tail_return(&mut try_expr.or_else, in_function);
+            }
+            for except in try_expr.except.iter_mut() {
+                if let ExpressionEnum::Except(except) = except {
+                    strip_tail_return(&mut except.body, in_function);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 每个分支末尾的 `return None`, 只剩下 `return None` 的 else 也去掉,
+/// 但是函数里 `if a: return` 后面还有别的代码的时候, 这个 `return` 要留着
+fn strip_if_return(if_else: &mut If, in_function: bool) {
+    if let Some(ExpressionEnum::If(or_else)) = if_else.or_else.as_deref_mut() {
+        strip_if_return(or_else, in_function);
+        if or_else.test.is_none() && or_else.body.is_empty() {
+            if_else.or_else = None;
+        }
+    }
+    let early_return = in_function
+        && if_else.test.is_some()
+        && if_else.or_else.is_some()
+        && !if_else.body.is_empty()
+        && if_else.body.iter().all(is_none_return);
+    if !early_return {
+        strip_tail_return(&mut if_else.body, in_function);
+    }
+}
+
+/// `return None`, 也就是 `return`
+fn is_none_return(expr: &ExpressionEnum) -> bool {
+    matches!(expr, ExpressionEnum::Return(ret)
+        if matches!(ret.value.as_ref(), ExpressionEnum::BaseValue(none) if none.value == "None"))
+}
+
+/// 函数的文档字符串不会被加载, 只能从 `dis.code_info` 的常量里找到
+fn function_docstring(
+    function: &Function,
+    function_expr: &Expr,
+    trace: &TraceBack,
+) -> Option<ExpressionEnum> {
+    if function.name.starts_with('<') {
+        return None;
+    }
+    let docstring = trace.get_code_info()?.docstring()?;
+    // 3.14 doesn't keep the first constant for the docstring
+    if function_expr
+        .query::<BaseValue>()
+        .iter()
+        .any(|x| &x.value == docstring)
+    {
+        return None;
+    }
+    Some(ExpressionEnum::Docstring(Docstring {
+        value: docstring.clone(),
+        ..Default::default()
+    }))
+}
+
+fn is_comprehension(name: &str) -> bool {
+    ["<listcomp>", "<setcomp>", "<dictcomp>", "<genexpr>"].contains(&name)
+}
+
+/// 函数开头的 `global` 和 `nonlocal` 声明
+fn declarations(trace: &TraceBack) -> Vec<ExpressionEnum> {
+    let mut declarations = Vec::new();
+    if !trace.get_globals().is_empty() {
+        declarations.push(ExpressionEnum::Global(Global {
+            names: trace.get_globals().clone(),
+            ..Default::default()
+        }));
+    }
+    if !trace.get_nonlocals().is_empty() {
+        declarations.push(ExpressionEnum::Nonlocal(Nonlocal {
+            names: trace.get_nonlocals().clone(),
+            ..Default::default()
+        }));
+    }
+    declarations
+}
+
 fn find_expr_among(
     expr: &Expr,
     offset: usize,
@@ -210,12 +611,9 @@ fn find_expr_among(
     let mut res = Vec::new();
     let mut want_to_remove = Vec::new();
     for (i, e) in expr.iter().enumerate() {
-        let (start, end) = e.get_offset();
-        #[cfg(debug_assertions)]
-        {
-            //dbg!(start, end, offset, target_offset);
-        }
-        if start > offset && end < target_offset {
+        // 3.12 moves the except blocks of a try statement to the end, so only the start counts
+        let (start, _) = e.get_offset();
+        if start > offset && start < target_offset {
             res.push(e.to_owned());
             want_to_remove.push(i);
         }
@@ -224,6 +622,10 @@ fn find_expr_among(
 }
 
 fn commit_expr(expr: &Expr, want_to_remove: &[usize]) -> Result<()> {
+    // remove from the back, so the indexes of the rest are not changed
+    let mut want_to_remove = want_to_remove.to_vec();
+    want_to_remove.sort_unstable();
+    want_to_remove.dedup();
     for idx in want_to_remove.iter().rev() {
         expr.with_mut_unchecked().patch_by(|mut e| {
             e.bodys.remove(*idx);
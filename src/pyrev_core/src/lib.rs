@@ -1,5 +1,3 @@
-#![feature(let_chains)]
-
 // 包括了opcode和opcodeInstruction的定义
 pub mod opcode;
 
@@ -15,12 +13,19 @@ pub mod decompile;
 // 分析opcode生成ast
 pub mod ast;
 
+// 在生成的代码里用注释标出字节码的来源
+pub mod annotate;
+
 #[allow(unused)]
 pub mod prelude {
-    pub use super::ast::{get_trace, ExprParser};
-    pub use super::common::{Colorize, IStream, Local, OStream, OrderMap, Result, TraceBack};
+    pub use super::annotate::{AnnotatedGenerator, Annotation};
+    pub use super::ast::{get_trace, resolve_generic, ExprParser};
+    pub use super::common::{
+        CodeInfo, Colorize, IStream, Local, OStream, OrderMap, Result, TraceBack,
+    };
     pub use super::decompile::{DecompiledCode, Decompiler};
     pub use super::opcode::{Opcode, OpcodeInstruction};
     pub use super::parse_opcode::{CodeObject, CodeObjectMap, OpcodeParser};
     pub use crate::{error, info, warn};
+    pub use pyrev_ast::{CodeGenerator, CodeStyle, PythonGenerator, QuoteStyle, TrailingComma};
 }
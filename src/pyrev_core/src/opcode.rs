@@ -1,4 +1,5 @@
-use std::cell::RefCell;
+use super::common::Result;
+use std::cell::{Cell, RefCell};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OpcodeInstruction {
@@ -12,6 +13,8 @@ pub struct OpcodeInstruction {
     pub is_jump_target: bool,
     // Version >= 3.11, save the begin and end of the source code
     pub positions: Vec<usize>,
+    // Version >= 3.11, the exception handler (from the ExceptionTable) which covers this instruction
+    pub handler: Cell<Option<usize>>,
 }
 
 impl OpcodeInstruction {
@@ -52,6 +55,7 @@ impl OpcodeInstruction {
             "RETURN_VALUE" => Opcode::ReturnValue,
             "RETURN_CONST" => Opcode::ReturnConst,
             "YIELD_VALUE" => Opcode::YieldValue,
+            "YIELD_FROM" => Opcode::YieldFrom,
             "SETUP_ANNOTATIONS" => Opcode::SetupAnnotations,
             "POP_EXCEPT" => Opcode::PopExcept,
             "RERAISE" => Opcode::Reraise,
@@ -60,6 +64,7 @@ impl OpcodeInstruction {
             "CHECK_EG_MATCH" => Opcode::CheckEgMatch,
             "WITH_EXCEPT_START" => Opcode::WithExceptStart,
             "LOAD_ASSERTION_ERROR" => Opcode::LoadAssertionError,
+            "LOAD_COMMON_CONSTANT" => Opcode::LoadCommonConstant,
             "LOAD_BUILD_CLASS" => Opcode::LoadBuildClass,
             "BEFORE_WITH" => Opcode::BeforeWith,
             "GET_LEN" => Opcode::GetLen,
@@ -89,6 +94,7 @@ impl OpcodeInstruction {
             "SET_UPDATE" => Opcode::SetUpdate,
             "DICT_UPDATE" => Opcode::DictUpdate,
             "DICT_MERGE" => Opcode::DictMerge,
+            "LIST_TO_TUPLE" => Opcode::ListToTuple,
             "LOAD_ATTR" => Opcode::LoadAttr,
             "LOAD_SUPER_ATTR" => Opcode::LoadSuperAttr,
             "COMPARE_OP" => Opcode::CompareOp,
@@ -99,6 +105,8 @@ impl OpcodeInstruction {
             "JUMP_FORWARD" => Opcode::JumpForward,
             "JUMP_BACKWARD" => Opcode::JumpBackward,
             "JUMP_BACKWARD_NO_INTERRUPT" => Opcode::JumpBackwardNoInterrupt,
+            "JUMP_IF_FALSE_OR_POP" => Opcode::JumpIfFalseOrPop,
+            "JUMP_IF_TRUE_OR_POP" => Opcode::JumpIfTrueOrPop,
             "POP_JUMP_IF_TRUE" | "POP_JUMP_FORWARD_IF_TRUE" | "POP_JUMP_BACKWARD_IF_TRUE" => {
                 Opcode::PopJumpIfTrue
             }
@@ -120,26 +128,33 @@ impl OpcodeInstruction {
             "DELETE_FAST" => Opcode::DeleteFast,
             "MAKE_CELL" => Opcode::MakeCell,
             "LOAD_CLOSURE" => Opcode::LoadClosure,
-            "LOAD_DEREF" => Opcode::LoadDeref,
+            // 3.11, a class body reads the variables of the enclosing function
+            "LOAD_DEREF" | "LOAD_CLASSDEREF" => Opcode::LoadDeref,
             "LOAD_FROM_DICT_OR_DEREF" => Opcode::LoadFromDictOrDeref,
             "STORE_DEREF" => Opcode::StoreDeref,
             "DELETE_DEREF" => Opcode::DeleteDeref,
             "COPY_FREE_VARS" => Opcode::CopyFreeVars,
             "RAISE_VARARGS" => Opcode::RaiseVarargs,
             "CALL" | "CALL_FUNCTION" => Opcode::Call,
+            "CALL_KW" => Opcode::CallKw,
+            "CALL_FUNCTION_KW" => Opcode::CallFunctionKw,
             "CALL_FUNCTION_EX" => Opcode::CallFunctionEx,
             "PUSH_NULL" => Opcode::PushNull,
             "KW_NAMES" => Opcode::KwNames,
             "MAKE_FUNCTION" => Opcode::MakeFunction,
+            "SET_FUNCTION_ATTRIBUTE" => Opcode::SetFunctionAttribute,
             "BUILD_SLICE" => Opcode::BuildSlice,
             "EXTENDED_ARG" => Opcode::ExtendedArg,
             "FORMAT_VALUE" => Opcode::FormatValue,
+            "CONVERT_VALUE" => Opcode::ConvertValue,
+            "FORMAT_SIMPLE" => Opcode::FormatSimple,
+            "FORMAT_WITH_SPEC" => Opcode::FormatWithSpec,
             "MATCH_CLASS" => Opcode::MatchClass,
             "RESUME" => Opcode::Resume,
             "RETURN_GENERATOR" => Opcode::ReturnGenerator,
             "SEND" => Opcode::Send,
-            "CALL_INTRINSIC1" => Opcode::CallIntrinsic1,
-            "CALL_INTRINSIC2" => Opcode::CallIntrinsic2,
+            "CALL_INTRINSIC_1" | "CALL_INTRINSIC1" => Opcode::CallIntrinsic1,
+            "CALL_INTRINSIC_2" | "CALL_INTRINSIC2" => Opcode::CallIntrinsic2,
             _ => Opcode::None,
         };
         Self {
@@ -151,12 +166,44 @@ impl OpcodeInstruction {
             starts_line,
             is_jump_target: false,
             positions: vec![],
+            handler: Cell::new(None),
         }
     }
 
     pub fn opcode(&self) -> Opcode {
         self.opcode.borrow().clone()
     }
+
+    /// 判断两条指令是否做了相同的操作(忽略偏移和跳转目标)
+    pub fn same_operation(&self, other: &Self) -> bool {
+        self.opname == other.opname && (self.opname.contains("JUMP") || self.argval == other.argval)
+    }
+
+    /// 跳转的目标, 例如: `POP_JUMP_IF_FALSE 3 (to 28)`,
+    /// 3.13 的标签 `(to L3)` 在解析的时候已经换成了偏移量
+    pub fn jump_target(&self) -> Result<usize> {
+        Ok(self
+            .argval
+            .as_deref()
+            .and_then(|argval| argval.strip_prefix("to "))
+            .ok_or(format!(
+                "[{}] No jump target, deviation is {}",
+                self.opname, self.offset
+            ))?
+            .parse::<usize>()?)
+    }
+
+    /// 像 dis 一样打印这条指令, 例如: `  12 LOAD_NAME 1 (print)`
+    pub fn disassemble(&self) -> String {
+        let mut line = format!("{:>4} {}", self.offset, self.opname);
+        if let Some(arg) = self.arg {
+            line.push_str(&format!(" {}", arg));
+        }
+        if let Some(argval) = self.argval.as_ref() {
+            line.push_str(&format!(" ({})", argval));
+        }
+        line
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -218,6 +265,8 @@ pub enum Opcode {
     ReturnConst,
     // 3.11 changed, 3.12 changed
     YieldValue, //
+    // 3.11 removed, replaced by SEND
+    YieldFrom,
     // 3.6 added
     SetupAnnotations,
     // 3.11 changed
@@ -234,6 +283,8 @@ pub enum Opcode {
     WithExceptStart,
     // 3.9 added
     LoadAssertionError,
+    // 3.14 added, e.g. AssertionError, NotImplementedError
+    LoadCommonConstant,
     LoadBuildClass,
     // 3.11 added
     BeforeWith,
@@ -275,6 +326,8 @@ pub enum Opcode {
     DictUpdate,
     // 3.9 added
     DictMerge,
+    // 3.9 added, 3.12 removed
+    ListToTuple,
     LoadAttr,
     // 3.12 added
     LoadSuperAttr,
@@ -290,6 +343,10 @@ pub enum Opcode {
     JumpBackward,
     // 3.11 added
     JumpBackwardNoInterrupt,
+    // 3.12 removed
+    JumpIfFalseOrPop,
+    // 3.12 removed
+    JumpIfTrueOrPop,
     PopJumpIfTrue,
     PopJumpIfFalse,
     PopJumpIfNotNone,
@@ -313,16 +370,28 @@ pub enum Opcode {
     RaiseVarargs,
     // 3.11 added
     Call,
+    // 3.13 added
+    CallKw,
+    // 3.11 removed
+    CallFunctionKw,
     CallFunctionEx,
     // 3.11 added
     PushNull,
     // 3.11 added
     KwNames,
     MakeFunction,
+    // 3.13 added
+    SetFunctionAttribute,
     BuildSlice,
     ExtendedArg,
-    // 3.6 added
+    // 3.6 added, 3.13 removed
     FormatValue,
+    // 3.13 added
+    ConvertValue,
+    // 3.13 added
+    FormatSimple,
+    // 3.13 added
+    FormatWithSpec,
     // 3.10 added
     MatchClass,
     // 3.11 added
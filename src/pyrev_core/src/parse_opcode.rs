@@ -1,12 +1,43 @@
-use super::{common::*, opcode::OpcodeInstruction};
+use super::{
+    common::*,
+    opcode::{Opcode, OpcodeInstruction},
+};
 use regex::Regex;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
 
 pub type ObjectMark = String;
 pub type LineNumber = usize;
 //pub type CodeObject = OrderMap<LineNumber, Vec<OpcodeInstruction>>;
-pub type CodeObject = Vec<OpcodeInstruction>;
 pub type CodeObjectMap = OrderMap<ObjectMark, CodeObject>;
 
+/// 一个对象主体的所有指令, 以及可能存在的 `dis.code_info` 信息
+#[derive(Debug, Clone, Default)]
+pub struct CodeObject {
+    pub instructions: Vec<OpcodeInstruction>,
+    pub info: Option<CodeInfo>,
+}
+
+impl CodeObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Deref for CodeObject {
+    type Target = Vec<OpcodeInstruction>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instructions
+    }
+}
+
+impl DerefMut for CodeObject {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.instructions
+    }
+}
+
 pub trait OpcodeParser {
     fn parse_opcode(&self) -> Result<CodeObjectMap>;
 }
@@ -19,15 +50,17 @@ where
     /// ObjectMark 是一个对象的标记(String), 就是字节码里面看到<>包裹的
     /// LineNumber 是一个行号(usize), 就是字节码里每一段左上角的数字
     /// CodeObject 是一个字节码对象, 里面包含了一个对象主体的所有指令
+    /// 如果对象的指令前面有 `dis.show_code` 打印的信息, 会一并记录下来
     fn parse_opcode(&self) -> Result<CodeObjectMap> {
         let reg = Regex::new(
             r#"(?s)(?x)
             (Disassembly\ of\ (?P<mark>[\S\ ]+):\s+)?      # mark  (optional)
-            (?P<line>\d+)?          # line  (optional)
+            (?P<line>\d+|--)?       # line  (optional, 3.13 prints `--` for no line)
             ([\ >]+)?
+            ((?P<label>L\d+):[\ ]+)? # label (3.13, optional)
             (?P<off>\d+)            # offset
-            [\ ]
-            (?P<bc>[A-Z0-9_]+)      # bytecode
+            [\ ]+
+            (?P<bc>[A-Z][A-Z_][A-Z0-9_]*) # bytecode, not a label like `L2`
             ([\ ]+)?
             (?P<a>\d+)?             # arg   (optional)
             [\ ]?
@@ -38,20 +71,45 @@ where
         let mut this_obj_mark = "<main>".to_string();
         let mut code_object = CodeObject::new();
         let mut code_object_map = CodeObjectMap::default();
+        let code_infos = parse_code_info(self.as_ref())?;
+        let mut code_infos_iter = code_infos.iter().peekable();
+        let mut code_info = None;
+        let exception_table = parse_exception_table(self.as_ref())?;
+        let mut exception_table_iter = exception_table.iter().peekable();
+        // 3.13 jumps to labels, e.g. `L3:` -> 56
+        let mut labels = HashMap::new();
         for cap in reg.captures_iter(self.as_ref()) {
+            let start = cap.get(0).map_or(0, |m| m.start());
+            if code_infos.iter().any(|(range, _)| range.contains(&start)) {
+                // the constants in the code info may look like instructions
+                continue;
+            }
+            // the code info is printed before the disassembly of its code object
+            while let Some((_, info)) = code_infos_iter.next_if(|(range, _)| range.end <= start) {
+                code_info = Some(info.clone());
+            }
             let mark = cap.name("mark").map_or("", |m| m.as_str());
             let line = cap.name("line").map_or("", |m| m.as_str());
             let off = cap.name("off").map_or("", |m| m.as_str());
             let bc = cap.name("bc").map_or("", |m| m.as_str());
             let a = cap.name("a").map_or("", |m| m.as_str());
             let ra = cap.name("ra").map_or("", |m| m.as_str());
+            let label = cap.name("label").map(|m| m.as_str().to_string());
             // turn to next mark
             //println!("mark: {}", mark);
             if !mark.is_empty() {
+                // the exception table is printed after the disassembly of its code object
+                let entries =
+                    std::iter::from_fn(|| exception_table_iter.next_if(|(pos, _)| *pos < start));
+                finish_code_object(&mut code_object, entries.map(|(_, entry)| entry), &labels);
+                labels.clear();
                 code_object_map.insert(this_obj_mark.clone(), code_object);
                 this_obj_mark = mark.to_string();
                 code_object = CodeObject::new();
             }
+            if code_info.is_some() {
+                code_object.info = code_info.take();
+            }
             let mut instruction = OpcodeInstruction::new(
                 bc,
                 a.parse::<usize>().ok(),
@@ -63,6 +121,9 @@ where
                 off.parse::<usize>()?,
                 line.parse::<LineNumber>().ok(),
             );
+            if let Some(label) = label {
+                labels.insert(label, instruction.offset);
+            }
             if let Ok(line) = line.parse::<LineNumber>() {
                 last_line = line;
             } else {
@@ -71,8 +132,476 @@ where
             code_object.push(instruction);
         }
         if !code_object_map.contains_key(&this_obj_mark) {
+            let entries = exception_table_iter.map(|(_, entry)| entry);
+            finish_code_object(&mut code_object, entries, &labels);
             code_object_map.insert(this_obj_mark, code_object);
         }
         Ok(code_object_map)
     }
 }
+
+/// 一个代码对象的指令都读完之后, 把跳转的标签换成偏移量, 标上异常表, 整理异常处理块
+fn finish_code_object<'a>(
+    code_object: &mut CodeObject,
+    entries: impl Iterator<Item = &'a ExceptionEntry>,
+    labels: &HashMap<String, usize>,
+) {
+    for instruction in code_object.iter_mut() {
+        // `to L3` -> `to 56`
+        if let Some(offset) = instruction
+            .argval
+            .as_deref()
+            .and_then(|argval| argval.strip_prefix("to "))
+            .and_then(|label| labels.get(label))
+        {
+            instruction.argval = Some(format!("to {}", offset));
+        }
+    }
+    for entry in entries {
+        entry.apply(code_object, labels);
+    }
+    hide_with_handlers(code_object);
+    inline_exception_handlers(code_object);
+    resolve_kw_names(code_object);
+}
+
+/// 3.11 的 `KW_NAMES` 只打印常量的下标, 例如 `KW_NAMES 1`,
+/// 关键字参数的名字要从 `dis.code_info` 的常量里找, 例如 `1: ('end',)`
+fn resolve_kw_names(code_object: &mut CodeObject) {
+    let Some(info) = code_object.info.clone() else {
+        return;
+    };
+    for instruction in code_object.iter_mut() {
+        if instruction.opcode() == Opcode::KwNames
+            && instruction.argval.is_none()
+            && let Some(names) = instruction.arg.and_then(|arg| info.constants.get(arg))
+        {
+            instruction.argval = Some(names.clone());
+        }
+    }
+}
+
+/// 找到所有 `dis.code_info` 的文本块, 返回它们的范围和解析出的信息
+///
+/// Name:              f
+/// Filename:          test.py
+/// Argument count:    2
+/// Positional-only arguments: 1
+/// Kw-only arguments: 2
+/// Number of locals:  5
+/// Stack size:        2
+/// Flags:             OPTIMIZED, NEWLOCALS, VARARGS, VARKEYWORDS
+/// Constants:
+///    0: None
+/// Variable names:
+///    0: a
+///    ...
+fn parse_code_info(text: &str) -> Result<Vec<(std::ops::Range<usize>, CodeInfo)>> {
+    // `Flags: ...` or `   0: a`
+    let reg = Regex::new(r"^([A-Z][A-Za-z -]*:|\s+\d+: )")?;
+    let mut code_infos = Vec::new();
+    let mut lines = text
+        .split_inclusive('\n')
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some((start, line))
+        })
+        .peekable();
+    while let Some((start, line)) = lines.next() {
+        if !line.starts_with("Name:") {
+            continue;
+        }
+        let mut end = start + line.len();
+        let mut info = CodeInfo::default();
+        let mut section = "";
+        while let Some((offset, line)) = lines.next_if(|(_, line)| reg.is_match(line)) {
+            end = offset + line.len();
+            let line = line.trim_end();
+            if line.starts_with(' ') {
+                // `   0: a`
+                if let Some((_, value)) = line.split_once(": ") {
+                    match section {
+                        "Constants" => info.constants.push(value.to_string()),
+                        "Variable names" => info.var_names.push(value.to_string()),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            let (key, value) = line.split_once(':').unwrap_or_default();
+            let value = value.trim();
+            match key {
+                "Argument count" => info.arg_count = value.parse().unwrap_or_default(),
+                "Positional-only arguments" => {
+                    info.pos_only_arg_count = value.parse().unwrap_or_default()
+                }
+                "Kw-only arguments" => info.kw_only_arg_count = value.parse().unwrap_or_default(),
+                "Flags" => info.flags = value.split(", ").map(|x| x.to_string()).collect(),
+                _ => {}
+            }
+            section = key;
+        }
+        code_infos.push((start..end, info));
+    }
+    Ok(code_infos)
+}
+
+/// 异常表中的一项, 从 start 到 end (包含) 的指令抛出异常时跳转到 target
+///
+/// 3.13 打印的是标签, 而且 end 是这一段后面的那条指令 (不包含)
+struct ExceptionEntry {
+    start: String,
+    end: String,
+    target: String,
+}
+
+impl ExceptionEntry {
+    fn apply(&self, instructions: &[OpcodeInstruction], labels: &HashMap<String, usize>) {
+        let offset = |position: &String| position.parse().ok().or(labels.get(position).copied());
+        let (Some(start), Some(end), Some(target)) =
+            (offset(&self.start), offset(&self.end), offset(&self.target))
+        else {
+            return;
+        };
+        let range = if labels.contains_key(&self.end) {
+            start..end
+        } else {
+            start..end + 1
+        };
+        instructions
+            .iter()
+            .filter(|x| range.contains(&x.offset))
+            .for_each(|x| x.handler.set(Some(target)));
+    }
+}
+
+/// 找到所有的异常表项, 返回它们在文本中的位置和内容 (3.11+)
+///
+/// ExceptionTable:
+///   4 to 22 -> 46 [0]
+///   46 to 62 -> 70 [1] lasti
+///   L4 to L5 -> L8 [0]
+fn parse_exception_table(text: &str) -> Result<Vec<(usize, ExceptionEntry)>> {
+    let reg = Regex::new(r"(?m)^\s+(L?\d+) to (L?\d+) -> (L?\d+) \[\d+\]( lasti)?\s*$")?;
+    let mut entries = Vec::new();
+    for cap in reg.captures_iter(text) {
+        entries.push((
+            cap.get(0).map_or(0, |m| m.start()),
+            ExceptionEntry {
+                start: cap[1].to_string(),
+                end: cap[2].to_string(),
+                target: cap[3].to_string(),
+            },
+        ));
+    }
+    Ok(entries)
+}
+
+/// try 语句的异常处理块的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandlerKind {
+    Except,
+    ExceptStar,
+    Finally,
+}
+
+/// try 语句的一个异常处理块, 从 `PUSH_EXC_INFO` 开始, 到清理块的 `RERAISE 1` 结束
+///
+/// start 和 end 是指令的下标 (不包含 end), cleanup 是清理块 `COPY 3; POP_EXCEPT; RERAISE 1` 的下标
+#[derive(Debug, Clone, Copy)]
+pub struct HandlerRegion {
+    pub start: usize,
+    pub cleanup: usize,
+    pub end: usize,
+    pub kind: HandlerKind,
+}
+
+/// 找到跳转到 handler 的异常处理块, `with` 语句的异常处理块不算在内
+pub fn handler_region(instructions: &[OpcodeInstruction], handler: usize) -> Option<HandlerRegion> {
+    let start = instructions.iter().position(|x| x.offset == handler)?;
+    let opnames = |idx: usize, len: usize| {
+        instructions
+            .get(idx..idx + len)
+            .map(|x| x.iter().map(|x| x.opname.as_str()).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    if instructions[start].opname != "PUSH_EXC_INFO"
+        || opnames(start + 1, 1) == ["WITH_EXCEPT_START"]
+    {
+        return None;
+    }
+    let cleanup_offset = instructions[start].handler.get()?;
+    let cleanup = instructions
+        .iter()
+        .position(|x| x.offset == cleanup_offset)?;
+    if cleanup <= start || opnames(cleanup, 3) != ["COPY", "POP_EXCEPT", "RERAISE"] {
+        return None;
+    }
+    // except* keeps a list of the raised exceptions
+    let kind = if opnames(start + 1, 3) == ["COPY", "BUILD_LIST", "SWAP"]
+        || opnames(start + 1, 2) == ["BUILD_LIST", "COPY"]
+    {
+        HandlerKind::ExceptStar
+    } else if opnames(start + 1, 1) == ["POP_TOP"] {
+        // bare `except:`
+        HandlerKind::Except
+    } else if let Some(check) =
+        (start + 1..cleanup).find(|&i| instructions[i].opname == "CHECK_EXC_MATCH")
+        && instructions[start + 1..check]
+            .iter()
+            .all(|x| x.handler.get() == Some(cleanup_offset))
+    {
+        // the exception is loaded in the handler itself, not in a nested try statement
+        HandlerKind::Except
+    } else {
+        HandlerKind::Finally
+    };
+    Some(HandlerRegion {
+        start,
+        cleanup,
+        end: cleanup + 3,
+        kind,
+    })
+}
+
+/// with 语句在异常时调用 `__exit__` 的处理块不会出现在源码中, 直接隐藏掉:
+/// PUSH_EXC_INFO, WITH_EXCEPT_START, ..., POP_TOP, POP_EXCEPT, POP_TOP, POP_TOP
+/// 以及它的清理块 COPY 3, POP_EXCEPT, RERAISE 1
+/// 3.12 的处理块在代码对象的最后, 清理块前面还会有一条跳回去(或者复制的 return)的指令
+fn hide_with_handlers(instructions: &[OpcodeInstruction]) {
+    for (idx, instruction) in instructions.iter().enumerate() {
+        if instruction.opname != "PUSH_EXC_INFO"
+            || instructions
+                .get(idx + 1)
+                .is_none_or(|x| x.opname != "WITH_EXCEPT_START")
+        {
+            continue;
+        }
+        let Some(cleanup) = instruction
+            .handler
+            .get()
+            .and_then(|handler| instructions.iter().position(|x| x.offset == handler))
+        else {
+            continue;
+        };
+        let Some(exit) = instructions[idx..]
+            .windows(4)
+            .position(|x| {
+                x.iter().map(|x| x.opname.as_str()).eq([
+                    "POP_TOP",
+                    "POP_EXCEPT",
+                    "POP_TOP",
+                    "POP_TOP",
+                ])
+            })
+            .map(|i| idx + i + 3)
+        else {
+            continue;
+        };
+        let end = exit.max(cleanup + 2).min(instructions.len() - 1);
+        // 3.11: the normal exit jumps over the handler
+        if idx > 0
+            && instructions[idx - 1].opname == "JUMP_FORWARD"
+            && unconditional_jump_target(&instructions[idx - 1])
+                == instructions.get(end + 1).map(|x| x.offset)
+        {
+            instructions[idx - 1].opcode.replace(Opcode::Nop);
+        }
+        for x in instructions[idx..=end].iter() {
+            x.opcode.replace(Opcode::Nop);
+            x.handler.set(None);
+        }
+    }
+}
+
+/// 3.12 把 try 语句的异常处理块移到了代码对象的最后 (最后一个 return 后面),
+/// 这里把它们移回 try 语句的后面, 和 3.11 的布局保持一致
+fn inline_exception_handlers(instructions: &mut Vec<OpcodeInstruction>) {
+    // only the blocks which can't be reached by falling through
+    let mut handlers = Vec::new();
+    for handler in instructions.iter().filter_map(|x| x.handler.get()) {
+        if !handlers.contains(&handler)
+            && let Some(region) = handler_region(instructions, handler)
+            && region.start > 0
+            && matches!(
+                instructions[region.start - 1].opname.as_str(),
+                "RETURN_VALUE" | "RETURN_CONST" | "RERAISE" | "RAISE_VARARGS"
+            )
+        {
+            handlers.push(handler);
+        }
+    }
+    // a block may move again after the blocks nested in its try statement are moved
+    for _ in 0..=handlers.len() {
+        let mut moved = false;
+        for &handler in handlers.iter() {
+            moved |= move_exception_handler(instructions, handler);
+        }
+        if !moved {
+            break;
+        }
+    }
+    move_unprotected_finally(instructions);
+}
+
+/// `try: pass` 的 body 没有被保护, 没有指令跳转到它的 finally 块, 3.12 把这个块留在了最后,
+/// 这里把它移到 body 后面的 finally 块的副本后面, 和 3.11 一样:
+/// NOP; <finally 块的副本>; PUSH_EXC_INFO; <finally 块>; ...
+fn move_unprotected_finally(instructions: &mut Vec<OpcodeInstruction>) {
+    let mut idx = 0;
+    while let Some(start) =
+        (idx..instructions.len()).find(|&i| instructions[i].opname == "PUSH_EXC_INFO")
+    {
+        idx = start + 1;
+        let handler = instructions[start].offset;
+        if start == 0
+            || instructions
+                .iter()
+                .any(|x| x.handler.get() == Some(handler))
+            || !matches!(
+                instructions[start - 1].opname.as_str(),
+                "RETURN_VALUE" | "RETURN_CONST" | "RERAISE" | "RAISE_VARARGS"
+            )
+        {
+            continue;
+        }
+        let Some(region) = handler_region(instructions, handler)
+            .filter(|region| region.kind == HandlerKind::Finally)
+        else {
+            continue;
+        };
+        let body = &instructions[region.start + 1..region.cleanup - 1];
+        // the copy right after a `NOP` (the line of `try:` or `pass`), on the same lines
+        let Some(end) = (1..region.start)
+            .filter(|&i| {
+                instructions[i - 1].opname == "NOP"
+                    && body.first().map(|x| x.starts_line) == Some(instructions[i].starts_line)
+            })
+            .find_map(|i| copy_end(instructions, i, body))
+        else {
+            continue;
+        };
+        // the statement after it is the last one
+        if end + 1 >= region.start {
+            continue;
+        }
+        let block = instructions
+            .drain(region.start..region.end)
+            .collect::<Vec<_>>();
+        instructions.splice(end..end, block);
+    }
+}
+
+/// 移动一个异常处理块, 返回它是否被移动了
+fn move_exception_handler(instructions: &mut Vec<OpcodeInstruction>, handler: usize) -> bool {
+    let Some(region) = handler_region(instructions, handler) else {
+        return false;
+    };
+    let block = instructions
+        .drain(region.start..region.end)
+        .collect::<Vec<_>>();
+    let Some(last) = instructions
+        .iter()
+        .rposition(|x| x.handler.get() == Some(handler))
+    else {
+        instructions.splice(region.start..region.start, block);
+        return false;
+    };
+    let insert = if region.kind == HandlerKind::Finally {
+        // the finally body is copied to the normal path, right after the try body
+        let body = block.get(1..block.len() - 4).unwrap_or_default();
+        let insert = copy_end(instructions, last + 1, body).unwrap_or(last + 1);
+        // `return value` in the try body: the value is loaded before the copy
+        match instructions.get(insert) {
+            Some(x) if x.opname == "RETURN_VALUE" => insert + 1,
+            _ => insert,
+        }
+    } else {
+        // the handlers jump back to the end of the try statement (after the else block)
+        let mut targets = Vec::<(usize, usize)>::new();
+        for target in block
+            .iter()
+            .filter_map(unconditional_jump_target)
+            .filter(|target| !block.iter().any(|x| x.offset == *target))
+        {
+            match targets.iter_mut().find(|(x, _)| *x == target) {
+                Some((_, count)) => *count += 1,
+                None => targets.push((target, 1)),
+            }
+        }
+        let rest = &instructions[last + 1..];
+        let end = match targets.iter().max_by_key(|(_, count)| *count) {
+            Some(&(target, _)) => rest
+                .iter()
+                .position(|x| x.offset == target || unconditional_jump_target(x) == Some(target)),
+            // the try statement is the last one, the handlers end with a copy of the return,
+            // `RETURN_CONST` (3.12) or `LOAD_CONST; RETURN_VALUE` (3.11, the block is already there)
+            None => block
+                .iter()
+                .position(|x| x.opname.starts_with("RETURN_"))
+                .and_then(|i| {
+                    let tail = match block[i].opname.as_str() {
+                        "RETURN_VALUE" => block.get(i.checked_sub(1)?..=i)?,
+                        _ => &block[i..=i],
+                    };
+                    rest.iter()
+                        .position(|x| {
+                            unconditional_jump_target(x).is_some()
+                                || x.opname.starts_with("RETURN_")
+                        })
+                        .filter(|&idx| {
+                            idx + 1 >= tail.len()
+                                && rest[idx + 1 - tail.len()..=idx]
+                                    .iter()
+                                    .zip(tail)
+                                    .all(|(x, y)| x.same_operation(y))
+                        })
+                        .map(|idx| idx + 1 - tail.len())
+                }),
+        };
+        end.map_or(last + 1, |idx| last + 1 + idx)
+    };
+    instructions.splice(insert..insert, block);
+    insert != region.start
+}
+
+/// 如果 start 处是 finally 块 body 的一个副本, 返回副本结束的下标
+///
+/// 副本里的跳转可能和 finally 块里的不同 (例如 3.12 跳过异常处理块的跳转), 所以比较时忽略无条件跳转
+pub fn copy_end(
+    instructions: &[OpcodeInstruction],
+    start: usize,
+    body: &[OpcodeInstruction],
+) -> Option<usize> {
+    let mut body = body
+        .iter()
+        .filter(|x| unconditional_jump_target(x).is_none())
+        .peekable();
+    body.peek()?;
+    let mut idx = start;
+    while let Some(expected) = body.peek() {
+        let instruction = instructions.get(idx)?;
+        if idx > start && unconditional_jump_target(instruction).is_some() {
+            idx += 1;
+            continue;
+        }
+        if !instruction.same_operation(expected) {
+            return None;
+        }
+        body.next();
+        idx += 1;
+    }
+    Some(idx)
+}
+
+/// 无条件跳转的目标
+pub fn unconditional_jump_target(instruction: &OpcodeInstruction) -> Option<usize> {
+    if !matches!(
+        instruction.opname.as_str(),
+        "JUMP_FORWARD" | "JUMP_BACKWARD" | "JUMP_BACKWARD_NO_INTERRUPT" | "JUMP_ABSOLUTE"
+    ) {
+        return None;
+    }
+    instruction.jump_target().ok()
+}